@@ -3,7 +3,6 @@
 /// Run with: `cargo run --release -- --plugin-uri=${PLUGIN_URI}`
 use livi::event::LV2AtomSequence;
 use log::{debug, error, info, warn};
-use std::convert::TryFrom;
 use structopt::StructOpt;
 
 /// The configuration for the backend.
@@ -41,7 +40,7 @@ fn main() {
         .unwrap_or_else(|| panic!("Could not find plugin with URI {}", config.plugin_uri));
 
     let (client, status) =
-        jack::Client::new(&plugin.name(), jack::ClientOptions::NO_START_SERVER).unwrap();
+        jack::Client::new(plugin.name(), jack::ClientOptions::NO_START_SERVER).unwrap();
     info!("Created jack client {:?} with status {:?}.", client, status);
 
     let process_handler = Processor::new(&livi, plugin, &client, config.volume);
@@ -77,6 +76,7 @@ impl Processor {
         let features = world.build_features(livi::FeaturesBuilder {
             min_block_length: 1,
             max_block_length: buffer_size,
+            ..livi::FeaturesBuilder::default()
         });
         #[allow(clippy::cast_precision_loss)]
         let plugin_instance = unsafe {
@@ -183,14 +183,13 @@ impl jack::ProcessHandler for Processor {
             copy_midi_in_to_atom_sequence(src, dst, ps, self.midi_urid)
         }
 
-        let ports = livi::PortConnections {
-            audio_inputs: self.audio_inputs.iter().map(|p| p.as_slice(ps)),
-            audio_outputs: self.audio_outputs.iter_mut().map(|p| p.as_mut_slice(ps)),
-            atom_sequence_inputs: self.event_inputs.iter().map(|(_, e)| e),
-            atom_sequence_outputs: self.event_outputs.iter_mut().map(|(_, e)| e),
-            cv_inputs: self.cv_inputs.iter().map(|p| p.as_slice(ps)),
-            cv_outputs: self.cv_outputs.iter_mut().map(|p| p.as_mut_slice(ps)),
-        };
+        let ports = livi::EmptyPortConnections::new()
+            .with_audio_inputs(self.audio_inputs.iter().map(|p| p.as_slice(ps)))
+            .with_audio_outputs(self.audio_outputs.iter_mut().map(|p| p.as_mut_slice(ps)))
+            .with_atom_sequence_inputs(self.event_inputs.iter().map(|(_, e)| e))
+            .with_atom_sequence_outputs(self.event_outputs.iter_mut().map(|(_, e)| e))
+            .with_cv_inputs(self.cv_inputs.iter().map(|p| p.as_slice(ps)))
+            .with_cv_outputs(self.cv_outputs.iter_mut().map(|p| p.as_mut_slice(ps)));
         match unsafe { self.plugin.run(ps.n_frames() as usize, ports) } {
             Ok(()) => (),
             Err(e) => {
@@ -250,8 +249,18 @@ fn copy_atom_sequence_to_midi_out(
             );
             continue;
         }
+        let time = match event.time_in_frames_u32() {
+            Some(t) => t,
+            None => {
+                debug!(
+                    "Skipping midi event with out of range time: {}",
+                    event.event.time_in_frames
+                );
+                continue;
+            }
+        };
         let jack_event = jack::RawMidi {
-            time: u32::try_from(event.event.time_in_frames).unwrap(),
+            time,
             bytes: event.data,
         };
         match writer.write(&jack_event) {
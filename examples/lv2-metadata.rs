@@ -19,7 +19,10 @@ fn main() {
         Some(plugin) => println!("{plugin:#?}"),
         None => {
             error!("Could not find --plugin-uri {:?}", &config.plugin_uri);
-            let plugin_uris = world.iter_plugins().map(|p| p.uri()).collect::<Vec<_>>();
+            let plugin_uris = world
+                .iter_plugins()
+                .map(|p| p.uri().to_string())
+                .collect::<Vec<_>>();
             println!("Plugins: {plugin_uris:?}");
         }
     }
@@ -0,0 +1,95 @@
+/// midi-render renders a MIDI file through an LV2 instrument into a WAV file.
+///
+/// Run with: `cargo run --release --features=midi-file --example midi-render --
+/// --midi-file=${MIDI_FILE} --output=${WAV_FILE}`
+use log::info;
+use std::convert::TryFrom;
+use structopt::StructOpt;
+
+/// The configuration for the renderer.
+#[derive(StructOpt, Debug)]
+struct Configuration {
+    /// The uri of the instrument plugin to render with.
+    /// To see the set of available plugins, use `lv2ls`.
+    #[structopt(
+        long = "plugin-uri",
+        default_value = "http://drobilla.net/plugins/mda/EPiano"
+    )]
+    plugin_uri: String,
+
+    /// The MIDI file to render.
+    #[structopt(long = "midi-file")]
+    midi_file: std::path::PathBuf,
+
+    /// The WAV file to write the audio to.
+    #[structopt(long = "output", default_value = "output.wav")]
+    output: std::path::PathBuf,
+
+    /// The sample rate to render at.
+    #[structopt(long = "sample-rate", default_value = "44100")]
+    sample_rate: u32,
+
+    /// The number of seconds to keep rendering after the last MIDI event so
+    /// that notes can ring out.
+    #[structopt(long = "tail-seconds", default_value = "2")]
+    tail_seconds: u32,
+}
+
+const BLOCK_SIZE: usize = 512;
+
+fn main() {
+    let config = Configuration::from_args();
+    env_logger::builder().init();
+
+    let midi_data = std::fs::read(&config.midi_file).unwrap();
+    let events = livi::midi::file::parse(&midi_data, f64::from(config.sample_rate)).unwrap();
+    let tail_frames = u64::from(config.tail_seconds) * u64::from(config.sample_rate);
+    let end_frame = events
+        .last()
+        .map(|(frame, _)| *frame)
+        .unwrap_or(0)
+        .checked_add(tail_frames)
+        .expect("The MIDI file is too long to render.");
+    let total_samples = usize::try_from(end_frame).expect("The MIDI file is too long to render.");
+    info!(
+        "Loaded {} midi events from {:?}.",
+        events.len(),
+        config.midi_file
+    );
+
+    let world = livi::World::new();
+    let plugin = world
+        .plugin_by_uri(&config.plugin_uri)
+        .unwrap_or_else(|| panic!("Could not find plugin with URI {}", config.plugin_uri));
+    let features = world.build_features(livi::FeaturesBuilder {
+        min_block_length: 1,
+        max_block_length: BLOCK_SIZE,
+        ..livi::FeaturesBuilder::default()
+    });
+    let mut instance = unsafe {
+        plugin
+            .instantiate(features, f64::from(config.sample_rate))
+            .unwrap()
+    };
+    let rendered = unsafe { instance.render(&events, total_samples).unwrap() };
+
+    // WAV data is interleaved by channel.
+    let frames = rendered.first().map(Vec::len).unwrap_or(0);
+    let interleaved: Vec<f32> = (0..frames)
+        .flat_map(|frame| rendered.iter().map(move |channel| channel[frame]))
+        .collect();
+    let header = wav::Header::new(
+        wav::header::WAV_FORMAT_IEEE_FLOAT,
+        u16::try_from(rendered.len()).expect("The plugin has too many audio outputs."),
+        config.sample_rate,
+        32,
+    );
+    let mut out_file = std::fs::File::create(&config.output).unwrap();
+    wav::write(
+        header,
+        &wav::BitDepth::ThirtyTwoFloat(interleaved),
+        &mut out_file,
+    )
+    .unwrap();
+    info!("Wrote {} frames to {:?}.", frames, config.output);
+}
@@ -0,0 +1,259 @@
+use std::sync::Arc;
+
+use crate::error::{PluginChainError, RunError};
+use crate::plugin::NonAudioBuffers;
+use crate::{Features, Instance, PortType};
+
+/// A serial chain of plugin instances. The audio outputs of each instance are
+/// fed into the audio inputs of the next instance.
+///
+/// Atom sequence inputs of the instances are connected to empty sequences and
+/// CV inputs are connected to silent buffers.
+///
+/// #### Example usage:
+/// ```
+/// # let world = livi::World::new();
+/// # let features = world.build_features(livi::FeaturesBuilder::default());
+/// # let plugin = world
+/// #     .plugin_by_uri("http://drobilla.net/plugins/mda/Delay")
+/// #     .unwrap();
+/// let instances = (0..2)
+///     .map(|_| unsafe { plugin.instantiate(features.clone(), 44100.0).unwrap() })
+///     .collect();
+/// let mut chain = livi::PluginChain::new(instances).unwrap();
+/// let inputs = [vec![0.0; 64], vec![0.0; 64]];
+/// let mut outputs = [vec![0.0; 64], vec![0.0; 64]];
+/// unsafe {
+///     chain
+///         .run(
+///             64,
+///             inputs.iter().map(|i| i.as_slice()),
+///             outputs.iter_mut().map(|o| o.as_mut_slice()),
+///         )
+///         .unwrap()
+/// };
+/// ```
+pub struct PluginChain {
+    links: Vec<Link>,
+    // Scratch buffers for the audio between the instances. Each buffer holds
+    // the outputs of every other instance.
+    scratch: [Vec<Vec<f32>>; 2],
+}
+
+/// An instance and the buffers for its ports that are not part of the audio
+/// chain.
+struct Link {
+    instance: Instance,
+    buffers: NonAudioBuffers,
+}
+
+impl PluginChain {
+    /// Create a new chain that runs `instances` in order.
+    ///
+    /// # Errors
+    /// Returns an error if `instances` is empty or if the number of audio
+    /// outputs of an instance does not match the number of audio inputs of the
+    /// instance that follows it.
+    pub fn new(instances: Vec<Instance>) -> Result<PluginChain, PluginChainError> {
+        if instances.is_empty() {
+            return Err(PluginChainError::Empty);
+        }
+        for (index, pair) in instances.windows(2).enumerate() {
+            let outputs = pair[0].port_counts_for_type(PortType::AudioOutput);
+            let inputs = pair[1].port_counts_for_type(PortType::AudioInput);
+            if outputs != inputs {
+                return Err(PluginChainError::ChannelCountMismatch {
+                    index,
+                    outputs,
+                    inputs,
+                });
+            }
+        }
+        let max_block_size = instances
+            .iter()
+            .map(Instance::max_block_size)
+            .max()
+            .unwrap_or(0);
+        let max_channels = instances
+            .iter()
+            .map(|i| i.port_counts_for_type(PortType::AudioOutput))
+            .max()
+            .unwrap_or(0);
+        let scratch_buffer = vec![vec![0.0; max_block_size]; max_channels];
+        let links = instances
+            .into_iter()
+            .map(|instance| Link {
+                buffers: NonAudioBuffers::new(&instance),
+                instance,
+            })
+            .collect();
+        Ok(PluginChain {
+            links,
+            scratch: [scratch_buffer.clone(), scratch_buffer],
+        })
+    }
+
+    /// Iterate over the instances in the chain.
+    pub fn iter_instances(&self) -> impl Iterator<Item = &'_ Instance> {
+        self.links.iter().map(|l| &l.instance)
+    }
+
+    /// Iterate mutably over the instances in the chain. This can be used to set
+    /// control values.
+    pub fn iter_instances_mut(&mut self) -> impl Iterator<Item = &'_ mut Instance> {
+        self.links.iter_mut().map(|l| &mut l.instance)
+    }
+
+    /// The total latency of the chain in samples. This is the sum of the
+    /// latencies reported by each instance during its last run.
+    pub fn latency(&self) -> u32 {
+        self.links
+            .iter()
+            .map(|l| l.instance.latency_samples())
+            .sum()
+    }
+
+    /// Run the workers of all instances in the chain. Each distinct
+    /// `WorkerManager` is run once.
+    pub fn run_workers(&self) {
+        let mut features: Vec<&Arc<Features>> = Vec::new();
+        for link in self.links.iter() {
+            let f = link.instance.features();
+            if !features.iter().any(|other| Arc::ptr_eq(other, f)) {
+                features.push(f);
+            }
+        }
+        for f in features {
+            f.worker_manager().run_workers();
+        }
+    }
+
+    /// Run `samples` samples of `audio_inputs` through the chain and write the
+    /// result to `audio_outputs`.
+    ///
+    /// # Safety
+    /// Running plugin code is unsafe.
+    ///
+    /// # Errors
+    /// Returns an error if any instance in the chain could not be run. The
+    /// number of `audio_inputs` must match the first instance and the number
+    /// of `audio_outputs` must match the last instance.
+    pub unsafe fn run<'a, AudioInputs, AudioOutputs>(
+        &mut self,
+        samples: usize,
+        audio_inputs: AudioInputs,
+        audio_outputs: AudioOutputs,
+    ) -> Result<(), RunError>
+    where
+        AudioInputs: ExactSizeIterator + Iterator<Item = &'a [f32]>,
+        AudioOutputs: ExactSizeIterator + Iterator<Item = &'a mut [f32]>,
+    {
+        let Self { links, scratch } = self;
+        let (first, rest) = links
+            .split_first_mut()
+            .expect("PluginChain should never be empty.");
+        let (last, middle) = match rest.split_last_mut() {
+            Some(split) => split,
+            None => return first.run(samples, audio_inputs, audio_outputs),
+        };
+        let (src, dst) = scratch.split_at_mut(1);
+        let (mut src, mut dst) = (&mut src[0], &mut dst[0]);
+        let channels = first.instance.port_counts_for_type(PortType::AudioOutput);
+        first.run(
+            samples,
+            audio_inputs,
+            src[..channels].iter_mut().map(Vec::as_mut_slice),
+        )?;
+        for link in middle.iter_mut() {
+            let inputs = link.instance.port_counts_for_type(PortType::AudioInput);
+            let outputs = link.instance.port_counts_for_type(PortType::AudioOutput);
+            link.run(
+                samples,
+                src[..inputs].iter().map(Vec::as_slice),
+                dst[..outputs].iter_mut().map(Vec::as_mut_slice),
+            )?;
+            std::mem::swap(&mut src, &mut dst);
+        }
+        let inputs = last.instance.port_counts_for_type(PortType::AudioInput);
+        last.run(
+            samples,
+            src[..inputs].iter().map(Vec::as_slice),
+            audio_outputs,
+        )
+    }
+}
+
+impl Link {
+    unsafe fn run<'i, 'o, AudioInputs, AudioOutputs>(
+        &mut self,
+        samples: usize,
+        audio_inputs: AudioInputs,
+        audio_outputs: AudioOutputs,
+    ) -> Result<(), RunError>
+    where
+        AudioInputs: ExactSizeIterator + Iterator<Item = &'i [f32]>,
+        AudioOutputs: ExactSizeIterator + Iterator<Item = &'o mut [f32]>,
+    {
+        self.buffers
+            .run(&mut self.instance, samples, audio_inputs, audio_outputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_validates_channel_counts() {
+        let world = crate::World::new();
+        let features = world.build_features(crate::FeaturesBuilder::default());
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let instances = (0..2)
+            .map(|_| unsafe { plugin.instantiate(features.clone(), 44100.0).unwrap() })
+            .collect();
+        assert_eq!(
+            PluginChain::new(instances).err(),
+            Some(PluginChainError::ChannelCountMismatch {
+                index: 0,
+                outputs: 2,
+                inputs: 0
+            })
+        );
+        assert_eq!(
+            PluginChain::new(Vec::new()).err(),
+            Some(PluginChainError::Empty)
+        );
+    }
+
+    #[test]
+    fn test_run_instrument_into_effect() {
+        let world = crate::World::new();
+        let features = world.build_features(crate::FeaturesBuilder::default());
+        let instances = [
+            "http://drobilla.net/plugins/mda/EPiano",
+            "http://drobilla.net/plugins/mda/Delay",
+        ]
+        .iter()
+        .map(|uri| {
+            let plugin = world.plugin_by_uri(uri).expect("Plugin not found.");
+            unsafe { plugin.instantiate(features.clone(), 44100.0).unwrap() }
+        })
+        .collect();
+        let mut chain = PluginChain::new(instances).unwrap();
+        let mut outputs = [vec![0.0; 64], vec![0.0; 64]];
+        unsafe {
+            chain
+                .run(
+                    64,
+                    std::iter::empty(),
+                    outputs.iter_mut().map(|o| o.as_mut_slice()),
+                )
+                .unwrap()
+        };
+        assert_eq!(chain.iter_instances().count(), 2);
+        assert_eq!(chain.latency(), 0);
+        chain.run_workers();
+    }
+}
@@ -1,15 +1,29 @@
 use std::{collections::HashMap, sync::Arc};
 
+pub const INSTRUMENT_CLASS_URI: &str = "http://lv2plug.in/ns/lv2core#InstrumentPlugin";
+pub const ANALYSER_CLASS_URI: &str = "http://lv2plug.in/ns/lv2core#AnalyserPlugin";
+pub const EFFECT_CLASS_URIS: &[&str] = &[
+    "http://lv2plug.in/ns/lv2core#DelayPlugin",
+    "http://lv2plug.in/ns/lv2core#DistortionPlugin",
+    "http://lv2plug.in/ns/lv2core#DynamicsPlugin",
+    "http://lv2plug.in/ns/lv2core#FilterPlugin",
+    "http://lv2plug.in/ns/lv2core#ModulatorPlugin",
+    "http://lv2plug.in/ns/lv2core#ReverbPlugin",
+    "http://lv2plug.in/ns/lv2core#SimulatorPlugin",
+    "http://lv2plug.in/ns/lv2core#SpatialPlugin",
+    "http://lv2plug.in/ns/lv2core#SpectralPlugin",
+];
+
 #[derive(Hash, Eq, PartialEq, Clone)]
 pub struct Class {
-    uri: String,
-    name: String,
+    pub uri: String,
+    pub name: String,
 }
 
 impl Class {
     fn from_raw(c: &lilv::plugin::Class) -> Class {
         Class {
-            uri: c.uri().unwrap().turtle_token(),
+            uri: c.uri().unwrap().as_uri().unwrap_or("BAD_URI").to_string(),
             name: c.label().turtle_token(),
         }
     }
@@ -19,12 +33,12 @@ impl Class {
 pub fn class_with_parents(
     raw_class: &lilv::plugin::Class,
     class_to_parent: &HashMap<Class, Arc<Class>>,
-) -> Vec<String> {
+) -> Vec<Class> {
     let mut class = Class::from_raw(raw_class);
-    let mut ret = vec![class.name.clone()];
+    let mut ret = vec![class.clone()];
     while let Some(parent) = class_to_parent.get(&class) {
         class = parent.as_ref().clone();
-        ret.push(class.name.clone());
+        ret.push(class.clone());
     }
     ret
 }
@@ -1,8 +1,29 @@
-/// An error with plugin instantiation.
-#[derive(Copy, Clone, Debug)]
+/// An error with plugin instantiation. Each variant includes the URI of the
+/// plugin that could not be instantiated.
+#[derive(Clone, Debug, PartialEq)]
 pub enum InstantiateError {
-    /// An error ocurred, but it is not known why.
-    UnknownError,
+    /// The plugin returned a null instance.
+    NullInstance { plugin_uri: String },
+
+    /// The plugin uses the worker extension but did not provide the
+    /// `worker:interface` extension data.
+    MissingWorkerInterface { plugin_uri: String },
+
+    /// The plugin requires features that the `Features` do not provide. See
+    /// `Features::provided_features`.
+    FeatureSetupFailed {
+        plugin_uri: String,
+        missing_features: Vec<String>,
+    },
+
+    /// The sample rate is outside of the range that the plugin supports. See
+    /// `Plugin::sample_rate_range`.
+    SampleRateUnsupported {
+        plugin_uri: String,
+        sample_rate: f64,
+        min_supported: f64,
+        max_supported: f64,
+    },
 }
 
 /// An error that occurs when dealing with atom events.
@@ -16,6 +37,102 @@ pub enum EventError {
 
     /// The size of the sequence has exceeded its capacity.
     SequenceFull { capacity: usize, requested: usize },
+
+    /// The sequence is not well formed. `offset` is the offset, in bytes from
+    /// the start of the sequence body, of the header or event that would read
+    /// past the end of the sequence.
+    MalformedSequence { offset: usize },
+}
+
+/// An error that occurs when parsing a Standard MIDI File.
+#[cfg(feature = "midi-file")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MidiFileError {
+    /// The data does not start with a Standard MIDI File header.
+    NotAMidiFile,
+
+    /// The data ended in the middle of a chunk or event.
+    UnexpectedEnd,
+
+    /// The file uses SMPTE based timing which is not supported.
+    UnsupportedTimeDivision,
+
+    /// An event in a track could not be parsed.
+    InvalidEvent { offset: usize },
+}
+
+/// An error with building a `PluginChain`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PluginChainError {
+    /// The chain does not contain any instances.
+    Empty,
+
+    /// The number of audio outputs of the instance at `index` is different
+    /// than the number of audio inputs of the instance at `index + 1`.
+    ChannelCountMismatch {
+        index: usize,
+        outputs: usize,
+        inputs: usize,
+    },
+}
+
+/// An error with saving or restoring the state of a plugin instance.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StateError {
+    /// The plugin does not implement `state:interface`.
+    NotSupported,
+
+    /// The plugin returned a `LV2_State_Status` error code.
+    PluginError { status: u32 },
+}
+
+/// An error with getting or setting the options of a plugin instance through
+/// `options:interface`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OptionsError {
+    /// The plugin does not implement `options:interface`.
+    NotSupported,
+
+    /// The plugin returned a value with a type that is not `atom:Int` or
+    /// `atom:Float`.
+    UnsupportedType { type_: u32 },
+
+    /// The plugin returned a `LV2_Options_Status` error code.
+    PluginError { status: u32 },
+}
+
+/// An error with changing the block length of a plugin instance.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlockLengthError {
+    /// The block length is smaller than the minimum block length of the
+    /// `Features`.
+    TooSmall { min_supported: usize, actual: usize },
+
+    /// The block length is larger than the maximum block length of the
+    /// `Features`.
+    TooLarge { max_supported: usize, actual: usize },
+
+    /// The plugin requires `buf-size:powerOf2BlockLength` but the block length
+    /// is not a power of 2.
+    NotPowerOf2 { actual: usize },
+
+    /// The minimum block length is 0 or larger than the maximum block length.
+    InvalidBounds { min: usize, max: usize },
+
+    /// The plugin was given `buf-size:fixedBlockLength` with a block length of
+    /// `length` but the bounds do not describe a single block length that the
+    /// plugin can be told about.
+    NotFixed {
+        length: usize,
+        min: usize,
+        max: usize,
+    },
+
+    /// The options were sent with a type the plugin does not support.
+    UnsupportedType { type_: u32 },
+
+    /// The plugin returned a `LV2_Options_Status` error code.
+    PluginError { status: u32 },
 }
 
 /// An error associated with running a plugin.
@@ -47,25 +164,113 @@ pub enum RunError {
     /// required.
     AtomSequenceInputsSizeMismatch { expected: usize, actual: usize },
 
-    /// The number of atom sequence outputs was different than what the plugin
-    /// required.
+    /// The number of atom sequence outputs was larger than what the plugin
+    /// has.
     AtomSequenceOutputsSizeMismatch { expected: usize, actual: usize },
 
     /// The number of cv inputs was different than what the plugin required.
     CVInputsSizeMismatch { expected: usize, actual: usize },
 
+    /// The number of samples in the cv inputs was too small to contain the
+    /// number of specified samples.
+    CVInputSampleCountTooSmall { expected: usize, actual: usize },
+
     /// The number of cv outputs was different than what the plugin required.
     CVOutputsSizeMismatch { expected: usize, actual: usize },
+
+    /// The number of samples in the cv outputs was too small to contain the
+    /// number of specified samples.
+    CVOutputSampleCountTooSmall { expected: usize, actual: usize },
+
+    /// Control inputs were connected but their number was different than the
+    /// number of control inputs of the plugin.
+    ControlInputsSizeMismatch { expected: usize, actual: usize },
+
+    /// Control outputs were connected but their number was different than the
+    /// number of control outputs of the plugin.
+    ControlOutputsSizeMismatch { expected: usize, actual: usize },
+
+    /// The sample count is not a power of 2 but the plugin requires
+    /// `buf-size:powerOf2BlockLength`.
+    SampleCountNotPowerOf2 { actual: usize },
+
+    /// An input and an output buffer overlap but the plugin has the
+    /// `lv2:inPlaceBroken` feature. See `Plugin::in_place_broken`.
+    InPlaceNotSupported,
+
+    /// The instance was deactivated with `Instance::deactivate` and must be
+    /// activated again before it can be run.
+    InstanceNotActive,
+
+    /// The transport position and the events of the first atom sequence input
+    /// could not be combined in `Instance::run_with_transport`.
+    TransportEventsFailed { error: EventError },
+
+    /// The MIDI events of a block could not be written to the first atom
+    /// sequence input in `Instance::render`.
+    MidiEventsFailed { error: EventError },
+}
+
+impl RunError {
+    /// The type of the ports that had the wrong number of connections, or
+    /// `None` if the error is not a size mismatch. Use
+    /// `Instance::missing_port_symbols` to get the ports that were not
+    /// connected.
+    pub fn port_type(&self) -> Option<crate::PortType> {
+        match self {
+            RunError::AudioInputsSizeMismatch { .. } => Some(crate::PortType::AudioInput),
+            RunError::AudioOutputsSizeMismatch { .. } => Some(crate::PortType::AudioOutput),
+            RunError::AtomSequenceInputsSizeMismatch { .. } => {
+                Some(crate::PortType::AtomSequenceInput)
+            }
+            RunError::AtomSequenceOutputsSizeMismatch { .. } => {
+                Some(crate::PortType::AtomSequenceOutput)
+            }
+            RunError::CVInputsSizeMismatch { .. } => Some(crate::PortType::CVInput),
+            RunError::CVOutputsSizeMismatch { .. } => Some(crate::PortType::CVOutput),
+            RunError::ControlInputsSizeMismatch { .. } => Some(crate::PortType::ControlInput),
+            RunError::ControlOutputsSizeMismatch { .. } => Some(crate::PortType::ControlOutput),
+            _ => None,
+        }
+    }
 }
 
 impl std::error::Error for InstantiateError {}
 impl std::error::Error for EventError {}
+impl std::error::Error for PluginChainError {}
 impl std::error::Error for RunError {}
+impl std::error::Error for StateError {}
+impl std::error::Error for BlockLengthError {}
+impl std::error::Error for OptionsError {}
+#[cfg(feature = "midi-file")]
+impl std::error::Error for MidiFileError {}
 
 impl std::fmt::Display for InstantiateError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            InstantiateError::UnknownError => f.write_str("unknown error"),
+            InstantiateError::NullInstance { plugin_uri } => {
+                write!(f, "plugin {plugin_uri} returned a null instance")
+            }
+            InstantiateError::MissingWorkerInterface { plugin_uri } => write!(
+                f,
+                "plugin {plugin_uri} did not provide the worker interface",
+            ),
+            InstantiateError::FeatureSetupFailed {
+                plugin_uri,
+                missing_features,
+            } => write!(
+                f,
+                "plugin {plugin_uri} requires features that are not provided: {missing_features:?}",
+            ),
+            InstantiateError::SampleRateUnsupported {
+                plugin_uri,
+                sample_rate,
+                min_supported,
+                max_supported,
+            } => write!(
+                f,
+                "plugin {plugin_uri} does not support sample rate {sample_rate}, the supported range is {min_supported} to {max_supported}",
+            ),
         }
     }
 }
@@ -87,6 +292,103 @@ impl std::fmt::Display for EventError {
                 f,
                 "sequence with capacity {capacity} is full but requested {requested}",
             ),
+            EventError::MalformedSequence { offset } => {
+                write!(f, "atom sequence is malformed at offset {offset}")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "midi-file")]
+impl std::fmt::Display for MidiFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MidiFileError::NotAMidiFile => f.write_str("data is not a standard midi file"),
+            MidiFileError::UnexpectedEnd => f.write_str("midi file ended unexpectedly"),
+            MidiFileError::UnsupportedTimeDivision => {
+                f.write_str("midi files with SMPTE time division are not supported")
+            }
+            MidiFileError::InvalidEvent { offset } => {
+                write!(f, "invalid midi event in track at byte {offset}")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for PluginChainError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PluginChainError::Empty => f.write_str("plugin chain has no instances"),
+            PluginChainError::ChannelCountMismatch {
+                index,
+                outputs,
+                inputs,
+            } => write!(
+                f,
+                "instance {index} has {outputs} audio outputs but the next instance has {inputs} audio inputs",
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for StateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StateError::NotSupported => f.write_str("plugin does not support state"),
+            StateError::PluginError { status } => {
+                write!(f, "plugin returned state error code {status}")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OptionsError::NotSupported => f.write_str("plugin does not support options"),
+            OptionsError::UnsupportedType { type_ } => {
+                write!(f, "option has unsupported type with urid {type_}")
+            }
+            OptionsError::PluginError { status } => {
+                write!(f, "plugin returned options error code {status}")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for BlockLengthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockLengthError::TooSmall {
+                min_supported,
+                actual,
+            } => write!(
+                f,
+                "block length of {actual} is less than minimum supported block length of {min_supported}",
+            ),
+            BlockLengthError::TooLarge {
+                max_supported,
+                actual,
+            } => write!(
+                f,
+                "block length of {actual} is greater than maximum supported block length of {max_supported}",
+            ),
+            BlockLengthError::NotPowerOf2 { actual } => {
+                write!(f, "block length of {actual} is not a power of 2")
+            }
+            BlockLengthError::InvalidBounds { min, max } => {
+                write!(f, "block length bounds of {min} to {max} are invalid")
+            }
+            BlockLengthError::NotFixed { length, min, max } => write!(
+                f,
+                "block length bounds of {min} to {max} break the fixed block length of {length}",
+            ),
+            BlockLengthError::UnsupportedType { type_ } => {
+                write!(f, "option type with urid {type_} is not supported")
+            }
+            BlockLengthError::PluginError { status } => {
+                write!(f, "plugin returned options error code {status}")
+            }
         }
     }
 }
@@ -129,15 +431,42 @@ impl std::fmt::Display for RunError {
             ),
             RunError::AtomSequenceOutputsSizeMismatch { expected, actual } => write!(
                 f,
-                "expected {expected} atom sequence outputs but found {actual}",
+                "expected at most {expected} atom sequence outputs but found {actual}",
             ),
             RunError::CVInputsSizeMismatch { expected, actual } => {
                 write!(f, "expected {expected} cv inputs but found {actual}")
             }
+            RunError::CVInputSampleCountTooSmall { expected, actual } => write!(
+                f,
+                "cv input required at least {expected} samples but has {actual}",
+            ),
             RunError::CVOutputsSizeMismatch { expected, actual } => write!(
                 f,
                 "cv output required at least {expected} samples but has {actual}",
             ),
+            RunError::CVOutputSampleCountTooSmall { expected, actual } => write!(
+                f,
+                "cv output required at least {expected} samples but has {actual}",
+            ),
+            RunError::ControlInputsSizeMismatch { expected, actual } => {
+                write!(f, "expected {expected} control inputs but found {actual}")
+            }
+            RunError::ControlOutputsSizeMismatch { expected, actual } => {
+                write!(f, "expected {expected} control outputs but found {actual}")
+            }
+            RunError::SampleCountNotPowerOf2 { actual } => {
+                write!(f, "sample count {actual} is not a power of 2")
+            }
+            RunError::InPlaceNotSupported => {
+                f.write_str("plugin does not support overlapping input and output buffers")
+            }
+            RunError::InstanceNotActive => f.write_str("instance is not active"),
+            RunError::TransportEventsFailed { error } => {
+                write!(f, "could not add the transport position to the events: {error}")
+            }
+            RunError::MidiEventsFailed { error } => {
+                write!(f, "could not write the MIDI events of the block: {error}")
+            }
         }
     }
 }
@@ -1,6 +1,7 @@
 use crate::error::EventError;
 use lv2_raw::LV2Atom;
 use std::convert::TryFrom;
+use std::ffi::CStr;
 use std::fmt::Debug;
 use std::marker::PhantomData;
 
@@ -95,6 +96,142 @@ impl<const MAX_SIZE: usize> Debug for LV2AtomEventBuilder<MAX_SIZE> {
     }
 }
 
+/// A builder for an `atom:Object`. The object is built from a type and a list
+/// of properties. Each property value is an atom which is padded to 8 bytes as
+/// required by the atom specification.
+///
+/// #### Example usage:
+/// ```
+/// # let world = livi::World::new();
+/// # let features = world.build_features(livi::FeaturesBuilder::default());
+/// let urids = features.common_urids();
+/// let sample = features.urid(
+///     std::ffi::CStr::from_bytes_with_nul(b"http://lv2plug.in/plugins/eg-sampler#sample\0")
+///         .unwrap(),
+/// );
+/// let mut object = livi::event::LV2AtomObjectBuilder::new(&urids, urids.patch_set);
+/// object
+///     .push_urid(urids.patch_property, sample)
+///     .push_path(urids.patch_value, "/tmp/sample.wav");
+///
+/// let mut sequence = livi::event::LV2AtomSequence::new(&features, 1024);
+/// sequence.push_object(0, &object).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct LV2AtomObjectBuilder {
+    urids: crate::CommonUrids,
+    /// The body of the object. This starts with the id and type of the object
+    /// and is followed by the properties.
+    body: Vec<u8>,
+}
+
+impl LV2AtomObjectBuilder {
+    /// Create a new object of type `otype` with no properties. The object is
+    /// blank, that is it has an id of `0`.
+    #[must_use]
+    pub fn new(urids: &crate::CommonUrids, otype: lv2_raw::LV2Urid) -> LV2AtomObjectBuilder {
+        let mut body = Vec::with_capacity(64);
+        body.extend_from_slice(&0u32.to_ne_bytes());
+        body.extend_from_slice(&otype.to_ne_bytes());
+        LV2AtomObjectBuilder {
+            urids: *urids,
+            body,
+        }
+    }
+
+    /// Set the id of the object.
+    #[must_use]
+    pub fn with_id(mut self, id: lv2_raw::LV2Urid) -> LV2AtomObjectBuilder {
+        self.body[0..4].copy_from_slice(&id.to_ne_bytes());
+        self
+    }
+
+    /// Add a property with an already encoded atom value. `value_body` is the
+    /// body of the atom, without the atom header.
+    pub fn push_property(
+        &mut self,
+        key: lv2_raw::LV2Urid,
+        value_type: lv2_raw::LV2Urid,
+        value_body: &[u8],
+    ) -> &mut LV2AtomObjectBuilder {
+        self.body.extend_from_slice(&key.to_ne_bytes());
+        // The context is unused and must be 0.
+        self.body.extend_from_slice(&0u32.to_ne_bytes());
+        self.body
+            .extend_from_slice(&(value_body.len() as u32).to_ne_bytes());
+        self.body.extend_from_slice(&value_type.to_ne_bytes());
+        self.body.extend_from_slice(value_body);
+        let padded_size = lv2_raw::lv2_atom_pad_size(self.body.len() as u32) as usize;
+        self.body.resize(padded_size, 0);
+        self
+    }
+
+    /// Add an `atom:Bool` property.
+    pub fn push_bool(&mut self, key: lv2_raw::LV2Urid, value: bool) -> &mut LV2AtomObjectBuilder {
+        let value = i32::from(value);
+        self.push_property(key, self.urids.atom_bool, &value.to_ne_bytes())
+    }
+
+    /// Add an `atom:Int` property.
+    pub fn push_int(&mut self, key: lv2_raw::LV2Urid, value: i32) -> &mut LV2AtomObjectBuilder {
+        self.push_property(key, self.urids.atom_int, &value.to_ne_bytes())
+    }
+
+    /// Add an `atom:Long` property.
+    pub fn push_long(&mut self, key: lv2_raw::LV2Urid, value: i64) -> &mut LV2AtomObjectBuilder {
+        self.push_property(key, self.urids.atom_long, &value.to_ne_bytes())
+    }
+
+    /// Add an `atom:Float` property.
+    pub fn push_float(&mut self, key: lv2_raw::LV2Urid, value: f32) -> &mut LV2AtomObjectBuilder {
+        self.push_property(key, self.urids.atom_float, &value.to_ne_bytes())
+    }
+
+    /// Add an `atom:Double` property.
+    pub fn push_double(&mut self, key: lv2_raw::LV2Urid, value: f64) -> &mut LV2AtomObjectBuilder {
+        self.push_property(key, self.urids.atom_double, &value.to_ne_bytes())
+    }
+
+    /// Add an `atom:URID` property.
+    pub fn push_urid(
+        &mut self,
+        key: lv2_raw::LV2Urid,
+        value: lv2_raw::LV2Urid,
+    ) -> &mut LV2AtomObjectBuilder {
+        self.push_property(key, self.urids.atom_urid, &value.to_ne_bytes())
+    }
+
+    /// Add an `atom:String` property. The string is null terminated.
+    pub fn push_string(&mut self, key: lv2_raw::LV2Urid, value: &str) -> &mut LV2AtomObjectBuilder {
+        let value_type = self.urids.atom_string;
+        self.push_null_terminated(key, value_type, value)
+    }
+
+    /// Add an `atom:Path` property. The path is null terminated.
+    pub fn push_path(&mut self, key: lv2_raw::LV2Urid, value: &str) -> &mut LV2AtomObjectBuilder {
+        let value_type = self.urids.atom_path;
+        self.push_null_terminated(key, value_type, value)
+    }
+
+    fn push_null_terminated(
+        &mut self,
+        key: lv2_raw::LV2Urid,
+        value_type: lv2_raw::LV2Urid,
+        value: &str,
+    ) -> &mut LV2AtomObjectBuilder {
+        let mut value_body = Vec::with_capacity(value.len() + 1);
+        value_body.extend_from_slice(value.as_bytes());
+        value_body.push(0);
+        self.push_property(key, value_type, &value_body)
+    }
+
+    /// The body of the object atom, without the atom header.
+    #[must_use]
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+}
+
 /// An atom sequence.
 pub struct LV2AtomSequence {
     atom_sequence_urid: lv2_raw::LV2Urid,
@@ -179,6 +316,151 @@ impl LV2AtomSequence {
         Ok(())
     }
 
+    /// Append an atom event with the given time and type. `body` is the
+    /// already encoded body of the atom, without the atom header. The header is
+    /// built from `type_urid` and the size of `body`.
+    ///
+    /// Unlike `push_event`, the size of the body is not limited by a const
+    /// generic.
+    ///
+    /// # Errors
+    /// Returns an error if the sequence does not have the capacity for the
+    /// event.
+    pub fn push_raw_atom(
+        &mut self,
+        time_in_frames: i64,
+        type_urid: lv2_raw::LV2Urid,
+        body: &[u8],
+    ) -> Result<(), EventError> {
+        self.push_raw_atom_parts(time_in_frames, type_urid, &[body])
+    }
+
+    /// Append an atom event like `push_raw_atom` where the body is the
+    /// concatenation of `body_parts`. This avoids allocating a buffer for
+    /// bodies that are built from several pieces.
+    fn push_raw_atom_parts(
+        &mut self,
+        time_in_frames: i64,
+        type_urid: lv2_raw::LV2Urid,
+        body_parts: &[&[u8]],
+    ) -> Result<(), EventError> {
+        let body_size: usize = body_parts.iter().map(|p| p.len()).sum();
+        let header_size = std::mem::size_of::<lv2_raw::LV2AtomEvent>();
+        let event_size = header_size + body_size;
+        let sequence = unsafe { &mut *self.as_mut_ptr() };
+        // This size includes the atom sequence header.
+        let current_sequence_size =
+            std::mem::size_of_val(&sequence.atom) + sequence.atom.size as usize;
+        if self.buffer.len() < current_sequence_size + event_size {
+            return Err(EventError::SequenceFull {
+                capacity: self.capacity(),
+                requested: current_sequence_size + event_size,
+            });
+        }
+        let header = lv2_raw::LV2AtomEvent {
+            time_in_frames,
+            body: LV2Atom {
+                size: body_size as u32,
+                mytype: type_urid,
+            },
+        };
+        let end = unsafe { lv2_raw::lv2_atom_sequence_end(&sequence.body, sequence.atom.size) }
+            as *mut u8;
+        unsafe {
+            let header_ptr: *const u8 = (&header as *const lv2_raw::LV2AtomEvent).cast();
+            std::ptr::copy_nonoverlapping(header_ptr, end, header_size);
+            let mut offset = header_size;
+            for part in body_parts {
+                std::ptr::copy_nonoverlapping(part.as_ptr(), end.add(offset), part.len());
+                offset += part.len();
+            }
+        };
+        // This size only includes the sequencey body.
+        sequence.atom.size += lv2_raw::lv2_atom_pad_size(event_size as u32);
+        Ok(())
+    }
+
+    /// Append an `atom:Object` event built with `LV2AtomObjectBuilder`.
+    ///
+    /// # Errors
+    /// Returns an error if the sequence does not have the capacity for the
+    /// event.
+    pub fn push_object(
+        &mut self,
+        time_in_frames: i64,
+        object: &LV2AtomObjectBuilder,
+    ) -> Result<(), EventError> {
+        self.push_raw_atom(time_in_frames, object.urids.atom_object, object.body())
+    }
+
+    /// Append a `patch:Set` object that sets the parameter `property` to a
+    /// value at `time_in_frames`. `value_body` is the body of an atom of type
+    /// `value_type`, without the atom header. This allows sample accurate
+    /// changes of parameters that plugins expose as properties instead of
+    /// control ports.
+    ///
+    /// Unlike `push_object`, this does not allocate so it is realtime safe.
+    ///
+    /// # Errors
+    /// Returns an error if the sequence does not have the capacity for the
+    /// event.
+    pub fn push_patch_set(
+        &mut self,
+        time_in_frames: i64,
+        urids: &crate::CommonUrids,
+        property: lv2_raw::LV2Urid,
+        value_type: lv2_raw::LV2Urid,
+        value_body: &[u8],
+    ) -> Result<(), EventError> {
+        // The property atom is a 4 byte URID padded to 8 bytes.
+        const PADDING: [u8; 4] = [0; 4];
+        let context = 0u32.to_ne_bytes();
+        self.push_raw_atom_parts(
+            time_in_frames,
+            urids.atom_object,
+            &[
+                // The object id and type.
+                &0u32.to_ne_bytes(),
+                &urids.patch_set.to_ne_bytes(),
+                // The patch:property property.
+                &urids.patch_property.to_ne_bytes(),
+                &context,
+                &4u32.to_ne_bytes(),
+                &urids.atom_urid.to_ne_bytes(),
+                &property.to_ne_bytes(),
+                &PADDING,
+                // The patch:value property.
+                &urids.patch_value.to_ne_bytes(),
+                &context,
+                &(value_body.len() as u32).to_ne_bytes(),
+                &value_type.to_ne_bytes(),
+                value_body,
+            ],
+        )
+    }
+
+    /// Append a `patch:Set` object that sets the parameter `property` to the
+    /// `atom:Float` `value`. See `push_patch_set`.
+    ///
+    /// # Errors
+    /// Returns an error if the sequence does not have the capacity for the
+    /// event.
+    pub fn push_float_parameter(
+        &mut self,
+        time_in_frames: i64,
+        urids: &crate::CommonUrids,
+        property: lv2_raw::LV2Urid,
+        value: f32,
+    ) -> Result<(), EventError> {
+        self.push_patch_set(
+            time_in_frames,
+            urids,
+            property,
+            urids.atom_float,
+            &value.to_ne_bytes(),
+        )
+    }
+
     /// Push a new midi event into the sequence. The `midi_data` must be of size
     /// `MAX_SIZE` or smaller. If this is not the case, an error is returned.
     ///
@@ -194,6 +476,106 @@ impl LV2AtomSequence {
         self.push_event(&event)
     }
 
+    /// Append a midi event of any size to the sequence. Unlike
+    /// `push_midi_event`, the size of `data` is not limited by a const generic,
+    /// so it can be used for variable length messages like sysex. The data is
+    /// copied directly into the sequence without allocating.
+    ///
+    /// # Errors
+    /// Returns an error if the sequence does not have the capacity for the
+    /// event.
+    pub fn push_midi_event_dyn(
+        &mut self,
+        time_in_frames: i64,
+        midi_uri: lv2_raw::LV2Urid,
+        data: &[u8],
+    ) -> Result<(), EventError> {
+        self.push_raw_atom(time_in_frames, midi_uri, data)
+    }
+
+    /// Append all events of `other` to the end of the sequence. This can be
+    /// used to merge events from several sources into a single input. The
+    /// events are not reordered, see `sorted_by_time`.
+    ///
+    /// # Errors
+    /// Returns an error if `other` is malformed or if the sequence does not
+    /// have the capacity for all events of `other`. The sequence is not
+    /// modified if an error is returned.
+    pub fn extend_from(&mut self, other: &LV2AtomSequence) -> Result<(), EventError> {
+        other.validate()?;
+        let other_size = unsafe { (*other.as_ptr()).atom.size } as usize;
+        if unsafe { (*other.as_ptr()).atom.mytype } != other.atom_sequence_urid {
+            return Ok(());
+        }
+        let events_start =
+            std::mem::size_of::<LV2Atom>() + std::mem::size_of::<lv2_raw::LV2AtomSequenceBody>();
+        let events = &other.buffer[events_start..std::mem::size_of::<LV2Atom>() + other_size];
+        // Plugins may not pad the last event of the sequences they write.
+        let padded_size = lv2_raw::lv2_atom_pad_size(events.len() as u32) as usize;
+        let current_sequence_size = self.size();
+        if self.buffer.len() < current_sequence_size + padded_size {
+            return Err(EventError::SequenceFull {
+                capacity: self.capacity(),
+                requested: current_sequence_size + padded_size,
+            });
+        }
+        let dst = &mut self.buffer[current_sequence_size..current_sequence_size + padded_size];
+        dst[..events.len()].copy_from_slice(events);
+        dst[events.len()..].fill(0);
+        unsafe { (*self.as_mut_ptr()).atom.size += padded_size as u32 };
+        Ok(())
+    }
+
+    /// Return a copy of the sequence with its events sorted by time. Events
+    /// with the same time keep their relative order. Plugins expect the events
+    /// of a sequence to be in time ascending order, which may not be the case
+    /// after `extend_from`.
+    ///
+    /// # Panics
+    /// Panics if the underlying sequence is not well formed.
+    #[must_use]
+    pub fn sorted_by_time(&self) -> LV2AtomSequence {
+        let base = self.buffer.as_ptr() as usize;
+        let mut events: Vec<(i64, std::ops::Range<usize>)> = self
+            .iter()
+            .map(|e| {
+                let start = e.event as *const lv2_raw::LV2AtomEvent as usize - base;
+                let size = std::mem::size_of::<lv2_raw::LV2AtomEvent>() + e.data.len();
+                (e.event.time_in_frames, start..start + size)
+            })
+            .collect();
+        // The sort is stable so events with the same time keep their order.
+        events.sort_by_key(|(time, _)| *time);
+        let mut buffer = self.buffer.clone();
+        let mut offset =
+            std::mem::size_of::<LV2Atom>() + std::mem::size_of::<lv2_raw::LV2AtomSequenceBody>();
+        for (_, range) in events {
+            let size = range.len();
+            let padded_end =
+                (offset + lv2_raw::lv2_atom_pad_size(size as u32) as usize).min(buffer.len());
+            buffer[offset..offset + size].copy_from_slice(&self.buffer[range]);
+            buffer[offset + size..padded_end].fill(0);
+            offset = padded_end;
+        }
+        LV2AtomSequence {
+            atom_sequence_urid: self.atom_sequence_urid,
+            atom_chunk_urid: self.atom_chunk_urid,
+            buffer,
+        }
+    }
+
+    /// Copy all the events of the sequence into owned data. Unlike `iter`, the
+    /// events are not tied to the lifetime of the sequence so they may be
+    /// buffered or sent to other threads. This allocates so it should not be
+    /// used in a realtime thread.
+    ///
+    /// # Panics
+    /// Panics if the underlying sequence is not well formed.
+    #[must_use]
+    pub fn to_owned_events(&self) -> Vec<OwnedAtomEvent> {
+        self.iter().map(|e| e.to_owned_event()).collect()
+    }
+
     /// Return a pointer to the underlying data.
     #[must_use]
     pub fn as_ptr(&self) -> *const lv2_raw::LV2AtomSequence {
@@ -224,7 +606,8 @@ impl LV2AtomSequence {
     /// Iterate over all events (and event data) in the sequence.
     ///
     /// # Panics
-    /// Panics if the underlying sequence is not well formed.
+    /// Panics if the underlying sequence is not well formed. Use `try_iter` for
+    /// sequences that were written by plugins.
     #[must_use]
     pub fn iter(&self) -> LV2AtomSequenceIter<'_> {
         unsafe {
@@ -250,6 +633,51 @@ impl LV2AtomSequence {
             next: begin,
         }
     }
+
+    /// Iterate over all events (and event data) in the sequence after checking
+    /// that the sequence is well formed.
+    ///
+    /// # Errors
+    /// Returns `EventError::MalformedSequence` if the sequence header or any of
+    /// its events would read past the end of the sequence.
+    pub fn try_iter(&self) -> Result<LV2AtomSequenceIter<'_>, EventError> {
+        self.validate()?;
+        Ok(self.iter())
+    }
+
+    /// Check that the sizes of the sequence and all its events fit within the
+    /// sequence. Only sequences are validated since `iter` does not yield any
+    /// events for other atom types.
+    fn validate(&self) -> Result<(), EventError> {
+        let raw = unsafe { self.as_ptr().as_ref().unwrap() };
+        if raw.atom.mytype != self.atom_sequence_urid {
+            return Ok(());
+        }
+        let atom_header_size = std::mem::size_of::<LV2Atom>();
+        let body_header_size = std::mem::size_of::<lv2_raw::LV2AtomSequenceBody>();
+        let event_header_size = std::mem::size_of::<lv2_raw::LV2AtomEvent>();
+        let body_size = raw.atom.size as usize;
+        if body_size < body_header_size || atom_header_size + body_size > self.buffer.len() {
+            return Err(EventError::MalformedSequence { offset: 0 });
+        }
+        let body = &self.buffer[atom_header_size..atom_header_size + body_size];
+        let mut offset = body_header_size;
+        while offset < body_size {
+            if offset + event_header_size > body_size {
+                return Err(EventError::MalformedSequence { offset });
+            }
+            // The atom size follows the 8 byte event time.
+            let size_offset = offset + std::mem::size_of::<i64>();
+            let mut size_bytes = [0; 4];
+            size_bytes.copy_from_slice(&body[size_offset..size_offset + 4]);
+            let data_size = u32::from_ne_bytes(size_bytes) as usize;
+            if offset + event_header_size + data_size > body_size {
+                return Err(EventError::MalformedSequence { offset });
+            }
+            offset += (event_header_size + data_size + 7) & !7;
+        }
+        Ok(())
+    }
 }
 
 impl Debug for LV2AtomSequence {
@@ -311,6 +739,144 @@ pub struct LV2AtomEventWithData<'a> {
     pub data: &'a [u8],
 }
 
+impl<'a> LV2AtomEventWithData<'a> {
+    /// The data of the event if it is a MIDI event. `midi_urid` is the URID of
+    /// `http://lv2plug.in/ns/ext/midi#MidiEvent`, see `Features::midi_urid`.
+    #[must_use]
+    pub fn as_midi(&self, midi_urid: lv2_raw::LV2Urid) -> Option<&'a [u8]> {
+        if self.event.body.mytype == midi_urid {
+            Some(self.data)
+        } else {
+            None
+        }
+    }
+
+    /// The child type and elements of the event if it is an `atom:Vector`.
+    /// `vector_urid` is the URID of `http://lv2plug.in/ns/ext/atom#Vector`,
+    /// see `CommonUrids::atom_vector`. Returns `None` if the event is not a
+    /// vector or if the size of its elements does not match `T`.
+    #[must_use]
+    pub fn as_vector<T: AtomVectorElement>(
+        &self,
+        vector_urid: lv2_raw::LV2Urid,
+    ) -> Option<(lv2_raw::LV2Urid, &'a [T])> {
+        if self.event.body.mytype != vector_urid {
+            return None;
+        }
+        let header_size = std::mem::size_of::<lv2_raw::LV2AtomVectorBody>();
+        let header = self.data.get(..header_size)?;
+        let mut child_size = [0; 4];
+        child_size.copy_from_slice(&header[0..4]);
+        let mut child_type = [0; 4];
+        child_type.copy_from_slice(&header[4..8]);
+        if u32::from_ne_bytes(child_size) as usize != std::mem::size_of::<T>() {
+            return None;
+        }
+        let elements = &self.data[header_size..];
+        if elements.as_ptr().align_offset(std::mem::align_of::<T>()) != 0 {
+            return None;
+        }
+        let len = elements.len() / std::mem::size_of::<T>();
+        Some((u32::from_ne_bytes(child_type), unsafe {
+            std::slice::from_raw_parts(elements.as_ptr().cast(), len)
+        }))
+    }
+
+    /// The time of the event in frames if it can be represented as a `u32`.
+    /// Returns `None` for negative times and times that are too large.
+    #[must_use]
+    pub fn time_in_frames_u32(&self) -> Option<u32> {
+        u32::try_from(self.event.time_in_frames).ok()
+    }
+
+    /// Copy the event into owned data.
+    #[must_use]
+    pub fn to_owned_event(&self) -> OwnedAtomEvent {
+        OwnedAtomEvent {
+            time_in_frames: self.event.time_in_frames,
+            type_urid: self.event.body.mytype,
+            data: self.data.to_vec(),
+        }
+    }
+
+    /// The kind of atom that the event contains. This maps URIs with
+    /// `features`, so it should not be used in a realtime thread.
+    #[must_use]
+    pub fn kind(&self, features: &crate::Features) -> AtomKind {
+        let mytype = self.event.body.mytype;
+        let is = |uri: &[u8]| mytype == features.urid(CStr::from_bytes_with_nul(uri).unwrap());
+        if mytype == features.midi_urid() {
+            AtomKind::Midi
+        } else if is(b"http://lv2plug.in/ns/ext/atom#Blank\0") {
+            AtomKind::Blank
+        } else if is(b"http://lv2plug.in/ns/ext/atom#Object\0") {
+            AtomKind::Object
+        } else if is(b"http://lv2plug.in/ns/ext/atom#Float\0") {
+            AtomKind::Float
+        } else if is(b"http://lv2plug.in/ns/ext/atom#Int\0") {
+            AtomKind::Int
+        } else if is(b"http://lv2plug.in/ns/ext/atom#Path\0") {
+            AtomKind::Path
+        } else {
+            AtomKind::Unknown
+        }
+    }
+}
+
+/// A type that can be read from the elements of an `atom:Vector` with
+/// `LV2AtomEventWithData::as_vector`.
+///
+/// # Safety
+/// Every bit pattern of the size of the type must be a valid value of the type.
+pub unsafe trait AtomVectorElement: Copy {}
+
+unsafe impl AtomVectorElement for i32 {}
+unsafe impl AtomVectorElement for i64 {}
+unsafe impl AtomVectorElement for u32 {}
+unsafe impl AtomVectorElement for f32 {}
+unsafe impl AtomVectorElement for f64 {}
+
+/// An owned copy of an event of an `LV2AtomSequence`. See
+/// `LV2AtomSequence::to_owned_events`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OwnedAtomEvent {
+    /// The time of the event in frames.
+    pub time_in_frames: i64,
+
+    /// The URID of the type of the event's atom.
+    pub type_urid: lv2_raw::LV2Urid,
+
+    /// The body of the event's atom.
+    pub data: Vec<u8>,
+}
+
+/// The kind of atom of an `LV2AtomEventWithData`. See
+/// http://lv2plug.in/ns/ext/atom.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AtomKind {
+    /// A MIDI event, `midi:MidiEvent`.
+    Midi,
+
+    /// A blank object, `atom:Blank`. This is deprecated in favor of
+    /// `atom:Object` but is still used by some plugins.
+    Blank,
+
+    /// An object, `atom:Object`. For example, a `patch:Set` message.
+    Object,
+
+    /// A 32 bit float, `atom:Float`.
+    Float,
+
+    /// A 32 bit integer, `atom:Int`.
+    Int,
+
+    /// A path, `atom:Path`.
+    Path,
+
+    /// Any other type of atom.
+    Unknown,
+}
+
 impl<'a> Debug for LV2AtomEventWithData<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("LV2AtomEventWithData")
@@ -336,6 +902,7 @@ mod tests {
         TEST_WORLD.build_features(crate::features::FeaturesBuilder {
             min_block_length: 1024,
             max_block_length: 1024,
+            ..crate::features::FeaturesBuilder::default()
         })
     }
 
@@ -352,6 +919,228 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_try_iter_detects_malformed_sequences() {
+        let mut sequence = LV2AtomSequence::new(&test_features(), 256);
+        let event = LV2AtomEventBuilder::<8>::new(0, 0, &[0, 10, 20, 30, 40, 50, 60, 70]).unwrap();
+        for _ in 0..3 {
+            sequence.push_event(&event).unwrap();
+        }
+        assert_eq!(sequence.try_iter().unwrap().count(), 3);
+
+        // Make the last event claim more data than is in the sequence. The
+        // events start after the 8 byte atom header and 8 byte sequence body
+        // header, and each event takes 24 bytes.
+        let last_event_size_offset = 8 + 8 + 2 * 24 + 8;
+        sequence.buffer[last_event_size_offset..last_event_size_offset + 4]
+            .copy_from_slice(&64u32.to_ne_bytes());
+        assert_eq!(
+            sequence.try_iter().unwrap_err(),
+            EventError::MalformedSequence { offset: 8 + 2 * 24 }
+        );
+
+        // Make the sequence claim to be larger than its buffer.
+        sequence.clear();
+        unsafe { (*sequence.as_mut_ptr()).atom.size = 1024 };
+        assert_eq!(
+            sequence.try_iter().unwrap_err(),
+            EventError::MalformedSequence { offset: 0 }
+        );
+    }
+
+    #[test]
+    fn test_extend_from_and_sorted_by_time() {
+        let features = test_features();
+        let mut sequence = LV2AtomSequence::new(&features, 1024);
+        sequence.push_raw_atom(0, 1, &[1]).unwrap();
+        sequence.push_raw_atom(20, 1, &[2]).unwrap();
+        let mut other = LV2AtomSequence::new(&features, 1024);
+        other.push_raw_atom(10, 2, &[3, 3, 3]).unwrap();
+        other.push_raw_atom(20, 2, &[4]).unwrap();
+
+        sequence.extend_from(&other).unwrap();
+        let times_and_data = |s: &LV2AtomSequence| {
+            s.iter()
+                .map(|e| (e.event.time_in_frames, e.data.to_vec()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            times_and_data(&sequence),
+            vec![
+                (0, vec![1]),
+                (20, vec![2]),
+                (10, vec![3, 3, 3]),
+                (20, vec![4])
+            ]
+        );
+        assert_eq!(
+            times_and_data(&sequence.sorted_by_time()),
+            vec![
+                (0, vec![1]),
+                (10, vec![3, 3, 3]),
+                (20, vec![2]),
+                (20, vec![4])
+            ]
+        );
+        assert_eq!(sequence.sorted_by_time().size(), sequence.size());
+
+        let mut small = LV2AtomSequence::new(&features, 32);
+        small.push_raw_atom(0, 1, &[1]).unwrap();
+        let size = small.size();
+        assert!(matches!(
+            small.extend_from(&other),
+            Err(EventError::SequenceFull { .. })
+        ));
+        assert_eq!(small.size(), size);
+    }
+
+    #[test]
+    fn test_time_in_frames_u32() {
+        let mut sequence = LV2AtomSequence::new(&test_features(), 1024);
+        for time in [-1, 0, 100, i64::from(u32::MAX) + 1] {
+            sequence.push_raw_atom(time, 1, &[0]).unwrap();
+        }
+        let times: Vec<_> = sequence.iter().map(|e| e.time_in_frames_u32()).collect();
+        assert_eq!(times, vec![None, Some(0), Some(100), None]);
+    }
+
+    #[test]
+    fn test_as_vector() {
+        let features = test_features();
+        let urids = features.common_urids();
+        let spectrum = [0.25f32, 0.5, 1.0];
+        let mut body = Vec::new();
+        body.extend_from_slice(&4u32.to_ne_bytes());
+        body.extend_from_slice(&urids.atom_float.to_ne_bytes());
+        for value in spectrum {
+            body.extend_from_slice(&value.to_ne_bytes());
+        }
+        let mut sequence = LV2AtomSequence::new(&features, 1024);
+        sequence.push_raw_atom(0, urids.atom_vector, &body).unwrap();
+        sequence.push_raw_atom(1, urids.atom_float, &body).unwrap();
+        let events: Vec<_> = sequence.iter().collect();
+        assert_eq!(
+            events[0].as_vector::<f32>(urids.atom_vector),
+            Some((urids.atom_float, &spectrum[..]))
+        );
+        assert_eq!(events[0].as_vector::<f64>(urids.atom_vector), None);
+        assert_eq!(events[1].as_vector::<f32>(urids.atom_vector), None);
+    }
+
+    #[test]
+    fn test_push_midi_event_dyn() {
+        let features = test_features();
+        let mut sysex = vec![0xF0];
+        sysex.extend(std::iter::repeat(0x12).take(300));
+        sysex.push(0xF7);
+        let mut sequence = LV2AtomSequence::new(&features, 1024);
+        sequence
+            .push_midi_event_dyn(4, features.midi_urid(), &sysex)
+            .unwrap();
+        sequence
+            .push_midi_event_dyn(5, features.midi_urid(), &[0x90, 0x40, 0x7f])
+            .unwrap();
+        let events: Vec<_> = sequence
+            .iter()
+            .map(|e| e.as_midi(features.midi_urid()).unwrap().to_vec())
+            .collect();
+        assert_eq!(events, vec![sysex.clone(), vec![0x90, 0x40, 0x7f]]);
+
+        let mut small_sequence = LV2AtomSequence::new(&features, 64);
+        assert!(matches!(
+            small_sequence.push_midi_event_dyn(0, features.midi_urid(), &sysex),
+            Err(EventError::SequenceFull { .. })
+        ));
+    }
+
+    #[test]
+    fn test_to_owned_events() {
+        let features = test_features();
+        let mut sequence = LV2AtomSequence::new(&features, 1024);
+        sequence
+            .push_midi_event::<3>(1, features.midi_urid(), &[0x90, 0x40, 0x7f])
+            .unwrap();
+        sequence.push_raw_atom(7, 1, &[1, 2, 3, 4, 5]).unwrap();
+        let events = sequence.to_owned_events();
+        sequence.clear();
+        assert_eq!(
+            events,
+            vec![
+                OwnedAtomEvent {
+                    time_in_frames: 1,
+                    type_urid: features.midi_urid(),
+                    data: vec![0x90, 0x40, 0x7f],
+                },
+                OwnedAtomEvent {
+                    time_in_frames: 7,
+                    type_urid: 1,
+                    data: vec![1, 2, 3, 4, 5],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_push_patch_set_matches_object_builder() {
+        let features = test_features();
+        let urids = features.common_urids();
+        let gain = features.urid_str("http://example.com/plugin#gain");
+        let mut sequence = LV2AtomSequence::new(&features, 1024);
+        sequence
+            .push_float_parameter(12, &urids, gain, 0.5)
+            .unwrap();
+        sequence
+            .push_patch_set(20, &urids, gain, urids.atom_int, &3i32.to_ne_bytes())
+            .unwrap();
+
+        let mut float_object = LV2AtomObjectBuilder::new(&urids, urids.patch_set);
+        float_object
+            .push_urid(urids.patch_property, gain)
+            .push_float(urids.patch_value, 0.5);
+        let mut int_object = LV2AtomObjectBuilder::new(&urids, urids.patch_set);
+        int_object
+            .push_urid(urids.patch_property, gain)
+            .push_int(urids.patch_value, 3);
+        let events: Vec<_> = sequence.iter().collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event.time_in_frames, 12);
+        assert_eq!(events[0].event.body.mytype, urids.atom_object);
+        // The builder pads the last property, the event only pads its size.
+        assert_eq!(events[0].data, &float_object.body()[..events[0].data.len()]);
+        assert_eq!(events[1].event.time_in_frames, 20);
+        assert_eq!(events[1].data, &int_object.body()[..events[1].data.len()]);
+    }
+
+    #[test]
+    fn test_push_object() {
+        let features = test_features();
+        let urids = features.common_urids();
+        let mut object = LV2AtomObjectBuilder::new(&urids, urids.patch_set).with_id(7);
+        object
+            .push_int(urids.patch_property, 3)
+            .push_string(urids.patch_value, "abcdefgh");
+        let body = object.body();
+        // The object header, an int property padded to 8 bytes and a string
+        // property of 9 bytes padded to 16 bytes.
+        assert_eq!(body.len(), 8 + (16 + 8) + (16 + 16));
+        assert_eq!(&body[0..4], &7u32.to_ne_bytes());
+        assert_eq!(&body[4..8], &urids.patch_set.to_ne_bytes());
+        assert_eq!(&body[8..12], &urids.patch_property.to_ne_bytes());
+        assert_eq!(&body[16..20], &4u32.to_ne_bytes());
+        assert_eq!(&body[20..24], &urids.atom_int.to_ne_bytes());
+        assert_eq!(&body[24..28], &3i32.to_ne_bytes());
+        assert_eq!(&body[40..44], &9u32.to_ne_bytes());
+        assert_eq!(&body[48..57], b"abcdefgh\0");
+
+        let mut sequence = LV2AtomSequence::new(&features, 1024);
+        sequence.push_object(5, &object).unwrap();
+        let events: Vec<_> = sequence.iter().collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event.time_in_frames, 5);
+        assert_eq!(events[0].event.body.mytype, urids.atom_object);
+        assert_eq!(events[0].data, body);
+    }
+
     #[test]
     fn test_sequence_push_events_fails_after_reaching_capacity() {
         // Keep it aligned to 8 bytes to prevent wasting capacity due to
@@ -392,6 +1181,49 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_push_raw_atom() {
+        let mut sequence = LV2AtomSequence::new(&test_features(), 64);
+        sequence.push_raw_atom(7, 3, &[1, 2, 3, 4, 5]).unwrap();
+        let events: Vec<_> = sequence.iter().collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event.time_in_frames, 7);
+        assert_eq!(events[0].event.body.mytype, 3);
+        assert_eq!(events[0].data, &[1, 2, 3, 4, 5]);
+
+        assert_eq!(
+            sequence.push_raw_atom(0, 3, &[0; 32]).err(),
+            Some(EventError::SequenceFull {
+                capacity: 64,
+                requested: 88,
+            })
+        );
+    }
+
+    #[test]
+    fn test_event_kind() {
+        let features = test_features();
+        let float = features
+            .urid(CStr::from_bytes_with_nul(b"http://lv2plug.in/ns/ext/atom#Float\0").unwrap());
+        let mut sequence = LV2AtomSequence::new(&features, 1024);
+        sequence
+            .push_midi_event::<3>(0, features.midi_urid(), &[0x90, 0x40, 0x7F])
+            .unwrap();
+        sequence
+            .push_raw_atom(1, float, &1.0f32.to_ne_bytes())
+            .unwrap();
+        sequence.push_raw_atom(2, 0, &[]).unwrap();
+
+        let events: Vec<_> = sequence.iter().collect();
+        let kinds: Vec<_> = events.iter().map(|e| e.kind(&features)).collect();
+        assert_eq!(kinds, [AtomKind::Midi, AtomKind::Float, AtomKind::Unknown]);
+        assert_eq!(
+            events[0].as_midi(features.midi_urid()),
+            Some(&[0x90, 0x40, 0x7F][..])
+        );
+        assert_eq!(events[1].as_midi(features.midi_urid()), None);
+    }
+
     #[test]
     fn test_clear() {
         let mut sequence = LV2AtomSequence::new(&test_features(), 1024);
@@ -0,0 +1,338 @@
+use lv2_raw::LV2Feature;
+use std::convert::TryInto;
+use std::ffi::CStr;
+use std::mem::size_of;
+use std::os::raw::{c_char, c_int, c_void};
+use std::pin::Pin;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::features::urid_map::UridMap;
+use crate::features::worker::{
+    instantiate_queue, message_buffer, pop_message, publish_message, MessageSender,
+    WorkerMessageReceiver,
+};
+
+/// The size of the buffer that messages are formatted into. Longer messages
+/// are truncated.
+const MESSAGE_CAPACITY: usize = 1024;
+
+/// The number of messages of `MESSAGE_CAPACITY` bytes that fit into the log
+/// queue. Messages that arrive when the queue is full are dropped.
+const QUEUE_MESSAGES: usize = 16;
+
+/// The size of a queued message. Each message is the URID of its log level
+/// followed by the text.
+const QUEUED_MESSAGE_SIZE: usize = size_of::<lv2_raw::LV2Urid>() + MESSAGE_CAPACITY;
+
+/// A `va_list` as it is passed to functions. Depending on the platform,
+/// `va_list` is a pointer, an array that decays to a pointer, or a struct that
+/// is passed by reference, so it is always passed as a single pointer.
+type VaList = *mut c_void;
+
+/// The layout of `LV2_Log_Log`. The functions differ from `lv2_sys` only in
+/// how the variadic arguments are passed. See `do_printf` and `VaList`.
+#[repr(C)]
+struct LogData {
+    handle: lv2_sys::LV2_Log_Handle,
+    printf:
+        unsafe extern "C" fn(lv2_sys::LV2_Log_Handle, lv2_sys::LV2_URID, *const c_char) -> c_int,
+    vprintf: unsafe extern "C" fn(
+        lv2_sys::LV2_Log_Handle,
+        lv2_sys::LV2_URID,
+        *const c_char,
+        VaList,
+    ) -> c_int,
+}
+
+extern "C" {
+    fn vsnprintf(s: *mut c_char, n: usize, format: *const c_char, ap: VaList) -> c_int;
+}
+
+/// The URIDs of the log levels.
+#[derive(Debug)]
+struct LogLevels {
+    error: lv2_raw::LV2Urid,
+    warning: lv2_raw::LV2Urid,
+    trace: lv2_raw::LV2Urid,
+}
+
+impl LogLevels {
+    /// The `log::Level` for the log level with URID `type_`. `log:Note` and
+    /// unknown types are logged as `log::Level::Info`.
+    fn level(&self, type_: lv2_raw::LV2Urid) -> log::Level {
+        if type_ == self.error {
+            log::Level::Error
+        } else if type_ == self.warning {
+            log::Level::Warn
+        } else if type_ == self.trace {
+            log::Level::Trace
+        } else {
+            log::Level::Info
+        }
+    }
+}
+
+/// The handle of the `log:log` feature. Messages are queued here since
+/// plugins may log from the realtime thread.
+struct LogSender {
+    sender: MessageSender,
+    dropped_messages: Arc<AtomicUsize>,
+}
+
+/// Queue `message`, which starts with `size_of::<LV2Urid>()` bytes for the log
+/// level. The level is filled in here.
+fn queue_message(handle: lv2_sys::LV2_Log_Handle, type_: lv2_raw::LV2Urid, message: &mut [u8]) {
+    let sender = match unsafe { (handle as *const LogSender).as_ref() } {
+        Some(s) => s,
+        None => return,
+    };
+    message[..size_of::<lv2_raw::LV2Urid>()].copy_from_slice(&type_.to_ne_bytes());
+    let status = publish_message(&sender.sender, message.len(), message.as_ptr());
+    if status != lv2_sys::LV2_Worker_Status_LV2_WORKER_SUCCESS {
+        sender.dropped_messages.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+unsafe extern "C" fn do_vprintf(
+    handle: lv2_sys::LV2_Log_Handle,
+    type_: lv2_sys::LV2_URID,
+    fmt: *const c_char,
+    ap: VaList,
+) -> c_int {
+    if fmt.is_null() {
+        return 0;
+    }
+    let mut buffer = [0u8; QUEUED_MESSAGE_SIZE];
+    let text = &mut buffer[size_of::<lv2_raw::LV2Urid>()..];
+    let written = vsnprintf(text.as_mut_ptr().cast(), text.len(), fmt, ap);
+    if written < 0 {
+        return written;
+    }
+    // Like `printf`, the full length is returned even if the message was
+    // truncated.
+    let len = (written as usize).min(MESSAGE_CAPACITY - 1);
+    queue_message(
+        handle,
+        type_,
+        &mut buffer[..size_of::<lv2_raw::LV2Urid>() + len],
+    );
+    written
+}
+
+/// Logs the format string of a `printf` call without formatting it and
+/// returns its length.
+///
+/// Rust can not define C variadic functions, so the arguments can not be read.
+/// This function is only called through a pointer with the variadic `printf`
+/// signature. The fixed arguments are passed the same way for variadic and
+/// non-variadic functions, and the variadic arguments are ignored.
+unsafe extern "C" fn do_printf(
+    handle: lv2_sys::LV2_Log_Handle,
+    type_: lv2_sys::LV2_URID,
+    fmt: *const c_char,
+) -> c_int {
+    if fmt.is_null() {
+        return 0;
+    }
+    let fmt = CStr::from_ptr(fmt).to_bytes();
+    let len = fmt.len().min(MESSAGE_CAPACITY - 1);
+    let mut buffer = [0u8; QUEUED_MESSAGE_SIZE];
+    buffer[size_of::<lv2_raw::LV2Urid>()..size_of::<lv2_raw::LV2Urid>() + len]
+        .copy_from_slice(&fmt[..len]);
+    queue_message(
+        handle,
+        type_,
+        &mut buffer[..size_of::<lv2_raw::LV2Urid>() + len],
+    );
+    fmt.len() as c_int
+}
+
+/// The receiving end of the log queue. Messages are forwarded to the `log`
+/// crate by `flush`.
+pub(crate) struct LogReceiver {
+    levels: LogLevels,
+    receiver: WorkerMessageReceiver,
+    buffer: Vec<u8>,
+    dropped_messages: Arc<AtomicUsize>,
+    logged_dropped_messages: usize,
+}
+
+impl LogReceiver {
+    /// Forward all queued messages to the `log` crate. This is not realtime
+    /// safe.
+    pub(crate) fn flush(&mut self) {
+        let dropped_messages = self.dropped_messages.load(Ordering::Relaxed);
+        if dropped_messages > self.logged_dropped_messages {
+            log::warn!(
+                "{} plugin log messages were dropped because the log queue was full.",
+                dropped_messages - self.logged_dropped_messages
+            );
+            self.logged_dropped_messages = dropped_messages;
+        }
+        while self.receiver.len() > size_of::<usize>() {
+            let size = match pop_message(&mut self.receiver, &mut self.buffer) {
+                Some(size) => size,
+                None => continue,
+            };
+            let (type_, text) = self.buffer[..size].split_at(size_of::<lv2_raw::LV2Urid>());
+            let type_ = lv2_raw::LV2Urid::from_ne_bytes(type_.try_into().unwrap());
+            let text = String::from_utf8_lossy(text);
+            log::log!(self.levels.level(type_), "{}", text.trim_end());
+        }
+    }
+}
+
+impl std::fmt::Debug for LogReceiver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LogReceiver")
+            .field("levels", &self.levels)
+            .field("receiver", &"__internal__")
+            .field("dropped_messages", &self.dropped_messages)
+            .finish()
+    }
+}
+
+/// Provides the `log:log` feature. Messages from plugins are forwarded to the
+/// `log` crate. `log:Error`, `log:Warning`, `log:Note`, and `log:Trace` are
+/// logged with `log::Level::Error`, `Warn`, `Info`, and `Trace` respectively.
+///
+/// Only `vprintf` formats its arguments. `printf` logs the unformatted format
+/// string.
+///
+/// Plugins may log from the realtime thread, so messages are queued and
+/// forwarded by `Features::flush_log_messages`, which the worker threads call
+/// periodically.
+pub struct Log {
+    sender: LogSender,
+    receiver: Arc<Mutex<LogReceiver>>,
+    log_data: LogData,
+    log_feature: LV2Feature,
+    _pin: std::marker::PhantomPinned,
+}
+
+unsafe impl Send for Log {}
+
+impl Log {
+    pub fn new(urid_map: &UridMap) -> Pin<Box<Log>> {
+        let urid = |uri: &[u8]| urid_map.map(CStr::from_bytes_with_nul(uri).unwrap());
+        let (sender, receiver) = instantiate_queue(QUEUED_MESSAGE_SIZE, QUEUE_MESSAGES);
+        let dropped_messages = Arc::new(AtomicUsize::new(0));
+        let mut log = Box::pin(Log {
+            sender: LogSender {
+                sender: MessageSender::new(sender, QUEUED_MESSAGE_SIZE),
+                dropped_messages: dropped_messages.clone(),
+            },
+            receiver: Arc::new(Mutex::new(LogReceiver {
+                levels: LogLevels {
+                    error: urid(lv2_sys::LV2_LOG__Error),
+                    warning: urid(lv2_sys::LV2_LOG__Warning),
+                    trace: urid(lv2_sys::LV2_LOG__Trace),
+                },
+                receiver,
+                buffer: message_buffer(QUEUED_MESSAGE_SIZE),
+                dropped_messages,
+                logged_dropped_messages: 0,
+            })),
+            log_data: LogData {
+                handle: std::ptr::null_mut(),
+                printf: do_printf,
+                vprintf: do_vprintf,
+            },
+            log_feature: LV2Feature {
+                uri: lv2_sys::LV2_LOG__log.as_ptr().cast(),
+                data: std::ptr::null_mut(),
+            },
+            _pin: std::marker::PhantomPinned,
+        });
+        let sender_ptr = NonNull::from(&log.sender);
+        let data_ptr = NonNull::from(&log.log_data);
+        unsafe {
+            let mut_ref = Pin::get_unchecked_mut(Pin::as_mut(&mut log));
+            mut_ref.log_data.handle = sender_ptr.as_ptr().cast();
+            mut_ref.log_feature.data = data_ptr.as_ptr().cast();
+        }
+        log
+    }
+
+    pub fn as_feature(&self) -> &LV2Feature {
+        &self.log_feature
+    }
+
+    /// The receiving end of the log queue.
+    pub(crate) fn receiver(&self) -> &Arc<Mutex<LogReceiver>> {
+        &self.receiver
+    }
+}
+
+impl std::fmt::Debug for Log {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Log")
+            .field("receiver", &self.receiver)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_levels() {
+        let urid_map = UridMap::new();
+        let log = Log::new(&urid_map);
+        let urid = |uri: &[u8]| urid_map.map(CStr::from_bytes_with_nul(uri).unwrap());
+        let mut receiver = log.receiver().lock().unwrap();
+        assert_eq!(
+            receiver.levels.level(urid(lv2_sys::LV2_LOG__Error)),
+            log::Level::Error
+        );
+        assert_eq!(
+            receiver.levels.level(urid(lv2_sys::LV2_LOG__Warning)),
+            log::Level::Warn
+        );
+        assert_eq!(
+            receiver.levels.level(urid(lv2_sys::LV2_LOG__Note)),
+            log::Level::Info
+        );
+        assert_eq!(
+            receiver.levels.level(urid(lv2_sys::LV2_LOG__Trace)),
+            log::Level::Trace
+        );
+
+        let data = unsafe { &*(log.as_feature().data as *const lv2_sys::LV2_Log_Log) };
+        let printf = data.printf.unwrap();
+        let written = unsafe {
+            printf(
+                data.handle,
+                urid(lv2_sys::LV2_LOG__Note),
+                b"hello\n\0".as_ptr().cast(),
+            )
+        };
+        assert_eq!(written, 6);
+        assert!(!receiver.receiver.is_empty());
+        receiver.flush();
+        assert!(receiver.receiver.is_empty());
+    }
+
+    #[test]
+    fn test_full_log_queue_drops_messages() {
+        let urid_map = UridMap::new();
+        let log = Log::new(&urid_map);
+        let data = unsafe { &*(log.as_feature().data as *const lv2_sys::LV2_Log_Log) };
+        let printf = data.printf.unwrap();
+        let message = vec![b'a'; MESSAGE_CAPACITY - 1]
+            .into_iter()
+            .chain(std::iter::once(0))
+            .collect::<Vec<u8>>();
+        for _ in 0..=QUEUE_MESSAGES {
+            unsafe { printf(data.handle, 0, message.as_ptr().cast()) };
+        }
+        let mut receiver = log.receiver().lock().unwrap();
+        assert_eq!(receiver.dropped_messages.load(Ordering::Relaxed), 1);
+        receiver.flush();
+        assert_eq!(receiver.logged_dropped_messages, 1);
+        assert!(receiver.receiver.is_empty());
+    }
+}
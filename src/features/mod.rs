@@ -1,12 +1,21 @@
-use crate::WorkerManager;
+use crate::{CommonUrids, WorkerManager};
 use lv2_raw::LV2Feature;
-use lv2_sys::LV2_BUF_SIZE__boundedBlockLength;
+use lv2_sys::{
+    LV2_BUF_SIZE__boundedBlockLength, LV2_BUF_SIZE__fixedBlockLength,
+    LV2_BUF_SIZE__powerOf2BlockLength,
+};
+use std::path::PathBuf;
 use std::pin::Pin;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
-use std::{collections::HashSet, ffi::CStr};
+use std::{
+    collections::HashSet,
+    ffi::{CStr, CString},
+};
 
+pub mod log;
 pub mod options;
+pub mod state_path;
 pub mod urid_map;
 pub mod worker;
 
@@ -19,6 +28,39 @@ pub struct FeaturesBuilder {
     /// The maximum block size. If plugins try to process more samples than this
     /// on a single `run` call, an error will be returned.
     pub max_block_length: usize,
+    /// Maps the paths that plugins store in their state. If set, it is
+    /// provided to plugins through the `state:mapPath` feature.
+    pub map_path: Option<state_path::PathMapping>,
+    /// The directory that plugins create files in. If set, it is provided to
+    /// plugins through the `state:makePath` feature. All instances that
+    /// share the `Features` share the directory.
+    pub state_dir: Option<PathBuf>,
+    /// The number of threads that run the workers of plugins. Each worker is
+    /// only run by one thread at a time, but the workers of different plugins
+    /// run in parallel. Values less than 1 are treated as 1.
+    pub worker_thread_count: usize,
+    /// The size in bytes of the largest message that plugins can send to
+    /// their worker and that workers can respond with. Larger messages are
+    /// rejected with `LV2_WORKER_ERR_NO_SPACE`. Each instance with a worker
+    /// allocates queues for a few messages of this size.
+    pub worker_max_message_size: usize,
+    /// The number of messages of `worker_max_message_size` bytes that fit into
+    /// each worker queue. More messages fit if they are smaller. Increase this
+    /// for plugins that schedule many pieces of work in a single `run`. Values
+    /// less than 1 are treated as 1.
+    pub worker_queue_messages: usize,
+    /// The worker manager that runs the workers of plugins. If set, the
+    /// manager can be shared by several `Features` and no worker threads are
+    /// started; the host is responsible for calling `run_workers` or
+    /// `run_workers_blocking`. If `None`, a new manager is created and run on
+    /// `worker_thread_count` background threads.
+    pub worker_manager: Option<Arc<WorkerManager>>,
+    /// If `true`, the worker manager created by `build` is run on
+    /// `worker_thread_count` background threads. Set this to `false` to run
+    /// the workers on a thread of the host's choosing by calling
+    /// `Features::worker_manager().run_workers()`. This has no effect if
+    /// `worker_manager` is set.
+    pub run_worker_thread: bool,
 }
 
 impl Default for FeaturesBuilder {
@@ -26,6 +68,13 @@ impl Default for FeaturesBuilder {
         FeaturesBuilder {
             min_block_length: 1,
             max_block_length: 4096,
+            map_path: None,
+            state_dir: None,
+            worker_thread_count: 1,
+            worker_max_message_size: worker::DEFAULT_MAX_MESSAGE_SIZE,
+            worker_queue_messages: worker::DEFAULT_QUEUE_MESSAGES,
+            worker_manager: None,
+            run_worker_thread: true,
         }
     }
 }
@@ -33,19 +82,42 @@ impl Default for FeaturesBuilder {
 impl FeaturesBuilder {
     /// Build a new `Features` object.
     pub fn build(self, _world: &crate::World) -> Arc<Features> {
-        let worker_manager = Arc::new(WorkerManager::default());
+        let (worker_manager, worker_thread_count) = match self.worker_manager {
+            // The workers of an external manager are run by the host.
+            Some(worker_manager) => (worker_manager, 0),
+            None => {
+                let worker_thread_count = self.worker_thread_count.max(1);
+                (
+                    Arc::new(WorkerManager::with_thread_count(worker_thread_count)),
+                    if self.run_worker_thread {
+                        worker_thread_count
+                    } else {
+                        0
+                    },
+                )
+            }
+        };
         let keep_worker_thread_alive = Arc::new(AtomicBool::new(true));
+        let urid_map = urid_map::UridMap::new();
+        let log = log::Log::new(&urid_map);
 
-        let keep_alive = keep_worker_thread_alive.clone();
-        let workers = worker_manager.clone();
-        let worker_thread = std::thread::spawn(move || {
-            while keep_alive.load(std::sync::atomic::Ordering::Relaxed) {
-                workers.run_workers();
-                std::thread::sleep(std::time::Duration::from_millis(100));
-            }
-        });
+        let worker_threads = (0..worker_thread_count)
+            .map(|_| {
+                let keep_alive = keep_worker_thread_alive.clone();
+                let workers = worker_manager.clone();
+                let log_receiver = log.receiver().clone();
+                std::thread::spawn(move || {
+                    while keep_alive.load(std::sync::atomic::Ordering::Relaxed) {
+                        workers.run_workers_blocking();
+                        log_receiver.lock().unwrap().flush();
+                    }
+                })
+            })
+            .collect();
+        let common_urids = CommonUrids::new(&urid_map);
         let mut features = Features {
-            urid_map: urid_map::UridMap::new(),
+            urid_map,
+            common_urids,
             options: options::Options::new(),
             min_block_length: self.min_block_length,
             max_block_length: self.max_block_length,
@@ -53,26 +125,29 @@ impl FeaturesBuilder {
                 uri: LV2_BUF_SIZE__boundedBlockLength.as_ptr().cast(),
                 data: std::ptr::null_mut(),
             },
+            fixed_block_length: (self.min_block_length == self.max_block_length).then(|| {
+                LV2Feature {
+                    uri: LV2_BUF_SIZE__fixedBlockLength.as_ptr().cast(),
+                    data: std::ptr::null_mut(),
+                }
+            }),
+            power_of_2_block_length: (self.min_block_length.is_power_of_two()
+                && self.max_block_length.is_power_of_two())
+            .then(|| LV2Feature {
+                uri: LV2_BUF_SIZE__powerOf2BlockLength.as_ptr().cast(),
+                data: std::ptr::null_mut(),
+            }),
+            map_path: self.map_path.map(state_path::MapPath::new),
+            free_path: state_path::FreePath::new(),
+            make_path: self.state_dir.map(state_path::MakePath::new),
+            log,
             worker_manager,
-            _worker_thread: worker_thread,
+            worker_max_message_size: self.worker_max_message_size,
+            worker_queue_messages: self.worker_queue_messages.max(1),
+            _worker_threads: worker_threads,
             keep_worker_thread_alive,
         };
-        features.options.set_int_option(
-            &features.urid_map,
-            features.urid_map.map(
-                CStr::from_bytes_with_nul(b"http://lv2plug.in/ns/ext/buf-size#minBlockLength\0")
-                    .unwrap(),
-            ),
-            self.min_block_length as i32,
-        );
-        features.options.set_int_option(
-            &features.urid_map,
-            features.urid_map.map(
-                CStr::from_bytes_with_nul(b"http://lv2plug.in/ns/ext/buf-size#maxBlockLength\0")
-                    .unwrap(),
-            ),
-            self.max_block_length as i32,
-        );
+        features.options = features.build_options(None);
         Arc::new(features)
     }
 }
@@ -80,12 +155,24 @@ impl FeaturesBuilder {
 /// `Features` are used to provide functionality to plugins.
 pub struct Features {
     urid_map: Pin<Box<urid_map::UridMap>>,
+    common_urids: CommonUrids,
     options: options::Options,
     bounded_block_length: LV2Feature,
+    // Only provided if the minimum and maximum block length are equal.
+    fixed_block_length: Option<LV2Feature>,
+    // Only provided if the minimum and maximum block length are powers of 2.
+    // `Instance::run` only accepts powers of 2 for plugins that use it.
+    power_of_2_block_length: Option<LV2Feature>,
     min_block_length: usize,
     max_block_length: usize,
+    map_path: Option<Pin<Box<state_path::MapPath>>>,
+    free_path: Pin<Box<state_path::FreePath>>,
+    make_path: Option<Pin<Box<state_path::MakePath>>>,
+    log: Pin<Box<log::Log>>,
     worker_manager: Arc<WorkerManager>,
-    _worker_thread: std::thread::JoinHandle<()>,
+    worker_max_message_size: usize,
+    worker_queue_messages: usize,
+    _worker_threads: Vec<std::thread::JoinHandle<()>>,
     keep_worker_thread_alive: Arc<AtomicBool>,
 }
 
@@ -100,20 +187,136 @@ impl Features {
             "http://lv2plug.in/ns/ext/urid#unmap",
             "http://lv2plug.in/ns/ext/options#options",
             "http://lv2plug.in/ns/ext/buf-size#boundedBlockLength",
+            "http://lv2plug.in/ns/ext/buf-size#fixedBlockLength",
+            "http://lv2plug.in/ns/ext/buf-size#powerOf2BlockLength",
             "http://lv2plug.in/ns/ext/worker#schedule",
+            "http://lv2plug.in/ns/ext/state#freePath",
+            "http://lv2plug.in/ns/ext/log#log",
+            // `Instance::run` rejects overlapping inputs and outputs.
+            "http://lv2plug.in/ns/lv2core#inPlaceBroken",
         ])
     }
 
+    /// Get the URIs for features that are provided only if they are
+    /// configured in the `FeaturesBuilder`. Plugins that require them are
+    /// loaded, but can only be instantiated with `Features` that provide them.
+    pub fn configurable_features() -> HashSet<&'static str> {
+        HashSet::from([
+            "http://lv2plug.in/ns/ext/state#mapPath",
+            "http://lv2plug.in/ns/ext/state#makePath",
+        ])
+    }
+
+    /// Get the URIs for all features that are provided to plugins. This is
+    /// `supported_features` along with the configurable features that have been
+    /// set up in the `FeaturesBuilder`.
+    pub fn provided_features(&self) -> HashSet<&'static str> {
+        let mut features = Features::supported_features();
+        if self.map_path.is_some() {
+            features.insert("http://lv2plug.in/ns/ext/state#mapPath");
+        }
+        if self.make_path.is_some() {
+            features.insert("http://lv2plug.in/ns/ext/state#makePath");
+        }
+        features
+    }
+
     /// Iterate over all the LV2 features.
     pub fn iter_features<'a>(
         &'a self,
         worker_feature: &'a LV2Feature,
+    ) -> impl Iterator<Item = &'a LV2Feature> {
+        self.iter_features_with_options(worker_feature, &self.options)
+    }
+
+    /// Iterate over all the LV2 features with `options` as the
+    /// `options:options` feature.
+    pub(crate) fn iter_features_with_options<'a>(
+        &'a self,
+        worker_feature: &'a LV2Feature,
+        options: &'a options::Options,
     ) -> impl Iterator<Item = &'a LV2Feature> {
         std::iter::once(self.urid_map.as_urid_map_feature())
             .chain(std::iter::once(self.urid_map.as_urid_unmap_feature()))
-            .chain(std::iter::once(self.options.as_feature()))
+            .chain(std::iter::once(options.as_feature()))
             .chain(std::iter::once(&self.bounded_block_length))
+            .chain(self.fixed_block_length.as_ref())
+            .chain(self.power_of_2_block_length.as_ref())
             .chain(std::iter::once(worker_feature))
+            .chain(self.map_path.as_ref().map(|m| m.as_feature()))
+            .chain(std::iter::once(self.free_path.as_feature()))
+            .chain(self.make_path.as_ref().map(|m| m.as_feature()))
+            .chain(std::iter::once(self.log.as_feature()))
+    }
+
+    /// Iterate over the LV2 features that are passed to `state:interface`
+    /// functions.
+    pub(crate) fn iter_state_features(&self) -> impl Iterator<Item = &'_ LV2Feature> {
+        self.map_path
+            .as_ref()
+            .map(|m| m.as_feature())
+            .into_iter()
+            .chain(std::iter::once(self.free_path.as_feature()))
+            .chain(self.make_path.as_ref().map(|m| m.as_feature()))
+    }
+
+    /// Build the options that are provided to plugins. If `sample_rate` is
+    /// set, then it is provided as `param:sampleRate`.
+    pub(crate) fn build_options(&self, sample_rate: Option<f64>) -> options::Options {
+        let mut options = options::Options::new();
+        self.set_block_length_options(&mut options, self.min_block_length, self.max_block_length);
+        if let Some(sample_rate) = sample_rate {
+            options.set_float_option(
+                &self.urid_map,
+                self.urid(
+                    CStr::from_bytes_with_nul(b"http://lv2plug.in/ns/ext/parameters#sampleRate\0")
+                        .unwrap(),
+                ),
+                sample_rate as f32,
+            );
+        }
+        options
+    }
+
+    /// Set the `buf-size:minBlockLength` and `buf-size:maxBlockLength` options
+    /// of `options`.
+    pub(crate) fn set_block_length_options(
+        &self,
+        options: &mut options::Options,
+        min_block_length: usize,
+        max_block_length: usize,
+    ) {
+        let urid = |uri: &[u8]| self.urid(CStr::from_bytes_with_nul(uri).unwrap());
+        options.set_int_option(
+            &self.urid_map,
+            urid(b"http://lv2plug.in/ns/ext/buf-size#minBlockLength\0"),
+            min_block_length as i32,
+        );
+        options.set_int_option(
+            &self.urid_map,
+            urid(b"http://lv2plug.in/ns/ext/buf-size#maxBlockLength\0"),
+            max_block_length as i32,
+        );
+    }
+
+    /// Returns `true` if `buf-size:fixedBlockLength` is provided to plugins.
+    pub(crate) fn provides_fixed_block_length(&self) -> bool {
+        self.fixed_block_length.is_some()
+    }
+
+    /// Returns `true` if `buf-size:powerOf2BlockLength` is provided to plugins.
+    pub(crate) fn provides_power_of_2_block_length(&self) -> bool {
+        self.power_of_2_block_length.is_some()
+    }
+
+    /// Forward the messages that plugins have logged through `log:log` to the
+    /// `log` crate. The worker threads do this periodically, so this only has
+    /// to be called if `FeaturesBuilder::run_worker_thread` is `false` or
+    /// `FeaturesBuilder::worker_manager` is set.
+    ///
+    /// This is not realtime safe.
+    pub fn flush_log_messages(&self) {
+        self.log.receiver().lock().unwrap().flush();
     }
 
     /// The minimum allowed block length.
@@ -131,12 +334,30 @@ impl Features {
         self.urid_map.map(uri)
     }
 
+    /// The urid for the given uri. This is like `urid` but allocates a
+    /// null terminated copy of `uri` so it is not realtime safe.
+    ///
+    /// # Panics
+    /// Panics if `uri` contains a null byte.
+    pub fn urid_str(&self, uri: &str) -> u32 {
+        let uri = CString::new(uri).expect("uri contains a null byte");
+        self.urid_map.map(&uri)
+    }
+
     /// The urid for midi.
     pub fn midi_urid(&self) -> lv2_raw::LV2Urid {
-        self.urid(
-            std::ffi::CStr::from_bytes_with_nul(b"http://lv2plug.in/ns/ext/midi#MidiEvent\0")
-                .unwrap(),
-        )
+        self.common_urids.midi_event
+    }
+
+    /// The urids of commonly used uris. These are mapped once when the
+    /// `Features` are built.
+    pub fn common_urids(&self) -> CommonUrids {
+        self.common_urids
+    }
+
+    /// A pointer to the `LV2_URID_Map` that is provided to plugins.
+    pub(crate) fn urid_map_ptr(&self) -> *mut lv2_raw::LV2UridMap {
+        self.urid_map.as_urid_map_feature().data.cast()
     }
 
     /// The uri for the given urid.
@@ -144,23 +365,55 @@ impl Features {
         self.urid_map.unmap(urid)
     }
 
-    /// The worker manager. This is automatically run periodically to perform
+    /// All uris that have been mapped so far along with their urids, sorted
+    /// by urid. This is a snapshot and is not realtime safe.
+    pub fn iter_urids(&self) -> Vec<(lv2_raw::LV2Urid, String)> {
+        self.urid_map.entries()
+    }
+
+    /// The worker manager. Unless it was provided through
+    /// `FeaturesBuilder::worker_manager` or `FeaturesBuilder::run_worker_thread`
+    /// is `false`, it is run automatically on background threads to perform
     /// any asynchronous work that plugins have scheduled.
     pub fn worker_manager(&self) -> &Arc<WorkerManager> {
         &self.worker_manager
     }
+
+    /// The size in bytes of the largest message that can be sent to or from a
+    /// worker. See `FeaturesBuilder::worker_max_message_size`.
+    pub fn worker_max_message_size(&self) -> usize {
+        self.worker_max_message_size
+    }
+
+    /// The number of messages of the largest size that fit into each worker
+    /// queue. See `FeaturesBuilder::worker_queue_messages`.
+    pub fn worker_queue_messages(&self) -> usize {
+        self.worker_queue_messages
+    }
 }
 
 impl std::fmt::Debug for Features {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Features")
             .field("urid_map", &self.urid_map)
+            .field("common_urids", &self.common_urids)
             .field("options", &self.options)
             .field("bounded_block_length", &"__uri__")
+            .field("fixed_block_length", &self.fixed_block_length.is_some())
+            .field(
+                "power_of_2_block_length",
+                &self.power_of_2_block_length.is_some(),
+            )
             .field("min_block_length", &self.min_block_length)
             .field("max_block_length", &self.max_block_length)
+            .field("map_path", &self.map_path)
+            .field("free_path", &self.free_path)
+            .field("make_path", &self.make_path)
+            .field("log", &self.log)
             .field("worker_manager", &self.worker_manager)
-            .field("_worker_thread", &self._worker_thread)
+            .field("worker_max_message_size", &self.worker_max_message_size)
+            .field("worker_queue_messages", &self.worker_queue_messages)
+            .field("_worker_threads", &self._worker_threads)
             .field("keep_worker_thread_alive", &self.keep_worker_thread_alive)
             .finish()
     }
@@ -170,5 +423,9 @@ impl Drop for Features {
     fn drop(&mut self) {
         self.keep_worker_thread_alive
             .store(false, std::sync::atomic::Ordering::Relaxed);
+        if !self._worker_threads.is_empty() {
+            self.worker_manager.wake();
+        }
+        self.flush_log_messages();
     }
 }
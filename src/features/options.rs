@@ -14,9 +14,50 @@ const EMPTY_OPTION: LV2_Options_Option = LV2_Options_Option {
     value: std::ptr::null(),
 };
 
+/// The value of an option. See http://lv2plug.in/ns/ext/options.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OptionValue {
+    /// An `atom:Int` value.
+    Int(i32),
+
+    /// An `atom:Float` value.
+    Float(f32),
+}
+
+impl OptionValue {
+    /// Read the value of `option`. Returns `None` if the type of the option is
+    /// not `atom:Int` or `atom:Float`.
+    pub(crate) unsafe fn from_option(
+        option: &LV2_Options_Option,
+        int_urid: LV2Urid,
+        float_urid: LV2Urid,
+    ) -> Option<OptionValue> {
+        if option.value.is_null() || option.size as usize != std::mem::size_of::<i32>() {
+            return None;
+        }
+        if option.type_ == int_urid {
+            Some(OptionValue::Int(*option.value.cast::<i32>()))
+        } else if option.type_ == float_urid {
+            Some(OptionValue::Float(*option.value.cast::<f32>()))
+        } else {
+            None
+        }
+    }
+
+    /// The bits of the value as they are passed to plugins. Plugins read the
+    /// value as an `int32_t` or `float`, so it must be stored with the
+    /// alignment of a `u32`.
+    pub(crate) fn to_bits(self) -> u32 {
+        match self {
+            OptionValue::Int(v) => v as u32,
+            OptionValue::Float(v) => v.to_bits(),
+        }
+    }
+}
+
 pub struct Options {
     data: Vec<lv2_sys::LV2_Options_Option>,
-    values: HashMap<LV2Urid, Box<i32>>,
+    values: HashMap<LV2Urid, Box<u32>>,
     feature: LV2Feature,
 }
 
@@ -46,25 +87,49 @@ impl Options {
         key: LV2Urid,
         value: i32,
     ) {
+        let type_ = urid_map
+            .map(CStr::from_bytes_with_nul(b"http://lv2plug.in/ns/ext/atom#Int\0").unwrap());
+        self.set_option(key, type_, OptionValue::Int(value));
+    }
+
+    pub fn set_float_option(
+        &mut self,
+        urid_map: &crate::features::urid_map::UridMap,
+        key: LV2Urid,
+        value: f32,
+    ) {
+        let type_ = urid_map
+            .map(CStr::from_bytes_with_nul(b"http://lv2plug.in/ns/ext/atom#Float\0").unwrap());
+        self.set_option(key, type_, OptionValue::Float(value));
+    }
+
+    fn set_option(&mut self, key: LV2Urid, type_: LV2Urid, value: OptionValue) {
         if let Some(v) = self.values.get_mut(&key) {
-            *v.as_mut() = value;
+            *v.as_mut() = value.to_bits();
+            if let Some(option) = self.data.iter_mut().find(|o| o.key == key) {
+                option.type_ = type_;
+            }
             return;
         }
-        let value = Box::new(value);
-        let value_ptr = value.as_ref() as *const i32;
+        let value = Box::new(value.to_bits());
+        let value_ptr = value.as_ref() as *const u32;
         self.values.insert(key, value);
         self.push_option(LV2_Options_Option {
-            context: 0,
+            context: lv2_sys::LV2_Options_Context_LV2_OPTIONS_INSTANCE,
             subject: 0,
             key,
-            size: u32::try_from(std::mem::size_of::<i32>())
+            size: u32::try_from(std::mem::size_of::<u32>())
                 .expect("Size exceeded capacity of u32."),
-            type_: urid_map
-                .map(CStr::from_bytes_with_nul(b"http://lv2plug.in/ns/ext/atom#Int\0").unwrap()),
+            type_,
             value: value_ptr.cast(),
         });
     }
 
+    /// The option with `key` or `None` if it is not set.
+    pub fn get(&self, key: LV2Urid) -> Option<&LV2_Options_Option> {
+        self.data.iter().find(|o| o.key == key && key != 0)
+    }
+
     fn push_option(&mut self, option: LV2_Options_Option) {
         self.data.pop(); // Remove the last `EMPTY_OPTION`.
         self.data.push(option);
@@ -82,3 +147,58 @@ impl std::fmt::Debug for Options {
             .finish()
     }
 }
+
+/// Get the options interface of `instance` or `None` if the plugin does not
+/// implement `options:interface`.
+pub(crate) unsafe fn maybe_get_options_interface(
+    instance: &lilv::instance::ActiveInstance,
+) -> Option<lv2_sys::LV2_Options_Interface> {
+    if !crate::plugin::has_extension_data_fn(instance.instance()) {
+        return None;
+    }
+    Some(
+        *instance
+            .instance()
+            .extension_data::<lv2_sys::LV2_Options_Interface>(
+                "http://lv2plug.in/ns/ext/options#interface",
+            )?
+            .as_ref(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::urid_map::UridMap;
+
+    #[test]
+    fn test_set_and_get_options() {
+        let urid_map = UridMap::new();
+        let urid = |uri: &[u8]| urid_map.map(CStr::from_bytes_with_nul(uri).unwrap());
+        let int_urid = urid(b"http://lv2plug.in/ns/ext/atom#Int\0");
+        let float_urid = urid(b"http://lv2plug.in/ns/ext/atom#Float\0");
+        let block_length = urid(b"http://lv2plug.in/ns/ext/buf-size#maxBlockLength\0");
+        let sample_rate = urid(b"http://lv2plug.in/ns/ext/parameters#sampleRate\0");
+
+        let mut options = Options::new();
+        options.set_int_option(&urid_map, block_length, 256);
+        options.set_float_option(&urid_map, sample_rate, 44100.0);
+        options.set_float_option(&urid_map, sample_rate, 48000.0);
+        let value = |key| unsafe {
+            OptionValue::from_option(options.get(key).unwrap(), int_urid, float_urid)
+        };
+        assert_eq!(value(block_length), Some(OptionValue::Int(256)));
+        assert_eq!(value(sample_rate), Some(OptionValue::Float(48000.0)));
+        assert!(options.get(urid(b"http://example.com/unset\0")).is_none());
+        // Plugins read the values as `int32_t` or `float`.
+        for key in [block_length, sample_rate] {
+            let value = options.get(key).unwrap().value;
+            assert_eq!(value as usize % std::mem::align_of::<u32>(), 0);
+        }
+
+        // The options that are passed to plugins end with an empty option.
+        let data = options.as_feature().data as *const LV2_Options_Option;
+        let last = unsafe { &*data.add(2) };
+        assert_eq!((last.key, last.value), (0, std::ptr::null()));
+    }
+}
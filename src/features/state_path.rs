@@ -0,0 +1,342 @@
+use core::ffi::c_void;
+use log::error;
+use lv2_raw::LV2Feature;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::ptr::NonNull;
+use std::sync::Arc;
+
+type MapFn = dyn Fn(&Path) -> PathBuf + Send + Sync;
+
+/// Maps between absolute paths and the abstract paths that plugins store in
+/// their state. This is provided to plugins through the `state:mapPath`
+/// feature. Hosts can use it to store paths relative to a session directory so
+/// that sessions can be moved between machines.
+///
+/// #### Example usage:
+/// ```
+/// let mapping = livi::PathMapping::with_directory("/home/me/session");
+/// assert_eq!(
+///     mapping.abstract_path("/home/me/session/kick.wav".as_ref()),
+///     std::path::PathBuf::from("kick.wav")
+/// );
+/// assert_eq!(
+///     mapping.absolute_path("kick.wav".as_ref()),
+///     std::path::PathBuf::from("/home/me/session/kick.wav")
+/// );
+/// ```
+#[derive(Clone)]
+pub struct PathMapping {
+    to_abstract: Arc<MapFn>,
+    to_absolute: Arc<MapFn>,
+}
+
+impl PathMapping {
+    /// Create a new mapping. `to_abstract` maps an absolute path to the path
+    /// that is stored in the plugin state and `to_absolute` maps it back.
+    pub fn new<A, B>(to_abstract: A, to_absolute: B) -> PathMapping
+    where
+        A: 'static + Fn(&Path) -> PathBuf + Send + Sync,
+        B: 'static + Fn(&Path) -> PathBuf + Send + Sync,
+    {
+        PathMapping {
+            to_abstract: Arc::new(to_abstract),
+            to_absolute: Arc::new(to_absolute),
+        }
+    }
+
+    /// Create a new mapping where abstract paths are relative to `directory`.
+    /// Paths outside of `directory` are left as they are.
+    pub fn with_directory<P: Into<PathBuf>>(directory: P) -> PathMapping {
+        let directory: Arc<Path> = directory.into().into();
+        let abstract_directory = directory.clone();
+        PathMapping::new(
+            move |path| match path.strip_prefix(&abstract_directory) {
+                Ok(relative) => relative.to_path_buf(),
+                Err(_) => path.to_path_buf(),
+            },
+            // Joining an absolute path returns the absolute path.
+            move |path| directory.join(path),
+        )
+    }
+
+    /// Map an absolute path to an abstract path.
+    pub fn abstract_path(&self, absolute_path: &Path) -> PathBuf {
+        (self.to_abstract)(absolute_path)
+    }
+
+    /// Map an abstract path to an absolute path.
+    pub fn absolute_path(&self, abstract_path: &Path) -> PathBuf {
+        (self.to_absolute)(abstract_path)
+    }
+}
+
+impl std::fmt::Debug for PathMapping {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PathMapping")
+            .field("to_abstract", &"__fn__")
+            .field("to_absolute", &"__fn__")
+            .finish()
+    }
+}
+
+/// Copy `path` into a string that is allocated with `malloc`. Plugins free the
+/// returned paths with `free` or through the `state:freePath` feature.
+fn to_malloc_string(path: &Path) -> *mut c_char {
+    let path = match CString::new(path.to_string_lossy().as_bytes()) {
+        Ok(p) => p,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    let bytes = path.as_bytes_with_nul();
+    unsafe {
+        let ptr: *mut c_char = libc::malloc(bytes.len()).cast();
+        if !ptr.is_null() {
+            std::ptr::copy_nonoverlapping(bytes.as_ptr().cast(), ptr, bytes.len());
+        }
+        ptr
+    }
+}
+
+unsafe fn map_with(
+    handle: *mut c_void,
+    path: *const c_char,
+    f: fn(&PathMapping, &Path) -> PathBuf,
+) -> *mut c_char {
+    let mapping = match (handle as *const PathMapping).as_ref() {
+        Some(m) => m,
+        None => return std::ptr::null_mut(),
+    };
+    if path.is_null() {
+        return std::ptr::null_mut();
+    }
+    let path = CStr::from_ptr(path).to_string_lossy();
+    to_malloc_string(&f(mapping, Path::new(path.as_ref())))
+}
+
+unsafe extern "C" fn do_abstract_path(handle: *mut c_void, path: *const c_char) -> *mut c_char {
+    map_with(handle, path, PathMapping::abstract_path)
+}
+
+unsafe extern "C" fn do_absolute_path(handle: *mut c_void, path: *const c_char) -> *mut c_char {
+    map_with(handle, path, PathMapping::absolute_path)
+}
+
+unsafe extern "C" fn do_free_path(_handle: *mut c_void, path: *mut c_char) {
+    libc::free(path.cast());
+}
+
+/// Provides the `state:mapPath` feature.
+pub struct MapPath {
+    mapping: PathMapping,
+    map_path_data: lv2_sys::LV2_State_Map_Path,
+    map_path_feature: LV2Feature,
+    _pin: std::marker::PhantomPinned,
+}
+
+unsafe impl Send for MapPath {}
+
+impl MapPath {
+    pub fn new(mapping: PathMapping) -> Pin<Box<MapPath>> {
+        let mut map_path = Box::pin(MapPath {
+            mapping,
+            map_path_data: lv2_sys::LV2_State_Map_Path {
+                handle: std::ptr::null_mut(),
+                abstract_path: Some(do_abstract_path),
+                absolute_path: Some(do_absolute_path),
+            },
+            map_path_feature: LV2Feature {
+                uri: lv2_sys::LV2_STATE__mapPath.as_ptr().cast(),
+                data: std::ptr::null_mut(),
+            },
+            _pin: std::marker::PhantomPinned,
+        });
+        let mapping_ptr = NonNull::from(&map_path.mapping);
+        let data_ptr = NonNull::from(&map_path.map_path_data);
+        unsafe {
+            let mut_ref = Pin::get_unchecked_mut(Pin::as_mut(&mut map_path));
+            mut_ref.map_path_data.handle = mapping_ptr.as_ptr().cast();
+            mut_ref.map_path_feature.data = data_ptr.as_ptr().cast();
+        }
+        map_path
+    }
+
+    pub fn as_feature(&self) -> &LV2Feature {
+        &self.map_path_feature
+    }
+}
+
+impl std::fmt::Debug for MapPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MapPath")
+            .field("mapping", &self.mapping)
+            .finish()
+    }
+}
+
+/// Provides the `state:freePath` feature. Plugins use this to free the paths
+/// that are returned by `state:mapPath`.
+pub struct FreePath {
+    free_path_data: lv2_sys::LV2_State_Free_Path,
+    free_path_feature: LV2Feature,
+    _pin: std::marker::PhantomPinned,
+}
+
+unsafe impl Send for FreePath {}
+
+impl FreePath {
+    pub fn new() -> Pin<Box<FreePath>> {
+        let mut free_path = Box::pin(FreePath {
+            free_path_data: lv2_sys::LV2_State_Free_Path {
+                handle: std::ptr::null_mut(),
+                free_path: Some(do_free_path),
+            },
+            free_path_feature: LV2Feature {
+                uri: lv2_sys::LV2_STATE__freePath.as_ptr().cast(),
+                data: std::ptr::null_mut(),
+            },
+            _pin: std::marker::PhantomPinned,
+        });
+        let data_ptr = NonNull::from(&free_path.free_path_data);
+        unsafe {
+            let mut_ref = Pin::get_unchecked_mut(Pin::as_mut(&mut free_path));
+            mut_ref.free_path_feature.data = data_ptr.as_ptr().cast();
+        }
+        free_path
+    }
+
+    pub fn as_feature(&self) -> &LV2Feature {
+        &self.free_path_feature
+    }
+}
+
+impl std::fmt::Debug for FreePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FreePath").finish()
+    }
+}
+
+/// Join `path` to `directory`, dropping any root and `..` components so that
+/// the result is always within `directory`.
+fn path_within(directory: &Path, path: &Path) -> PathBuf {
+    let mut result = directory.to_path_buf();
+    for component in path.components() {
+        if let std::path::Component::Normal(c) = component {
+            result.push(c);
+        }
+    }
+    result
+}
+
+unsafe extern "C" fn do_make_path(handle: *mut c_void, path: *const c_char) -> *mut c_char {
+    let directory = match (handle as *const PathBuf).as_ref() {
+        Some(d) => d,
+        None => return std::ptr::null_mut(),
+    };
+    if path.is_null() {
+        return std::ptr::null_mut();
+    }
+    let path = CStr::from_ptr(path).to_string_lossy();
+    let path = path_within(directory, Path::new(path.as_ref()));
+    if let Some(parent) = path.parent() {
+        if let Err(err) = std::fs::create_dir_all(parent) {
+            error!("Could not create directory {:?}: {}", parent, err);
+            return std::ptr::null_mut();
+        }
+    }
+    to_malloc_string(&path)
+}
+
+/// Provides the `state:makePath` feature. Plugins use this to get paths for
+/// the files that they create. All paths are within `directory`.
+pub struct MakePath {
+    directory: PathBuf,
+    make_path_data: lv2_sys::LV2_State_Make_Path,
+    make_path_feature: LV2Feature,
+    _pin: std::marker::PhantomPinned,
+}
+
+unsafe impl Send for MakePath {}
+
+impl MakePath {
+    pub fn new(directory: PathBuf) -> Pin<Box<MakePath>> {
+        let mut make_path = Box::pin(MakePath {
+            directory,
+            make_path_data: lv2_sys::LV2_State_Make_Path {
+                handle: std::ptr::null_mut(),
+                path: Some(do_make_path),
+            },
+            make_path_feature: LV2Feature {
+                uri: lv2_sys::LV2_STATE__makePath.as_ptr().cast(),
+                data: std::ptr::null_mut(),
+            },
+            _pin: std::marker::PhantomPinned,
+        });
+        let directory_ptr = NonNull::from(&make_path.directory);
+        let data_ptr = NonNull::from(&make_path.make_path_data);
+        unsafe {
+            let mut_ref = Pin::get_unchecked_mut(Pin::as_mut(&mut make_path));
+            mut_ref.make_path_data.handle = directory_ptr.as_ptr().cast();
+            mut_ref.make_path_feature.data = data_ptr.as_ptr().cast();
+        }
+        make_path
+    }
+
+    pub fn as_feature(&self) -> &LV2Feature {
+        &self.make_path_feature
+    }
+}
+
+impl std::fmt::Debug for MakePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MakePath")
+            .field("directory", &self.directory)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_path_feature_maps_relative_to_directory() {
+        let map_path = MapPath::new(PathMapping::with_directory("/session"));
+        let data = unsafe { &*(map_path.as_feature().data as *const lv2_sys::LV2_State_Map_Path) };
+        let free_path = FreePath::new();
+        let free_data =
+            unsafe { &*(free_path.as_feature().data as *const lv2_sys::LV2_State_Free_Path) };
+        let map = |f: unsafe extern "C" fn(*mut c_void, *const c_char) -> *mut c_char,
+                   path: &[u8]| unsafe {
+            let ptr = f(data.handle, path.as_ptr().cast());
+            let mapped = CStr::from_ptr(ptr).to_str().unwrap().to_string();
+            (free_data.free_path.unwrap())(free_data.handle, ptr);
+            mapped
+        };
+        let abstract_path = data.abstract_path.unwrap();
+        let absolute_path = data.absolute_path.unwrap();
+        assert_eq!(map(abstract_path, b"/session/a/b.wav\0"), "a/b.wav");
+        assert_eq!(map(abstract_path, b"/other/c.wav\0"), "/other/c.wav");
+        assert_eq!(map(absolute_path, b"a/b.wav\0"), "/session/a/b.wav");
+    }
+
+    #[test]
+    fn test_make_path_feature_creates_directories() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let directory = tempdir.path().to_path_buf();
+        let make_path = MakePath::new(directory.clone());
+        let data =
+            unsafe { &*(make_path.as_feature().data as *const lv2_sys::LV2_State_Make_Path) };
+        let make = |path: &[u8]| unsafe {
+            let ptr = (data.path.unwrap())(data.handle, path.as_ptr().cast());
+            let made = PathBuf::from(CStr::from_ptr(ptr).to_str().unwrap());
+            libc::free(ptr.cast());
+            made
+        };
+        let path = make(b"recordings/take1.wav\0");
+        assert_eq!(path, directory.join("recordings/take1.wav"));
+        assert!(directory.join("recordings").is_dir());
+        assert_eq!(make(b"/../escape.wav\0"), directory.join("escape.wav"));
+    }
+}
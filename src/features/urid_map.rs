@@ -104,6 +104,17 @@ impl UridMap {
         }
     }
 
+    /// A snapshot of all mapped uris and their urids, sorted by urid.
+    pub fn entries(&self) -> Vec<(lv2_raw::LV2Urid, String)> {
+        let map = self.map.lock().unwrap();
+        let mut entries: Vec<_> = map
+            .iter()
+            .map(|(uri, id)| (*id, uri.to_string_lossy().into_owned()))
+            .collect();
+        entries.sort_unstable_by_key(|(id, _)| *id);
+        entries
+    }
+
     pub fn as_urid_map_feature(&self) -> &LV2Feature {
         &self.urid_map_feature
     }
@@ -1,72 +1,222 @@
 use core::ffi::c_void;
 use std::mem::size_of;
 use std::slice;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
 
 pub(crate) type WorkerMessageSender = ringbuf::HeapProducer<u8>;
 pub(crate) type WorkerMessageReceiver = ringbuf::HeapConsumer<u8>;
 
-const MAX_MESSAGE_SIZE: usize = 8192;
-const N_MESSAGES: usize = 4;
+/// The default size of the largest message that can be sent to or from a
+/// worker. See `FeaturesBuilder::worker_max_message_size`.
+pub(crate) const DEFAULT_MAX_MESSAGE_SIZE: usize = 8192;
+/// The default number of messages of the largest size that fit into a worker
+/// queue. See `FeaturesBuilder::worker_queue_messages`.
+pub(crate) const DEFAULT_QUEUE_MESSAGES: usize = 4;
 
-type MessageBody = [u8; MAX_MESSAGE_SIZE];
+/// The longest time that `WorkerManager::run_workers_blocking` waits before
+/// checking for work again. This bounds the delay of a wake up that is missed
+/// because it happened right before the thread started waiting.
+const MAX_WAIT: Duration = Duration::from_millis(100);
 
-#[derive(Debug)]
-struct WorkerMessage {
-    size: usize,
-    body: MessageBody,
+/// Create a queue that holds up to `n_messages` messages of
+/// `max_message_size` bytes. More messages fit if they are smaller.
+pub(crate) fn instantiate_queue(
+    max_message_size: usize,
+    n_messages: usize,
+) -> (WorkerMessageSender, WorkerMessageReceiver) {
+    let capacity = (size_of::<usize>() + max_message_size) * n_messages.max(1);
+    let (sender, receiver) = ringbuf::HeapRb::new(capacity).split();
+    (sender, receiver)
 }
 
-impl WorkerMessage {
-    fn data(&mut self) -> *mut c_void {
-        &mut self.body as *mut MessageBody as *mut c_void
-    }
+/// Create a buffer that can hold any message of up to `max_message_size`
+/// bytes.
+pub(crate) fn message_buffer(max_message_size: usize) -> Vec<u8> {
+    vec![0; max_message_size]
 }
 
-pub(crate) fn instantiate_queue() -> (WorkerMessageSender, WorkerMessageReceiver) {
-    let (sender, receiver) = ringbuf::HeapRb::new(MAX_MESSAGE_SIZE * N_MESSAGES).split();
-    (sender, receiver)
+/// The sending end of a queue along with the size of the largest message that
+/// may be sent. The queue has a single producer, so it is behind a mutex.
+pub(crate) struct MessageSender {
+    sender: Mutex<WorkerMessageSender>,
+    max_message_size: usize,
 }
 
-fn publish_message(
-    sender: &mut WorkerMessageSender,
+impl MessageSender {
+    pub(crate) fn new(sender: WorkerMessageSender, max_message_size: usize) -> MessageSender {
+        MessageSender {
+            sender: Mutex::new(sender),
+            max_message_size,
+        }
+    }
+}
+
+/// Push a message onto the queue behind `sender`. The queue has a single
+/// producer, so concurrent callers are serialized with `try_lock`. A caller that
+/// finds the queue in use gets an error instead of blocking, which keeps this
+/// realtime safe.
+pub(crate) fn publish_message(
+    sender: &MessageSender,
     size: usize,
-    body: *mut u8,
+    body: *const u8,
 ) -> lv2_sys::LV2_Worker_Status {
-    if size > MAX_MESSAGE_SIZE {
+    if body.is_null() && size > 0 {
+        return lv2_sys::LV2_Worker_Status_LV2_WORKER_ERR_UNKNOWN;
+    }
+    if size > sender.max_message_size {
         return lv2_sys::LV2_Worker_Status_LV2_WORKER_ERR_NO_SPACE;
     }
-    let mut body = unsafe { slice::from_raw_parts(body, size) };
+    let mut sender = match sender.sender.try_lock() {
+        Ok(s) => s,
+        Err(_) => return lv2_sys::LV2_Worker_Status_LV2_WORKER_ERR_UNKNOWN,
+    };
+    let body = if size == 0 {
+        &[]
+    } else {
+        unsafe { slice::from_raw_parts(body, size) }
+    };
     let total_size = size_of::<usize>() + size;
     if sender.free_len() < total_size {
         return lv2_sys::LV2_Worker_Status_LV2_WORKER_ERR_NO_SPACE;
     }
-    let size_as_bytes = size.to_be_bytes();
-    sender.push_slice(&size_as_bytes);
-    let result = sender.read_from(&mut body, Some(size));
-    match result {
-        Ok(_) => lv2_sys::LV2_Worker_Status_LV2_WORKER_SUCCESS,
-        Err(_) => lv2_sys::LV2_Worker_Status_LV2_WORKER_ERR_UNKNOWN,
-    }
+    // There is enough space for the whole message so it is never partially
+    // written.
+    sender.push_slice(&size.to_be_bytes());
+    sender.push_slice(body);
+    lv2_sys::LV2_Worker_Status_LV2_WORKER_SUCCESS
 }
 
-fn pop_message(receiver: &mut WorkerMessageReceiver) -> WorkerMessage {
+/// Pop the next message from `receiver` into `buffer` and return its size.
+/// `buffer` should be created with `message_buffer`. Messages that do not fit
+/// into `buffer` are skipped and `None` is returned.
+pub(crate) fn pop_message(
+    receiver: &mut WorkerMessageReceiver,
+    buffer: &mut [u8],
+) -> Option<usize> {
     let mut size_as_bytes = [0; size_of::<usize>()];
     receiver.pop_slice(&mut size_as_bytes);
     let size = usize::from_be_bytes(size_as_bytes);
-    let mut body: MessageBody = [0; MAX_MESSAGE_SIZE];
-    let mut slice = &mut body[..];
-    receiver.write_into(&mut slice, Some(size)).unwrap();
-    WorkerMessage { size, body }
+    match buffer.get_mut(..size) {
+        Some(body) => {
+            receiver.pop_slice(body);
+            Some(size)
+        }
+        None => {
+            receiver.skip(size);
+            None
+        }
+    }
+}
+
+/// Signals threads in `WorkerManager::run_workers_blocking` that work has been
+/// scheduled.
+#[derive(Debug)]
+pub(crate) struct WorkSignal {
+    // The number of wake ups that have not been consumed by `wait`. It is
+    // capped at `max_pending` so that at most that many threads are woken up
+    // for work that was scheduled while they were busy.
+    pending: AtomicUsize,
+    max_pending: usize,
+    lock: Mutex<()>,
+    condvar: Condvar,
+}
+
+impl Default for WorkSignal {
+    fn default() -> WorkSignal {
+        WorkSignal::new(1)
+    }
+}
+
+impl WorkSignal {
+    fn new(max_pending: usize) -> WorkSignal {
+        WorkSignal {
+            pending: AtomicUsize::new(0),
+            max_pending: max_pending.max(1),
+            lock: Mutex::new(()),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// Wake up the waiting threads. This does not block or allocate.
+    pub(crate) fn notify(&self) {
+        let max_pending = self.max_pending;
+        let _ = self
+            .pending
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                Some((n + 1).min(max_pending))
+            });
+        self.condvar.notify_all();
+    }
+
+    /// Wake up all waiting threads, for example to let them exit.
+    fn notify_all(&self) {
+        self.pending.store(self.max_pending, Ordering::SeqCst);
+        self.condvar.notify_all();
+    }
+
+    /// Block until `notify` is called. Returns immediately if `notify` was
+    /// called since the last wait.
+    fn wait(&self) {
+        let mut guard = self.lock.lock().unwrap();
+        while self
+            .pending
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_err()
+        {
+            guard = self.condvar.wait_timeout(guard, MAX_WAIT).unwrap().0;
+        }
+    }
+}
+
+/// The data behind the handle of the `worker:schedule` feature.
+pub(crate) struct WorkScheduler {
+    sender: MessageSender,
+    signal: Arc<WorkSignal>,
+    // The number of messages that could not be scheduled. This is shared with
+    // the `Worker` which logs them outside of the realtime thread.
+    dropped_messages: Arc<AtomicUsize>,
+}
+
+impl WorkScheduler {
+    pub(crate) fn new(sender: MessageSender, signal: Arc<WorkSignal>) -> WorkScheduler {
+        WorkScheduler {
+            sender,
+            signal,
+            dropped_messages: Arc::default(),
+        }
+    }
+
+    /// The counter of messages that could not be scheduled.
+    pub(crate) fn dropped_messages(&self) -> &Arc<AtomicUsize> {
+        &self.dropped_messages
+    }
 }
 
+/// The `schedule_work` function of the `worker:schedule` feature. `handle` must
+/// point to a `WorkScheduler`.
+///
+/// livi expects plugins to call this from `run`, which is how the worker
+/// extension is meant to be used. Calling it from any other thread is still
+/// safe: concurrent calls do not block and fail with `LV2_WORKER_ERR_UNKNOWN`
+/// instead of corrupting the queue.
 pub extern "C" fn schedule_work(
     handle: lv2_sys::LV2_Worker_Schedule_Handle,
     size: u32,
     body: *const c_void,
 ) -> lv2_sys::LV2_Worker_Status {
-    let sender = unsafe { &mut *(handle as *mut WorkerMessageSender) };
-    publish_message(sender, size as usize, body as *mut u8)
+    let scheduler = match unsafe { (handle as *const WorkScheduler).as_ref() } {
+        Some(s) => s,
+        None => return lv2_sys::LV2_Worker_Status_LV2_WORKER_ERR_UNKNOWN,
+    };
+    let status = publish_message(&scheduler.sender, size as usize, body.cast());
+    if status == lv2_sys::LV2_Worker_Status_LV2_WORKER_SUCCESS {
+        scheduler.signal.notify();
+    } else {
+        scheduler.dropped_messages.fetch_add(1, Ordering::Relaxed);
+    }
+    status
 }
 
 extern "C" fn worker_respond(
@@ -74,8 +224,11 @@ extern "C" fn worker_respond(
     size: u32,
     body: *const c_void,
 ) -> lv2_sys::LV2_Worker_Status {
-    let sender = unsafe { &mut *(handle as *mut WorkerMessageSender) };
-    publish_message(sender, size as usize, body as *mut u8)
+    let sender = match unsafe { (handle as *const MessageSender).as_ref() } {
+        Some(s) => s,
+        None => return lv2_sys::LV2_Worker_Status_LV2_WORKER_ERR_UNKNOWN,
+    };
+    publish_message(sender, size as usize, body.cast())
 }
 
 /// A plugin instance delegates non-realtime-safe
@@ -85,15 +238,17 @@ extern "C" fn worker_respond(
 ///
 /// The worker itself is easy to use. Once you obtain
 /// a worker from the plugin, just call worker.do_work()
-/// periodically and that's it. Currently there's no method
-/// to "wait" on work and only perform work when messages arrive,
-/// you have to keep calling do_work while the plugin is alive.
+/// periodically and that's it. To only do work when messages
+/// arrive, use `WorkerManager::run_workers_blocking`.
 pub struct Worker {
     plugin_is_alive: Arc<Mutex<bool>>,
     interface: lv2_sys::LV2_Worker_Interface,
     instance_handle: lv2_sys::LV2_Handle,
     receiver: WorkerMessageReceiver, // Where we find work to do
-    sender: WorkerMessageSender,     // Where we send the results of our work
+    sender: MessageSender,           // Where we send the results of our work
+    message_buffer: Vec<u8>,
+    dropped_messages: Arc<AtomicUsize>,
+    logged_dropped_messages: usize,
 }
 
 unsafe impl Send for Worker {}
@@ -105,14 +260,19 @@ impl Worker {
         interface: lv2_sys::LV2_Worker_Interface,
         instance_handle: lv2_sys::LV2_Handle,
         receiver: WorkerMessageReceiver,
-        sender: WorkerMessageSender,
+        max_message_size: usize,
+        sender: MessageSender,
+        dropped_messages: Arc<AtomicUsize>,
     ) -> Self {
         Worker {
             plugin_is_alive,
             interface,
             instance_handle,
+            message_buffer: message_buffer(max_message_size),
             receiver,
             sender,
+            dropped_messages,
+            logged_dropped_messages: 0,
         }
     }
 
@@ -120,24 +280,50 @@ impl Worker {
     /// to do non-realtime work and send
     /// the results back to the realtime thread.
     pub fn do_work(&mut self) {
-        let plugin_is_alive = self.plugin_is_alive.lock().unwrap();
-        while *plugin_is_alive && self.receiver.len() > size_of::<usize>() {
-            let mut message = pop_message(&mut self.receiver);
+        // Dropped messages are logged here since logging is not realtime safe.
+        let dropped_messages = self.dropped_messages.load(Ordering::Relaxed);
+        if dropped_messages > self.logged_dropped_messages {
+            log::warn!(
+                "{} worker messages were dropped because the worker queue was full.",
+                dropped_messages - self.logged_dropped_messages
+            );
+            self.logged_dropped_messages = dropped_messages;
+        }
+        while self.receiver.len() > size_of::<usize>() {
+            // The lock is held for each message to prevent the instance from
+            // being dropped while it is doing work. It is released between
+            // messages so that dropping the instance does not have to wait for
+            // the whole queue to be processed.
+            let plugin_is_alive = self.plugin_is_alive.lock().unwrap();
+            if !*plugin_is_alive {
+                // Pending work can not be handled by a dropped instance.
+                self.receiver.clear();
+                return;
+            }
+            let size = match pop_message(&mut self.receiver, &mut self.message_buffer) {
+                Some(size) => size,
+                None => continue,
+            };
             if let Some(work_function) = self.interface.work {
-                let sender = &mut self.sender as *mut WorkerMessageSender as *mut c_void;
+                let sender = &self.sender as *const MessageSender as *mut c_void;
                 unsafe {
                     work_function(
                         self.instance_handle,
                         Some(worker_respond),
                         sender,
-                        message.size as u32,
-                        message.data(),
+                        size as u32,
+                        self.message_buffer.as_ptr().cast(),
                     )
                 };
             }
         }
     }
 
+    /// Returns `true` if the plugin scheduled work that has not been done yet.
+    pub(crate) fn has_pending_work(&self) -> bool {
+        self.receiver.len() > size_of::<usize>()
+    }
+
     /// Keep the worker working as long as this
     /// remains true. Once this returns false,
     /// you can drop the worker.
@@ -154,6 +340,7 @@ impl std::fmt::Debug for Worker {
             .field("instance_handle", &self.instance_handle)
             .field("receiver", &"__internal__")
             .field("sender", &"__internal__")
+            .field("dropped_messages", &self.dropped_messages)
             .finish()
     }
 }
@@ -167,13 +354,9 @@ pub(crate) unsafe fn maybe_get_worker_interface(
     if !plugin.has_feature(&common_uris.worker_schedule_feature_uri) {
         return None;
     }
-    // TODO: Remove below after
-    // https://github.com/poidl/lv2_raw/issues/4 is fixed.
-    let descriptor = instance.instance().descriptor().unwrap();
-    type ExtDataFn = extern "C" fn(uri: *const u8) -> *const c_void;
-    let extension_data: Option<ExtDataFn> = std::mem::transmute(descriptor.extension_data);
-    extension_data?;
-    // Delete up to here.
+    if !crate::plugin::has_extension_data_fn(instance.instance()) {
+        return None;
+    }
     Some(
         *instance
             .instance()
@@ -186,15 +369,20 @@ pub(crate) unsafe fn maybe_get_worker_interface(
 
 // Run this in the real-time thread
 // to process responses from the async worker.
+// `buffer` must be created with `message_buffer`.
 pub(crate) fn handle_work_responses(
     worker_interface: &mut lv2_sys::LV2_Worker_Interface,
     receiver: &mut WorkerMessageReceiver,
+    buffer: &mut [u8],
     handle: lv2_sys::LV2_Handle,
 ) {
     while receiver.len() > size_of::<usize>() {
-        let mut message = pop_message(receiver);
+        let size = match pop_message(receiver, buffer) {
+            Some(size) => size,
+            None => continue,
+        };
         if let Some(work_response_function) = worker_interface.work_response {
-            unsafe { work_response_function(handle, message.size as u32, message.data()) };
+            unsafe { work_response_function(handle, size as u32, buffer.as_ptr().cast()) };
         }
     }
 }
@@ -229,6 +417,7 @@ pub(crate) fn end_run(
 /// let features = world.build_features(livi::FeaturesBuilder{
 ///     min_block_length: MIN_BLOCK_SIZE,
 ///     max_block_length: MAX_BLOCK_SIZE,
+///     ..livi::FeaturesBuilder::default()
 /// });
 /// let mut instance = unsafe {
 ///     plugin
@@ -238,22 +427,118 @@ pub(crate) fn end_run(
 /// ```
 #[derive(Default, Debug)]
 pub struct WorkerManager {
-    new_workers: Mutex<Vec<Worker>>,
+    new_workers: Mutex<Vec<Arc<Mutex<Worker>>>>,
     // Workers that may be in the process of running are kept in a different
-    // variable to prevent blocking when adding new workers.
-    running_workers: Mutex<Vec<Worker>>,
+    // variable to prevent blocking when adding new workers. Each worker has its
+    // own lock so that different workers can run on different threads.
+    running_workers: Mutex<Vec<Arc<Mutex<Worker>>>>,
+    signal: Arc<WorkSignal>,
 }
 
 impl WorkerManager {
+    /// Create a worker manager that is meant to be run by `thread_count`
+    /// threads calling `run_workers_blocking`.
+    pub(crate) fn with_thread_count(thread_count: usize) -> WorkerManager {
+        WorkerManager {
+            new_workers: Mutex::default(),
+            running_workers: Mutex::default(),
+            signal: Arc::new(WorkSignal::new(thread_count)),
+        }
+    }
+
     /// Run all the workers that have been added and are alive. This function
-    /// should not be run in the Realtime thread. Additionally, there is no
-    /// benefit to running it in parallel as concurrency is limited to 1 worker
-    /// at a time.
+    /// should not be run in the Realtime thread.
+    ///
+    /// It may be called from several threads at once. Each worker is only run
+    /// by one thread at a time, and workers that are being run by another
+    /// thread are skipped. Different workers run in parallel.
     pub fn run_workers(&self) {
+        for worker in self.alive_workers().iter() {
+            if let Ok(mut worker) = worker.try_lock() {
+                worker.do_work();
+            }
+        }
+    }
+
+    /// Run all the workers like `run_workers`, but distribute them across a
+    /// pool of `pool_size` scoped threads. Each worker still runs on a single
+    /// thread at a time, but workers of different plugins run concurrently.
+    /// Returns once all the workers have been run.
+    ///
+    /// This spawns threads on every call so it is best suited for occasional
+    /// bursts of heavy work, like loading several plugin states at once.
+    pub fn run_workers_parallel(&self, pool_size: usize) {
+        let workers = self.alive_workers();
+        let pool_size = pool_size.max(1).min(workers.len());
+        if pool_size <= 1 {
+            self.run_workers();
+            return;
+        }
+        let next_worker = AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            for _ in 0..pool_size {
+                s.spawn(|| {
+                    while let Some(worker) =
+                        workers.get(next_worker.fetch_add(1, Ordering::Relaxed))
+                    {
+                        if let Ok(mut worker) = worker.try_lock() {
+                            worker.do_work();
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    /// Move the new workers into the running workers, drop the workers whose
+    /// plugin is no longer alive and return the remaining workers.
+    fn alive_workers(&self) -> Vec<Arc<Mutex<Worker>>> {
         let mut workers = self.running_workers.lock().unwrap();
         workers.extend(self.new_workers.lock().unwrap().drain(..));
-        workers.iter_mut().for_each(|worker| worker.do_work());
-        workers.retain(|worker| worker.should_keep_working());
+        // Workers that are running are still alive since their instance
+        // can not finish dropping while they do work.
+        workers.retain(|w| match w.try_lock() {
+            Ok(w) => w.should_keep_working(),
+            Err(_) => true,
+        });
+        workers.clone()
+    }
+
+    /// Block until a plugin schedules work and then run all the workers like
+    /// `run_workers`. The thread sleeps while there is no work. This is meant
+    /// to be called in a loop on a dedicated non-realtime thread.
+    pub fn run_workers_blocking(&self) {
+        self.signal.wait();
+        self.run_workers();
+    }
+
+    /// Wake up all threads that are blocked in `run_workers_blocking`.
+    pub(crate) fn wake(&self) {
+        self.signal.notify_all();
+    }
+
+    /// The signal that is notified when work is scheduled.
+    pub(crate) fn signal(&self) -> &Arc<WorkSignal> {
+        &self.signal
+    }
+
+    /// Returns `true` if any worker has work that its plugin scheduled but
+    /// that has not been done yet. Workers that are being run by another
+    /// thread are counted as having work.
+    pub fn has_pending_work(&self) -> bool {
+        let has_pending_work =
+            |w: &Arc<Mutex<Worker>>| w.try_lock().map_or(true, |w| w.has_pending_work());
+        self.new_workers
+            .lock()
+            .unwrap()
+            .iter()
+            .any(has_pending_work)
+            || self
+                .running_workers
+                .lock()
+                .unwrap()
+                .iter()
+                .any(has_pending_work)
     }
 
     /// The number of workers that are currently alive.
@@ -261,8 +546,12 @@ impl WorkerManager {
         self.running_workers.lock().unwrap().len() + self.new_workers.lock().unwrap().len()
     }
 
-    pub(crate) fn add_worker(&self, worker: Worker) {
-        self.new_workers.lock().unwrap().push(worker);
+    /// Add `worker` to the workers that are run. The returned worker can be
+    /// used to run it on its own.
+    pub(crate) fn add_worker(&self, worker: Worker) -> Arc<Mutex<Worker>> {
+        let worker = Arc::new(Mutex::new(worker));
+        self.new_workers.lock().unwrap().push(worker.clone());
+        worker
     }
 }
 
@@ -273,13 +562,229 @@ mod tests {
 
     #[test]
     fn test_send() {
-        let (mut sender, mut receiver) = instantiate_queue();
+        let (sender, mut receiver) =
+            instantiate_queue(DEFAULT_MAX_MESSAGE_SIZE, DEFAULT_QUEUE_MESSAGES);
+        let sender = MessageSender::new(sender, DEFAULT_MAX_MESSAGE_SIZE);
         let sentence_to_transfer = String::from("This is a message for you");
-        let mut data = sentence_to_transfer.clone().into_bytes();
-        publish_message(&mut sender, data.len(), data.as_mut_ptr());
-        let message = pop_message(&mut receiver);
-        let body = &message.body[..message.size];
-        let message_body = str::from_utf8(body).unwrap();
+        let data = sentence_to_transfer.clone().into_bytes();
+        publish_message(&sender, data.len(), data.as_ptr());
+        let mut buffer = message_buffer(DEFAULT_MAX_MESSAGE_SIZE);
+        let size = pop_message(&mut receiver, &mut buffer).unwrap();
+        let message_body = str::from_utf8(&buffer[..size]).unwrap();
         assert_eq!(sentence_to_transfer, message_body);
     }
+
+    #[test]
+    fn test_send_large_message() {
+        let max_message_size = 64 * 1024;
+        let (sender, mut receiver) = instantiate_queue(max_message_size, DEFAULT_QUEUE_MESSAGES);
+        let sender = MessageSender::new(sender, max_message_size);
+        let data: Vec<u8> = (0..max_message_size).map(|i| i as u8).collect();
+        assert_eq!(
+            publish_message(&sender, data.len(), data.as_ptr()),
+            lv2_sys::LV2_Worker_Status_LV2_WORKER_SUCCESS
+        );
+        let too_large = vec![0u8; max_message_size + 1];
+        assert_eq!(
+            publish_message(&sender, too_large.len(), too_large.as_ptr()),
+            lv2_sys::LV2_Worker_Status_LV2_WORKER_ERR_NO_SPACE
+        );
+        let mut buffer = message_buffer(max_message_size);
+        assert_eq!(buffer.len(), max_message_size);
+        let size = pop_message(&mut receiver, &mut buffer).unwrap();
+        assert_eq!(&buffer[..size], data.as_slice());
+        assert!(receiver.is_empty());
+    }
+
+    #[test]
+    fn test_queue_holds_n_messages() {
+        let max_message_size = 8;
+        let (sender, mut receiver) = instantiate_queue(max_message_size, 3);
+        let sender = MessageSender::new(sender, max_message_size);
+        let data = [7u8; 8];
+        for _ in 0..3 {
+            assert_eq!(
+                publish_message(&sender, data.len(), data.as_ptr()),
+                lv2_sys::LV2_Worker_Status_LV2_WORKER_SUCCESS
+            );
+        }
+        assert_eq!(
+            publish_message(&sender, data.len(), data.as_ptr()),
+            lv2_sys::LV2_Worker_Status_LV2_WORKER_ERR_NO_SPACE
+        );
+        let mut buffer = message_buffer(max_message_size);
+        for _ in 0..3 {
+            let size = pop_message(&mut receiver, &mut buffer).unwrap();
+            assert_eq!(&buffer[..size], &data);
+        }
+        assert!(receiver.is_empty());
+    }
+
+    #[test]
+    fn test_publish_message_does_not_block_concurrent_callers() {
+        let (sender, mut receiver) =
+            instantiate_queue(DEFAULT_MAX_MESSAGE_SIZE, DEFAULT_QUEUE_MESSAGES);
+        let sender = MessageSender::new(sender, DEFAULT_MAX_MESSAGE_SIZE);
+        let data = [1u8, 2, 3];
+        {
+            let _in_use = sender.sender.lock().unwrap();
+            assert_eq!(
+                publish_message(&sender, data.len(), data.as_ptr()),
+                lv2_sys::LV2_Worker_Status_LV2_WORKER_ERR_UNKNOWN
+            );
+        }
+        assert_eq!(
+            publish_message(&sender, data.len(), data.as_ptr()),
+            lv2_sys::LV2_Worker_Status_LV2_WORKER_SUCCESS
+        );
+        assert_eq!(
+            publish_message(&sender, 1, std::ptr::null()),
+            lv2_sys::LV2_Worker_Status_LV2_WORKER_ERR_UNKNOWN
+        );
+        let mut buffer = message_buffer(DEFAULT_MAX_MESSAGE_SIZE);
+        let size = pop_message(&mut receiver, &mut buffer).unwrap();
+        assert_eq!(&buffer[..size], &data);
+        assert!(receiver.is_empty());
+    }
+
+    #[test]
+    fn test_run_workers_blocking_wakes_on_schedule_work() {
+        let manager = Arc::new(WorkerManager::default());
+        let (sender, mut receiver) =
+            instantiate_queue(DEFAULT_MAX_MESSAGE_SIZE, DEFAULT_QUEUE_MESSAGES);
+        let scheduler = WorkScheduler::new(
+            MessageSender::new(sender, DEFAULT_MAX_MESSAGE_SIZE),
+            manager.signal().clone(),
+        );
+        let waiting = {
+            let manager = manager.clone();
+            std::thread::spawn(move || manager.run_workers_blocking())
+        };
+        let data = [1u8, 2, 3];
+        let handle = &scheduler as *const WorkScheduler as *mut c_void;
+        assert_eq!(
+            schedule_work(handle, 3, data.as_ptr().cast()),
+            lv2_sys::LV2_Worker_Status_LV2_WORKER_SUCCESS
+        );
+        waiting.join().unwrap();
+        let mut buffer = message_buffer(DEFAULT_MAX_MESSAGE_SIZE);
+        let size = pop_message(&mut receiver, &mut buffer).unwrap();
+        assert_eq!(&buffer[..size], &data);
+    }
+
+    struct ParallelWork {
+        barrier: std::sync::Barrier,
+        done: AtomicUsize,
+    }
+
+    unsafe extern "C" fn wait_for_other_worker(
+        instance: lv2_sys::LV2_Handle,
+        _respond: lv2_sys::LV2_Worker_Respond_Function,
+        _handle: lv2_sys::LV2_Worker_Respond_Handle,
+        _size: u32,
+        _data: *const c_void,
+    ) -> lv2_sys::LV2_Worker_Status {
+        let work = &*(instance as *const ParallelWork);
+        work.barrier.wait();
+        work.done.fetch_add(1, Ordering::SeqCst);
+        lv2_sys::LV2_Worker_Status_LV2_WORKER_SUCCESS
+    }
+
+    // Add `n` workers to `manager` that each have one message of pending work.
+    fn add_parallel_workers(manager: &WorkerManager, work: &ParallelWork, n: usize) {
+        let interface = lv2_sys::LV2_Worker_Interface {
+            work: Some(wait_for_other_worker),
+            work_response: None,
+            end_run: None,
+        };
+        let data = [0u8; 4];
+        for _ in 0..n {
+            let (instance_to_worker_sender, instance_to_worker_receiver) =
+                instantiate_queue(DEFAULT_MAX_MESSAGE_SIZE, DEFAULT_QUEUE_MESSAGES);
+            let (worker_to_instance_sender, _) =
+                instantiate_queue(DEFAULT_MAX_MESSAGE_SIZE, DEFAULT_QUEUE_MESSAGES);
+            manager.add_worker(Worker::new(
+                Arc::new(Mutex::new(true)),
+                interface,
+                work as *const ParallelWork as lv2_sys::LV2_Handle,
+                instance_to_worker_receiver,
+                DEFAULT_MAX_MESSAGE_SIZE,
+                MessageSender::new(worker_to_instance_sender, DEFAULT_MAX_MESSAGE_SIZE),
+                Arc::default(),
+            ));
+            let scheduler = WorkScheduler::new(
+                MessageSender::new(instance_to_worker_sender, DEFAULT_MAX_MESSAGE_SIZE),
+                manager.signal().clone(),
+            );
+            let handle = &scheduler as *const WorkScheduler as *mut c_void;
+            assert_eq!(
+                schedule_work(handle, 4, data.as_ptr().cast()),
+                lv2_sys::LV2_Worker_Status_LV2_WORKER_SUCCESS
+            );
+        }
+    }
+
+    #[test]
+    fn test_run_workers_in_parallel() {
+        // Each worker blocks until the other one is running, so the workers
+        // only finish if they run on different threads.
+        let work = ParallelWork {
+            barrier: std::sync::Barrier::new(2),
+            done: AtomicUsize::new(0),
+        };
+        let manager = WorkerManager::with_thread_count(2);
+        add_parallel_workers(&manager, &work, 2);
+        std::thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    while work.done.load(Ordering::SeqCst) < 2 {
+                        manager.run_workers();
+                    }
+                });
+            }
+        });
+        assert_eq!(manager.workers_count(), 2);
+    }
+
+    #[test]
+    fn test_run_workers_parallel() {
+        let work = ParallelWork {
+            barrier: std::sync::Barrier::new(3),
+            done: AtomicUsize::new(0),
+        };
+        let manager = WorkerManager::default();
+        add_parallel_workers(&manager, &work, 3);
+        manager.run_workers_parallel(3);
+        assert_eq!(work.done.load(Ordering::SeqCst), 3);
+        assert_eq!(manager.workers_count(), 3);
+    }
+
+    #[test]
+    fn test_schedule_work_counts_dropped_messages() {
+        let (sender, _receiver) =
+            instantiate_queue(DEFAULT_MAX_MESSAGE_SIZE, DEFAULT_QUEUE_MESSAGES);
+        let scheduler = WorkScheduler::new(
+            MessageSender::new(sender, DEFAULT_MAX_MESSAGE_SIZE),
+            Arc::default(),
+        );
+        let handle = &scheduler as *const WorkScheduler as *mut c_void;
+        let data = [0u8; DEFAULT_MAX_MESSAGE_SIZE];
+        let mut status = lv2_sys::LV2_Worker_Status_LV2_WORKER_SUCCESS;
+        while status == lv2_sys::LV2_Worker_Status_LV2_WORKER_SUCCESS {
+            status = schedule_work(handle, data.len() as u32, data.as_ptr().cast());
+        }
+        assert_eq!(status, lv2_sys::LV2_Worker_Status_LV2_WORKER_ERR_NO_SPACE);
+        assert_eq!(scheduler.dropped_messages().load(Ordering::Relaxed), 1);
+        schedule_work(handle, data.len() as u32, data.as_ptr().cast());
+        assert_eq!(scheduler.dropped_messages().load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_schedule_work_rejects_null_handle() {
+        let data = [0u8; 4];
+        assert_eq!(
+            schedule_work(std::ptr::null_mut(), 4, data.as_ptr().cast()),
+            lv2_sys::LV2_Worker_Status_LV2_WORKER_ERR_UNKNOWN
+        );
+    }
 }
@@ -37,29 +37,52 @@
 //! unsafe { instance.run(features.max_block_length(), ports).unwrap() };
 //! ```
 use log::{debug, error, info, warn};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use std::sync::Arc;
 
+pub use chain::PluginChain;
+pub use features::options::OptionValue;
+pub use features::state_path::PathMapping;
 pub use features::worker::{Worker, WorkerManager};
 pub use features::{Features, FeaturesBuilder};
-pub use plugin::{Instance, Plugin};
-pub use port::{EmptyPortConnections, Port, PortConnections, PortCounts, PortIndex, PortType};
+pub use plugin::{BlockLengthMode, Instance, Plugin, WorkerUsage};
+pub use port::{
+    ChannelLayout, EmptyPortConnections, IoLayout, Port, PortConnections, PortCounts, PortGroup,
+    PortGroupMember, PortIndex, PortProperties, PortType, ScalePoint,
+};
+pub use preset::Preset;
+pub use state::{PluginState, StateProperty};
+pub use transport::TransportState;
+pub use units::PortUnit;
+pub use urids::CommonUrids;
 
 /// The underlying `lilv` library.
 pub use lilv;
 
+mod chain;
 mod class_utils;
 /// Contains all the error types for the `livi` crate.
 pub mod error;
 /// Contains utility for dealing with `LV2` events.
 pub mod event;
 mod features;
+/// Contains utilities for scheduling MIDI events.
+pub mod midi;
 mod plugin;
 mod port;
+mod preset;
+mod state;
+mod transport;
+mod units;
+mod urids;
 
 /// Contains all plugins.
 pub struct World {
-    world: lilv::World,
+    world: Arc<lilv::World>,
     livi_plugins: Vec<Plugin>,
+    unsupported_plugins: Vec<(String, Vec<String>)>,
+    user_preset_dirs: preset::UserPresetDirs,
 }
 
 impl World {
@@ -76,24 +99,73 @@ impl World {
     /// bundle_uri must be a fully qualified URI to the bundle directory,
     /// with the trailing slash, eg file:///usr/lib/lv2/foo.lv2/.
     pub fn with_load_bundle(bundle_uri: &str) -> World {
-        let world = lilv::World::new();
-        let uri = world.new_uri(bundle_uri);
-        world.load_bundle(&uri);
+        World::with_load_bundles(std::iter::once(bundle_uri))
+    }
+
+    /// Creates a new world that includes the plugins of all the bundles in
+    /// `bundle_uris`. The bundles are loaded into a single lilv world, so
+    /// plugins can use data from other bundles in the list. Plugins that are
+    /// provided by several bundles are only included once.
+    ///
+    /// Like `with_load_bundle`, each URI must be a fully qualified URI to the
+    /// bundle directory with the trailing slash.
+    pub fn with_load_bundles<'a>(bundle_uris: impl IntoIterator<Item = &'a str>) -> World {
+        let world = Arc::new(lilv::World::new());
+        for bundle_uri in bundle_uris {
+            let uri = world.new_uri(bundle_uri);
+            world.load_bundle(&uri);
+        }
         let common_uris = Arc::new(CommonUris::new(&world));
+        let user_preset_dirs = preset::UserPresetDirs::default();
         let class_to_parent = class_utils::make_class_to_parent_map(&world);
+        let mut plugin_uris = HashSet::new();
         let plugins: Vec<Plugin> = world
             .plugins()
             .into_iter()
+            .filter(|p| plugin_uris.insert(p.uri().as_uri().unwrap_or("BAD_URI").to_string()))
             .map(|p| {
                 let classes = class_utils::class_with_parents(&p.class(), &class_to_parent);
-                Plugin::from_raw(p, common_uris.clone(), classes)
+                Plugin::from_raw(
+                    p,
+                    world.clone(),
+                    common_uris.clone(),
+                    user_preset_dirs.clone(),
+                    classes,
+                )
             })
             .collect();
 
         World {
             world,
             livi_plugins: plugins,
+            unsupported_plugins: Vec::new(),
+            user_preset_dirs,
+        }
+    }
+
+    /// Creates a new world that only loads the bundle that contains the plugin
+    /// with `plugin_uri`. Returns `None` if no installed bundle provides the
+    /// plugin.
+    ///
+    /// The bundle is found through lilv's index of installed bundles, which
+    /// only reads each bundle's manifest. The full plugin data is only loaded
+    /// for the single matching bundle.
+    #[must_use]
+    pub fn load_plugin_by_uri(plugin_uri: &str) -> Option<World> {
+        let index = lilv::World::with_load_all();
+        let bundle_uri = index
+            .plugins()
+            .plugin(&index.new_uri(plugin_uri))?
+            .bundle_uri();
+        let world = World::with_load_bundle(bundle_uri.as_uri()?);
+        if world.plugin_by_uri(plugin_uri).is_none() {
+            warn!(
+                "Plugin {} was not found in its bundle {:?}.",
+                plugin_uri, bundle_uri
+            );
+            return None;
         }
+        Some(world)
     }
 
     /// Get the underlying lilv world.
@@ -108,19 +180,57 @@ impl World {
     where
         P: Fn(&Plugin) -> bool,
     {
-        let world = lilv::World::with_load_all();
+        World::from_lilv_world(lilv::World::with_load_all(), predicate)
+    }
+
+    /// Creates a new world that includes all supported plugins that are found
+    /// in `paths`. The `LV2_PATH` environment variable and the default LV2
+    /// directories are not searched. Like `LV2_PATH`, each path is a directory
+    /// that contains bundles, for example `/usr/lib/lv2`.
+    #[must_use]
+    pub fn with_search_path(paths: &[&Path]) -> World {
+        let world = lilv::World::new();
+        let lv2_path = std::env::join_paths(paths)
+            .ok()
+            .and_then(|p| p.into_string().ok())
+            .unwrap_or_else(|| {
+                warn!("Search paths {:?} can not be used as an LV2 path.", paths);
+                String::new()
+            });
+        world.set_option(
+            "http://drobilla.net/ns/lilv#lv2-path",
+            &world.new_string(&lv2_path),
+        );
+        world.load_all();
+        World::from_lilv_world(world, |_| true)
+    }
+
+    /// Creates a new world from the plugins that have been loaded into `world`
+    /// that are supported and return `true` for `predicate`.
+    fn from_lilv_world<P>(world: lilv::World, predicate: P) -> World
+    where
+        P: Fn(&Plugin) -> bool,
+    {
+        let world = Arc::new(world);
         let common_uris = Arc::new(CommonUris::new(&world));
-        let supported_features = crate::Features::supported_features();
+        let user_preset_dirs = preset::UserPresetDirs::default();
+        let mut supported_features = crate::Features::supported_features();
+        supported_features.extend(crate::Features::configurable_features());
         info!(
             "Creating World with supported features {:?}",
             supported_features
         );
         let class_to_parent = class_utils::make_class_to_parent_map(&world);
+        let mut unsupported_plugins = Vec::new();
         let plugins: Vec<Plugin> = world
             .plugins()
             .into_iter()
             .filter(|p| {
-                let unsupported_features: Vec<_> = p.required_features().into_iter().filter(|f| !supported_features.contains(f.as_uri().unwrap_or(""))).collect();
+                let unsupported_features: Vec<_> = p
+                    .required_features()
+                    .into_iter()
+                    .filter(|f| !supported_features.contains(f.as_uri().unwrap_or("")))
+                    .collect();
                 let is_supported = unsupported_features.is_empty();
                 if !is_supported {
                     warn!(
@@ -128,6 +238,13 @@ impl World {
                         p.uri().as_uri().unwrap_or("BAD_URI"),
                         unsupported_features
                     );
+                    unsupported_plugins.push((
+                        p.uri().as_uri().unwrap_or("BAD_URI").to_string(),
+                        unsupported_features
+                            .iter()
+                            .map(|f| f.as_uri().unwrap_or("BAD_URI").to_string())
+                            .collect(),
+                    ));
                 }
                 is_supported
             })
@@ -152,6 +269,8 @@ impl World {
                             && class != common_uris.atom_port_uri
                             && class != common_uris.cv_port_uri
                             && class != common_uris.mod_cv_port_uri
+                            && class != common_uris.morph_port_uri
+                            && class != common_uris.auto_morph_port_uri
                         {
                             error!("Port class {:?} is not supported.", class);
                             return false;
@@ -167,18 +286,55 @@ impl World {
                         );
                         return false;
                     }
-                    if !port.is_a(&common_uris.audio_port_uri) && !port.is_a(&common_uris.control_port_uri) && !port.is_a(&common_uris.atom_port_uri) && !port.is_a(&common_uris.cv_port_uri) {
+                    if !port.is_a(&common_uris.audio_port_uri)
+                        && !port.is_a(&common_uris.control_port_uri)
+                        && !port.is_a(&common_uris.atom_port_uri)
+                        && !port.is_a(&common_uris.cv_port_uri)
+                    {
                         error!(
-                            "Port {:?}for plugin {} not a recognized data type. Supported types are Audio and Control", port, p.uri().as_str().unwrap_or("BAD_URI")
+                            "Port {:?}for plugin {} not a recognized data type. Supported types are Audio and Control",
+                            port,
+                            p.uri().as_str().unwrap_or("BAD_URI")
                         );
                         return false;
                     }
+                    // `morph:interface` is not provided so morph ports keep
+                    // their default type, which is the single concrete data
+                    // type that they declare. `morph:currentType` is only
+                    // known by a running instance.
+                    if port.is_a(&common_uris.morph_port_uri)
+                        || port.is_a(&common_uris.auto_morph_port_uri)
+                    {
+                        let data_types = [
+                            &common_uris.audio_port_uri,
+                            &common_uris.control_port_uri,
+                            &common_uris.atom_port_uri,
+                            &common_uris.cv_port_uri,
+                        ]
+                        .iter()
+                        .filter(|uri| port.is_a(uri))
+                        .count();
+                        if data_types != 1 {
+                            error!(
+                                "Morph port {:?} for plugin {} must declare exactly one data type.",
+                                port,
+                                p.uri().as_str().unwrap_or("BAD_URI")
+                            );
+                            return false;
+                        }
+                    }
                 }
                 true
             })
             .map(|p| {
                 let classes = class_utils::class_with_parents(&p.class(), &class_to_parent);
-                Plugin::from_raw(p, common_uris.clone(), classes)
+                Plugin::from_raw(
+                    p,
+                    world.clone(),
+                    common_uris.clone(),
+                    user_preset_dirs.clone(),
+                    classes,
+                )
     })
             .filter(|p| {
                 let keep = predicate(p);
@@ -192,6 +348,8 @@ impl World {
         World {
             world,
             livi_plugins: plugins,
+            unsupported_plugins,
+            user_preset_dirs,
         }
     }
 
@@ -200,10 +358,98 @@ impl World {
         self.livi_plugins.iter().cloned()
     }
 
+    /// Return the URI of each plugin that was not included because it requires
+    /// features that are not supported, together with the URIs of the
+    /// unsupported features. See `Features::supported_features`.
+    #[must_use]
+    pub fn unsupported_plugins(&self) -> Vec<(String, Vec<String>)> {
+        self.unsupported_plugins.clone()
+    }
+
+    /// Return the number of plugins in the world.
+    #[must_use]
+    pub fn plugin_count(&self) -> usize {
+        self.livi_plugins.len()
+    }
+
+    /// Returns true if the world has a plugin with the given URI. This is
+    /// cheaper than `plugin_by_uri` since no `Plugin` is cloned.
+    #[must_use]
+    pub fn contains_plugin(&self, uri: &str) -> bool {
+        self.livi_plugins.iter().any(|p| p.uri() == uri)
+    }
+
     /// Return the plugin given a URI or `None` if it does not exist.
     #[must_use]
     pub fn plugin_by_uri(&self, uri: &str) -> Option<Plugin> {
-        self.iter_plugins().find(|p| p.uri() == uri)
+        self.livi_plugins.iter().find(|p| p.uri() == uri).cloned()
+    }
+
+    /// Return all plugins whose class is `class_uri` or a subclass of it. For
+    /// example, "http://lv2plug.in/ns/lv2core#DynamicsPlugin" also matches
+    /// compressors and amplifiers.
+    #[must_use]
+    pub fn plugins_by_class(&self, class_uri: &str) -> Vec<Plugin> {
+        self.iter_plugins()
+            .filter(|p| p.has_class(class_uri))
+            .collect()
+    }
+
+    /// Return all plugins grouped by the URI of the bundle that contains them.
+    #[must_use]
+    pub fn plugins_by_bundle(&self) -> HashMap<String, Vec<Plugin>> {
+        let mut plugins: HashMap<String, Vec<Plugin>> = HashMap::new();
+        for plugin in self.iter_plugins() {
+            plugins.entry(plugin.bundle_uri()).or_default().push(plugin);
+        }
+        plugins
+    }
+
+    /// Load the user presets in `dir`. Each subdirectory of `dir` is loaded as
+    /// a preset bundle, like the bundles that are saved by hosts into
+    /// `~/.lv2`. The loaded presets are returned by `Plugin::user_presets`.
+    /// Presets for plugins that are not in the world are skipped.
+    ///
+    /// Returns the number of presets in `dir` that were loaded for plugins in
+    /// the world.
+    ///
+    /// This changes the underlying `lilv::World`, which is not thread safe.
+    /// `Plugin` and `Preset` values that were taken from the world before
+    /// share it and are invalidated; they must not be used while the presets
+    /// are loaded. Get them from the world again afterwards.
+    ///
+    /// # Errors
+    /// Returns an error if `dir` could not be read.
+    pub fn load_user_presets<P: AsRef<std::path::Path>>(
+        &mut self,
+        dir: P,
+    ) -> std::io::Result<usize> {
+        let dir = dir.as_ref().canonicalize()?;
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if !path.is_dir() {
+                continue;
+            }
+            let bundle_uri = preset::dir_uri(&self.world, &path);
+            self.world.load_bundle(&self.world.new_uri(&bundle_uri));
+        }
+        let dir_uri = preset::dir_uri(&self.world, &dir);
+        let mut user_preset_dirs = self.user_preset_dirs.lock().unwrap();
+        if !user_preset_dirs.contains(&dir_uri) {
+            user_preset_dirs.push(dir_uri.clone());
+        }
+        drop(user_preset_dirs);
+        let dirs = [dir_uri];
+        Ok(self
+            .livi_plugins
+            .iter()
+            .map(|p| {
+                p.all_presets()
+                    .iter()
+                    .filter(|preset| preset.is_in_dirs(&dirs))
+                    .count()
+            })
+            .sum())
     }
 
     /// Initialize the block length. This is the minimum and maximum number of
@@ -227,6 +473,7 @@ impl std::fmt::Debug for World {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("World")
             .field("livi_plugins", &self.livi_plugins)
+            .field("unsupported_plugins", &self.unsupported_plugins)
             .finish()
     }
 }
@@ -240,7 +487,41 @@ struct CommonUris {
     atom_port_uri: lilv::node::Node,
     cv_port_uri: lilv::node::Node,
     mod_cv_port_uri: lilv::node::Node,
+    morph_port_uri: lilv::node::Node,
+    auto_morph_port_uri: lilv::node::Node,
     worker_schedule_feature_uri: lilv::node::Node,
+    worker_interface_uri: lilv::node::Node,
+    trigger_port_property_uri: lilv::node::Node,
+    sample_rate_port_property_uri: lilv::node::Node,
+    toggled_port_property_uri: lilv::node::Node,
+    integer_port_property_uri: lilv::node::Node,
+    logarithmic_port_property_uri: lilv::node::Node,
+    enumeration_port_property_uri: lilv::node::Node,
+    expensive_port_property_uri: lilv::node::Node,
+    not_on_gui_port_property_uri: lilv::node::Node,
+    atom_supports_uri: lilv::node::Node,
+    units_unit_uri: lilv::node::Node,
+    units_render_uri: lilv::node::Node,
+    units_symbol_uri: lilv::node::Node,
+    bounded_block_length_feature_uri: lilv::node::Node,
+    fixed_block_length_feature_uri: lilv::node::Node,
+    coarse_block_length_feature_uri: lilv::node::Node,
+    preset_uri: lilv::node::Node,
+    pset_value_uri: lilv::node::Node,
+    rdfs_label_uri: lilv::node::Node,
+    rdfs_comment_uri: lilv::node::Node,
+    rdfs_see_also_uri: lilv::node::Node,
+    lv2_port_uri: lilv::node::Node,
+    lv2_symbol_uri: lilv::node::Node,
+    lv2_designation_uri: lilv::node::Node,
+    lv2_minimum_uri: lilv::node::Node,
+    lv2_maximum_uri: lilv::node::Node,
+    param_sample_rate_uri: lilv::node::Node,
+    doap_license_uri: lilv::node::Node,
+    in_place_broken_feature_uri: lilv::node::Node,
+    display_priority_uri: lilv::node::Node,
+    power_of_2_block_length_feature_uri: lilv::node::Node,
+    pg_group_uri: lilv::node::Node,
 }
 
 impl CommonUris {
@@ -253,7 +534,51 @@ impl CommonUris {
             atom_port_uri: world.new_uri("http://lv2plug.in/ns/ext/atom#AtomPort"),
             cv_port_uri: world.new_uri("http://lv2plug.in/ns/lv2core#CVPort"),
             mod_cv_port_uri: world.new_uri("http://moddevices.com/ns/mod#CVPort"),
+            morph_port_uri: world.new_uri("http://lv2plug.in/ns/ext/morph#MorphPort"),
+            auto_morph_port_uri: world.new_uri("http://lv2plug.in/ns/ext/morph#AutoMorphPort"),
             worker_schedule_feature_uri: world.new_uri("http://lv2plug.in/ns/ext/worker#schedule"),
+            worker_interface_uri: world.new_uri("http://lv2plug.in/ns/ext/worker#interface"),
+            trigger_port_property_uri: world.new_uri("http://lv2plug.in/ns/ext/port-props#trigger"),
+            sample_rate_port_property_uri: world.new_uri("http://lv2plug.in/ns/lv2core#sampleRate"),
+            toggled_port_property_uri: world.new_uri("http://lv2plug.in/ns/lv2core#toggled"),
+            integer_port_property_uri: world.new_uri("http://lv2plug.in/ns/lv2core#integer"),
+            logarithmic_port_property_uri: world
+                .new_uri("http://lv2plug.in/ns/ext/port-props#logarithmic"),
+            enumeration_port_property_uri: world
+                .new_uri("http://lv2plug.in/ns/lv2core#enumeration"),
+            expensive_port_property_uri: world
+                .new_uri("http://lv2plug.in/ns/ext/port-props#expensive"),
+            not_on_gui_port_property_uri: world
+                .new_uri("http://lv2plug.in/ns/ext/port-props#notOnGUI"),
+            atom_supports_uri: world.new_uri("http://lv2plug.in/ns/ext/atom#supports"),
+            units_unit_uri: world.new_uri("http://lv2plug.in/ns/extensions/units#unit"),
+            units_render_uri: world.new_uri("http://lv2plug.in/ns/extensions/units#render"),
+            units_symbol_uri: world.new_uri("http://lv2plug.in/ns/extensions/units#symbol"),
+            bounded_block_length_feature_uri: world
+                .new_uri("http://lv2plug.in/ns/ext/buf-size#boundedBlockLength"),
+            fixed_block_length_feature_uri: world
+                .new_uri("http://lv2plug.in/ns/ext/buf-size#fixedBlockLength"),
+            coarse_block_length_feature_uri: world
+                .new_uri("http://lv2plug.in/ns/ext/buf-size#coarseBlockLength"),
+            preset_uri: world.new_uri("http://lv2plug.in/ns/ext/presets#Preset"),
+            pset_value_uri: world.new_uri("http://lv2plug.in/ns/ext/presets#value"),
+            rdfs_label_uri: world.new_uri("http://www.w3.org/2000/01/rdf-schema#label"),
+            rdfs_comment_uri: world.new_uri("http://www.w3.org/2000/01/rdf-schema#comment"),
+            rdfs_see_also_uri: world.new_uri("http://www.w3.org/2000/01/rdf-schema#seeAlso"),
+            lv2_port_uri: world.new_uri("http://lv2plug.in/ns/lv2core#port"),
+            lv2_symbol_uri: world.new_uri("http://lv2plug.in/ns/lv2core#symbol"),
+            lv2_designation_uri: world.new_uri("http://lv2plug.in/ns/lv2core#designation"),
+            lv2_minimum_uri: world.new_uri("http://lv2plug.in/ns/lv2core#minimum"),
+            lv2_maximum_uri: world.new_uri("http://lv2plug.in/ns/lv2core#maximum"),
+            param_sample_rate_uri: world.new_uri("http://lv2plug.in/ns/ext/parameters#sampleRate"),
+            doap_license_uri: world.new_uri("http://usefulinc.com/ns/doap#license"),
+            in_place_broken_feature_uri: world
+                .new_uri("http://lv2plug.in/ns/lv2core#inPlaceBroken"),
+            display_priority_uri: world
+                .new_uri("http://lv2plug.in/ns/ext/port-props#displayPriority"),
+            power_of_2_block_length_feature_uri: world
+                .new_uri("http://lv2plug.in/ns/ext/buf-size#powerOf2BlockLength"),
+            pg_group_uri: world.new_uri("http://lv2plug.in/ns/ext/port-groups#group"),
         }
     }
 }
@@ -276,17 +601,44 @@ mod tests {
         let features = world.build_features(crate::features::FeaturesBuilder {
             min_block_length: MIN_BLOCK_SIZE,
             max_block_length: MAX_BLOCK_SIZE,
+            ..crate::features::FeaturesBuilder::default()
         });
         assert!(features.midi_urid() > 0, "midi urid is not valid");
     }
 
+    #[test]
+    fn test_urid_str_matches_urid() {
+        let world = World::new();
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let uri = "http://lv2plug.in/ns/ext/midi#MidiEvent";
+        assert_eq!(features.urid_str(uri), features.midi_urid());
+        assert_eq!(features.uri(features.urid_str(uri)), Some(uri));
+    }
+
+    #[test]
+    fn test_iter_urids() {
+        let world = World::new();
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let urid = features.urid_str("http://example.com/livi#test");
+        let urids = features.iter_urids();
+        assert!(urids.contains(&(urid, "http://example.com/livi#test".to_string())));
+        assert!(urids.contains(&(
+            features.midi_urid(),
+            "http://lv2plug.in/ns/ext/midi#MidiEvent".to_string()
+        )));
+        assert!(urids.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
     #[test]
     fn test_all() {
         let world = World::new();
         let block_size = 64;
+        let state_dir = tempfile::tempdir().unwrap();
         let features = world.build_features(crate::features::FeaturesBuilder {
             min_block_length: block_size,
             max_block_length: block_size,
+            state_dir: Some(state_dir.path().to_path_buf()),
+            ..crate::features::FeaturesBuilder::default()
         });
         for plugin in world.iter_plugins() {
             if plugin
@@ -339,6 +691,9 @@ mod tests {
                 cv_outputs: cv_out
                     .chunks_exact_mut(block_size)
                     .take(port_counts.cv_outputs),
+                control_inputs: &[],
+                control_outputs: &mut [],
+                cv_clamping: false,
             };
             unsafe {
                 assert_eq!(
@@ -375,6 +730,7 @@ mod tests {
         let features = world.build_features(FeaturesBuilder {
             min_block_length: MIN_BLOCK_SIZE,
             max_block_length: MAX_BLOCK_SIZE,
+            ..FeaturesBuilder::default()
         });
         let mut instance = unsafe {
             plugin
@@ -440,6 +796,7 @@ mod tests {
         let features = world.build_features(FeaturesBuilder {
             min_block_length: block_size,
             max_block_length: block_size,
+            ..FeaturesBuilder::default()
         });
         let mut instance = unsafe {
             plugin
@@ -510,6 +867,282 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_plugins_by_bundle() {
+        let world = World::new();
+        let plugins_by_bundle = world.plugins_by_bundle();
+        assert_eq!(
+            plugins_by_bundle.values().map(Vec::len).sum::<usize>(),
+            world.iter_plugins().len()
+        );
+        let epiano = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .unwrap();
+        assert!(plugins_by_bundle[&epiano.bundle_uri()].contains(&epiano));
+    }
+
+    #[test]
+    fn test_plugin_count_and_contains_plugin() {
+        let world = World::new();
+        assert_eq!(world.plugin_count(), world.iter_plugins().len());
+        assert!(world.contains_plugin("http://drobilla.net/plugins/mda/EPiano"));
+        assert!(!world.contains_plugin("http://example.com/plugins/missing"));
+    }
+
+    #[test]
+    fn test_plugins_by_class() {
+        let world = World::new();
+        let epiano = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .unwrap();
+        let amp = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-amp")
+            .unwrap();
+        let instruments = world.plugins_by_class("http://lv2plug.in/ns/lv2core#InstrumentPlugin");
+        assert!(instruments.contains(&epiano));
+        assert!(!instruments.contains(&amp));
+        assert!(instruments.iter().all(Plugin::is_instrument));
+        assert_eq!(
+            world
+                .plugins_by_class("http://lv2plug.in/ns/lv2core#Plugin")
+                .len(),
+            world.iter_plugins().len()
+        );
+        assert!(world
+            .plugins_by_class("http://example.com/NotAClass")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_load_user_presets() {
+        let dir = tempfile::tempdir().unwrap();
+        let other_dir = tempfile::tempdir().unwrap();
+        for (dir, bundle, plugin) in [
+            (&dir, "epiano.lv2", "http://drobilla.net/plugins/mda/EPiano"),
+            (&dir, "missing.lv2", "http://example.com/plugins/missing"),
+            (
+                &other_dir,
+                "epiano.lv2",
+                "http://drobilla.net/plugins/mda/EPiano",
+            ),
+        ] {
+            let bundle = dir.path().join(bundle);
+            std::fs::create_dir(&bundle).unwrap();
+            std::fs::write(
+                bundle.join("manifest.ttl"),
+                format!(
+                    r#"@prefix lv2: <http://lv2plug.in/ns/lv2core#> .
+@prefix pset: <http://lv2plug.in/ns/ext/presets#> .
+@prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .
+
+<preset.ttl>
+    a pset:Preset ;
+    lv2:appliesTo <{plugin}> ;
+    rdfs:seeAlso <preset.ttl> .
+"#
+                ),
+            )
+            .unwrap();
+            std::fs::write(
+                bundle.join("preset.ttl"),
+                r#"@prefix lv2: <http://lv2plug.in/ns/lv2core#> .
+@prefix pset: <http://lv2plug.in/ns/ext/presets#> .
+@prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .
+
+<>
+    a pset:Preset ;
+    rdfs:label "My Preset" ;
+    lv2:port [
+        lv2:symbol "env_decay" ;
+        pset:value 0.25
+    ] .
+"#,
+            )
+            .unwrap();
+        }
+
+        let mut world = World::new();
+        let factory_presets = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .unwrap()
+            .presets()
+            .len();
+        assert_eq!(world.load_user_presets(dir.path()).unwrap(), 1);
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .unwrap();
+        let user_presets = plugin.user_presets();
+        assert_eq!(user_presets.len(), 1);
+        assert_eq!(user_presets[0].name, "My Preset");
+        assert_eq!(user_presets[0].port_values(), &[(PortIndex(0), 0.25)]);
+        assert_eq!(plugin.presets().len(), factory_presets);
+
+        // Only the presets of the given directory are counted.
+        assert_eq!(world.load_user_presets(other_dir.path()).unwrap(), 1);
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .unwrap();
+        assert_eq!(plugin.user_presets().len(), 2);
+        assert_eq!(world.load_user_presets(dir.path()).unwrap(), 1);
+    }
+
+    /// Write a bundle named `name` to `dir` with the plugin `uri` that is
+    /// described by `plugin_ttl`. The plugin uses the binary of eg-amp, so it
+    /// can only be instantiated if it has the eg-amp URI.
+    pub(crate) fn write_test_bundle(dir: &Path, name: &str, uri: &str, plugin_ttl: &str) {
+        let binary = World::new()
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-amp")
+            .expect("Plugin not found.")
+            .raw()
+            .library_uri()
+            .expect("Plugin has no binary.");
+        let binary = binary.as_uri().unwrap();
+        let bundle = dir.join(name);
+        std::fs::create_dir(&bundle).unwrap();
+        std::fs::write(
+            bundle.join("manifest.ttl"),
+            format!(
+                r#"@prefix lv2: <http://lv2plug.in/ns/lv2core#> .
+@prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .
+
+<{uri}>
+    a lv2:Plugin ;
+    lv2:binary <{binary}> ;
+    rdfs:seeAlso <plugin.ttl> .
+"#
+            ),
+        )
+        .unwrap();
+        std::fs::write(
+            bundle.join("plugin.ttl"),
+            format!(
+                r#"@prefix doap: <http://usefulinc.com/ns/doap#> .
+@prefix lv2: <http://lv2plug.in/ns/lv2core#> .
+@prefix morph: <http://lv2plug.in/ns/ext/morph#> .
+@prefix param: <http://lv2plug.in/ns/ext/parameters#> .
+@prefix pg: <http://lv2plug.in/ns/ext/port-groups#> .
+@prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .
+
+{plugin_ttl}
+"#
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_morph_ports_use_their_declared_data_type() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, uri, classes) in [
+            (
+                "morph.lv2",
+                "http://example.com/plugins/morph",
+                "lv2:AudioPort, morph:MorphPort",
+            ),
+            (
+                "auto-morph.lv2",
+                "http://example.com/plugins/auto-morph",
+                "lv2:CVPort, morph:AutoMorphPort",
+            ),
+            (
+                "untyped.lv2",
+                "http://example.com/plugins/untyped",
+                "morph:MorphPort",
+            ),
+            (
+                "ambiguous.lv2",
+                "http://example.com/plugins/ambiguous",
+                "lv2:AudioPort, lv2:CVPort, morph:MorphPort",
+            ),
+        ] {
+            write_test_bundle(
+                dir.path(),
+                name,
+                uri,
+                &format!(
+                    r#"<{uri}>
+    a lv2:Plugin ;
+    doap:name "Morph" ;
+    lv2:port [
+        a lv2:InputPort, {classes} ;
+        lv2:index 0 ;
+        lv2:symbol "in" ;
+        lv2:name "In"
+    ] ."#
+                ),
+            );
+        }
+
+        let world = World::with_search_path(&[dir.path()]);
+        let port_type = |uri| {
+            let plugin = world.plugin_by_uri(uri).expect("Plugin not found.");
+            let port = plugin.ports().next().unwrap();
+            port.port_type
+        };
+        assert_eq!(
+            port_type("http://example.com/plugins/morph"),
+            PortType::AudioInput
+        );
+        assert_eq!(
+            port_type("http://example.com/plugins/auto-morph"),
+            PortType::CVInput
+        );
+        assert!(!world.contains_plugin("http://example.com/plugins/untyped"));
+        assert!(!world.contains_plugin("http://example.com/plugins/ambiguous"));
+    }
+
+    #[test]
+    fn test_load_plugin_by_uri() {
+        let uri = "http://lv2plug.in/plugins/eg-fifths";
+        let world = World::load_plugin_by_uri(uri).expect("Bundle not found.");
+        assert!(world.plugin_by_uri(uri).is_some());
+        assert!(world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .is_none());
+
+        assert!(World::load_plugin_by_uri("http://example.com/not-a-plugin").is_none());
+    }
+
+    #[test]
+    fn test_with_search_path() {
+        let bundle_uri = World::new()
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .unwrap()
+            .bundle_uri();
+        let bundle_path = bundle_uri.strip_prefix("file://").unwrap();
+        let lv2_dir = Path::new(bundle_path).parent().unwrap();
+
+        let world = World::with_search_path(&[lv2_dir]);
+        assert!(world.contains_plugin("http://drobilla.net/plugins/mda/EPiano"));
+
+        let empty_dir = tempfile::tempdir().unwrap();
+        let world = World::with_search_path(&[empty_dir.path()]);
+        assert_eq!(world.plugin_count(), 0);
+    }
+
+    #[test]
+    fn test_with_load_bundles() {
+        let world = World::new();
+        let bundle_uri = |uri| world.plugin_by_uri(uri).unwrap().bundle_uri();
+        let fifths_bundle = bundle_uri("http://lv2plug.in/plugins/eg-fifths");
+        let sampler_bundle = bundle_uri("http://lv2plug.in/plugins/eg-sampler");
+
+        let world = World::with_load_bundles([
+            fifths_bundle.as_str(),
+            sampler_bundle.as_str(),
+            fifths_bundle.as_str(),
+        ]);
+        let mut uris: Vec<String> = world.iter_plugins().map(|p| p.uri().to_string()).collect();
+        uris.sort();
+        assert_eq!(
+            uris,
+            [
+                "http://lv2plug.in/plugins/eg-fifths",
+                "http://lv2plug.in/plugins/eg-sampler"
+            ]
+        );
+    }
+
     #[test]
     fn test_supported_features() {
         let supported_features = Features::supported_features();
@@ -521,8 +1154,89 @@ mod tests {
             "http://lv2plug.in/ns/ext/urid#unmap",
             "http://lv2plug.in/ns/ext/options#options",
             "http://lv2plug.in/ns/ext/buf-size#boundedBlockLength",
+            "http://lv2plug.in/ns/ext/buf-size#fixedBlockLength",
+            "http://lv2plug.in/ns/ext/buf-size#powerOf2BlockLength",
             "http://lv2plug.in/ns/ext/worker#schedule",
+            "http://lv2plug.in/ns/ext/state#freePath",
+            "http://lv2plug.in/ns/ext/log#log",
+            "http://lv2plug.in/ns/lv2core#inPlaceBroken",
         ]);
         assert_eq!(want, supported_features);
     }
+
+    #[test]
+    fn test_configurable_features() {
+        let want = HashSet::from([
+            "http://lv2plug.in/ns/ext/state#mapPath",
+            "http://lv2plug.in/ns/ext/state#makePath",
+        ]);
+        assert_eq!(want, Features::configurable_features());
+        assert!(Features::supported_features().is_disjoint(&want));
+    }
+
+    #[test]
+    fn test_provided_features_include_configured_features() {
+        let world = World::new();
+        let make_path = "http://lv2plug.in/ns/ext/state#makePath";
+        let features = world.build_features(FeaturesBuilder::default());
+        assert_eq!(features.provided_features(), Features::supported_features());
+        assert!(!features.provided_features().contains(make_path));
+
+        let features = world.build_features(FeaturesBuilder {
+            state_dir: Some(std::env::temp_dir().join("livi-test-provided-features")),
+            ..FeaturesBuilder::default()
+        });
+        assert!(features.provided_features().contains(make_path));
+    }
+
+    #[test]
+    fn test_block_length_features() {
+        let world = World::new();
+        let feature_uris = |min_block_length, max_block_length| -> Vec<String> {
+            let features = world.build_features(FeaturesBuilder {
+                min_block_length,
+                max_block_length,
+                ..FeaturesBuilder::default()
+            });
+            let worker_feature = lv2_raw::LV2Feature {
+                uri: b"http://example.com/worker\0".as_ptr().cast(),
+                data: std::ptr::null_mut(),
+            };
+            features
+                .iter_features(&worker_feature)
+                .map(|f| unsafe { std::ffi::CStr::from_ptr(f.uri) })
+                .map(|uri| uri.to_string_lossy().into_owned())
+                .filter(|uri| uri.starts_with("http://lv2plug.in/ns/ext/buf-size#"))
+                .collect()
+        };
+        assert_eq!(
+            feature_uris(256, 256),
+            [
+                "http://lv2plug.in/ns/ext/buf-size#boundedBlockLength",
+                "http://lv2plug.in/ns/ext/buf-size#fixedBlockLength",
+                "http://lv2plug.in/ns/ext/buf-size#powerOf2BlockLength",
+            ]
+        );
+        assert_eq!(
+            feature_uris(100, 300),
+            ["http://lv2plug.in/ns/ext/buf-size#boundedBlockLength"]
+        );
+    }
+
+    #[test]
+    fn test_unsupported_plugins_are_excluded() {
+        let world = World::new();
+        let supported_features = Features::supported_features();
+        for (uri, features) in world.unsupported_plugins() {
+            assert!(world.plugin_by_uri(&uri).is_none(), "{}", uri);
+            assert!(!features.is_empty(), "{}", uri);
+            for feature in features {
+                assert!(
+                    !supported_features.contains(feature.as_str()),
+                    "{}",
+                    feature
+                );
+            }
+        }
+    }
 }
@@ -0,0 +1,252 @@
+use crate::error::MidiFileError;
+use std::convert::TryFrom;
+
+/// The tempo in microseconds per quarter note if the file does not specify
+/// one. This corresponds to 120 beats per minute.
+const DEFAULT_TEMPO: u32 = 500_000;
+
+enum TrackEvent {
+    Tempo(u32),
+    Midi(Vec<u8>),
+}
+
+/// Parse the contents of a Standard MIDI File and return all of its MIDI
+/// events as tuples of the absolute frame and the MIDI data. The events of all
+/// tracks are merged and sorted by frame. The frames are computed from the
+/// file's tempo map at `sample_rate`.
+///
+/// Meta events are not returned. System exclusive events are returned with
+/// their leading `0xF0` status byte.
+///
+/// The result can be passed directly to `MidiScheduler::new`.
+///
+/// # Errors
+/// Returns an error if `bytes` is not a well formed Standard MIDI File or if it
+/// uses SMPTE based timing, which is not supported.
+pub fn parse(bytes: &[u8], sample_rate: f64) -> Result<Vec<(u64, Vec<u8>)>, MidiFileError> {
+    let mut reader = Reader { bytes, position: 0 };
+    if reader.take(4)? != b"MThd" {
+        return Err(MidiFileError::NotAMidiFile);
+    }
+    let ticks_per_quarter_note = {
+        let mut header = Reader {
+            bytes: reader.take_chunk()?,
+            position: 0,
+        };
+        let _format = header.u16()?;
+        let _tracks = header.u16()?;
+        let division = header.u16()?;
+        if division & 0x8000 != 0 {
+            return Err(MidiFileError::UnsupportedTimeDivision);
+        }
+        f64::from(division.max(1))
+    };
+
+    let mut events = Vec::new();
+    while !reader.is_empty() {
+        let chunk_type = reader.take(4)?;
+        let chunk = reader.take_chunk()?;
+        // Unknown chunks must be ignored.
+        if chunk_type == b"MTrk" {
+            read_track(
+                Reader {
+                    bytes: chunk,
+                    position: 0,
+                },
+                &mut events,
+            )?;
+        }
+    }
+    // The sort is stable so simultaneous events keep their track order.
+    events.sort_by_key(|(tick, _)| *tick);
+
+    let mut tempo = DEFAULT_TEMPO;
+    let mut last_tick = 0;
+    let mut seconds = 0.0;
+    let mut midi_events = Vec::with_capacity(events.len());
+    for (tick, event) in events {
+        seconds +=
+            (tick - last_tick) as f64 * f64::from(tempo) / 1_000_000.0 / ticks_per_quarter_note;
+        last_tick = tick;
+        match event {
+            TrackEvent::Tempo(t) => tempo = t,
+            TrackEvent::Midi(data) => {
+                midi_events.push(((seconds * sample_rate).round() as u64, data));
+            }
+        }
+    }
+    Ok(midi_events)
+}
+
+fn read_track(
+    mut reader: Reader,
+    events: &mut Vec<(u64, TrackEvent)>,
+) -> Result<(), MidiFileError> {
+    let mut tick = 0;
+    let mut running_status = None;
+    while !reader.is_empty() {
+        tick += u64::from(reader.variable_length_quantity()?);
+        let status = match reader.peek()? {
+            s if s & 0x80 != 0 => {
+                reader.position += 1;
+                s
+            }
+            _ => running_status.ok_or(MidiFileError::InvalidEvent {
+                offset: reader.position,
+            })?,
+        };
+        match status {
+            0xFF => {
+                running_status = None;
+                let meta_type = reader.u8()?;
+                let length = reader.variable_length_quantity()? as usize;
+                let data = reader.take(length)?;
+                match meta_type {
+                    // End of track.
+                    0x2F => return Ok(()),
+                    0x51 if data.len() == 3 => {
+                        let tempo = u32::from_be_bytes([0, data[0], data[1], data[2]]);
+                        events.push((tick, TrackEvent::Tempo(tempo)));
+                    }
+                    _ => (),
+                }
+            }
+            0xF0 | 0xF7 => {
+                running_status = None;
+                let length = reader.variable_length_quantity()? as usize;
+                let mut data = Vec::with_capacity(length + 1);
+                // 0xF7 is an escape that contains the raw bytes to send.
+                if status == 0xF0 {
+                    data.push(0xF0);
+                }
+                data.extend_from_slice(reader.take(length)?);
+                events.push((tick, TrackEvent::Midi(data)));
+            }
+            _ => {
+                running_status = Some(status);
+                let data_length = match status & 0xF0 {
+                    0xC0 | 0xD0 => 1,
+                    _ => 2,
+                };
+                let mut data = Vec::with_capacity(data_length + 1);
+                data.push(status);
+                data.extend_from_slice(reader.take(data_length)?);
+                events.push((tick, TrackEvent::Midi(data)));
+            }
+        }
+    }
+    Ok(())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+
+    fn peek(&self) -> Result<u8, MidiFileError> {
+        self.bytes
+            .get(self.position)
+            .copied()
+            .ok_or(MidiFileError::UnexpectedEnd)
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], MidiFileError> {
+        let end = self
+            .position
+            .checked_add(length)
+            .ok_or(MidiFileError::UnexpectedEnd)?;
+        let data = self
+            .bytes
+            .get(self.position..end)
+            .ok_or(MidiFileError::UnexpectedEnd)?;
+        self.position = end;
+        Ok(data)
+    }
+
+    fn take_chunk(&mut self) -> Result<&'a [u8], MidiFileError> {
+        let length = self.u32()?;
+        self.take(usize::try_from(length).map_err(|_| MidiFileError::UnexpectedEnd)?)
+    }
+
+    fn u8(&mut self) -> Result<u8, MidiFileError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, MidiFileError> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, MidiFileError> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn variable_length_quantity(&mut self) -> Result<u32, MidiFileError> {
+        let mut value = 0u32;
+        // A variable length quantity is at most 4 bytes.
+        for _ in 0..4 {
+            let b = self.u8()?;
+            value = (value << 7) | u32::from(b & 0x7F);
+            if b & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(MidiFileError::InvalidEvent {
+            offset: self.position,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_with_tempo_and_running_status() {
+        #[rustfmt::skip]
+        let bytes = [
+            b'M', b'T', b'h', b'd', 0, 0, 0, 6,
+            0, 0, // Format 0.
+            0, 1, // 1 track.
+            0, 96, // 96 ticks per quarter note.
+            b'M', b'T', b'r', b'k', 0, 0, 0, 21,
+            // 60 beats per minute.
+            0x00, 0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40,
+            0x00, 0x90, 0x40, 0x7F,
+            // Running status, one quarter note later.
+            0x60, 0x40, 0x00,
+            0x00, 0xC0, 0x05,
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        assert_eq!(
+            parse(&bytes, 44100.0).unwrap(),
+            vec![
+                (0, vec![0x90, 0x40, 0x7F]),
+                (44100, vec![0x90, 0x40, 0x00]),
+                (44100, vec![0xC0, 0x05]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_input() {
+        assert_eq!(
+            parse(b"RIFF....", 44100.0).err(),
+            Some(MidiFileError::NotAMidiFile)
+        );
+        assert_eq!(
+            parse(b"MThd\0\0\0\x06\0\0", 44100.0).err(),
+            Some(MidiFileError::UnexpectedEnd)
+        );
+        assert_eq!(
+            parse(b"MThd\0\0\0\x06\0\0\0\x01\xE7\x28", 44100.0).err(),
+            Some(MidiFileError::UnsupportedTimeDivision)
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use crate::error::EventError;
+use crate::event::LV2AtomSequence;
+use std::convert::TryFrom;
+
+#[cfg(feature = "midi-file")]
+pub mod file;
+
+/// Schedules MIDI events that are timed by absolute frame into atom sequences,
+/// one block at a time.
+///
+/// #### Example usage:
+/// ```
+/// # let world = livi::World::new();
+/// # let features = world.build_features(livi::FeaturesBuilder::default());
+/// let mut scheduler = livi::midi::MidiScheduler::new(vec![
+///     (0, [0x90, 0x40, 0x7f]),
+///     (44100, [0x80, 0x40, 0x00]),
+/// ]);
+/// let mut sequence = livi::event::LV2AtomSequence::new(&features, 1024);
+/// while !scheduler.is_finished() {
+///     scheduler
+///         .fill_block(&mut sequence, features.midi_urid(), 512)
+///         .unwrap();
+///     // Run the plugin instance with `sequence` as its input.
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct MidiScheduler {
+    events: Vec<(u64, Vec<u8>)>,
+    next_event: usize,
+    frame: u64,
+}
+
+impl MidiScheduler {
+    /// Create a new scheduler for `events`. Each event is a tuple of the
+    /// absolute frame and the MIDI data. The events do not have to be sorted.
+    pub fn new<I, M>(events: I) -> MidiScheduler
+    where
+        I: IntoIterator<Item = (u64, M)>,
+        M: AsRef<[u8]>,
+    {
+        let mut events: Vec<(u64, Vec<u8>)> = events
+            .into_iter()
+            .map(|(frame, data)| (frame, data.as_ref().to_vec()))
+            .collect();
+        events.sort_by_key(|(frame, _)| *frame);
+        MidiScheduler {
+            events,
+            next_event: 0,
+            frame: 0,
+        }
+    }
+
+    /// The frame at which the next block starts.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Returns `true` if all events have been scheduled.
+    pub fn is_finished(&self) -> bool {
+        self.next_event >= self.events.len()
+    }
+
+    /// Clear `sequence` and fill it with the events that fall within the next
+    /// block of `samples` frames. The time of each event is relative to the
+    /// start of the block. Afterwards, the scheduler advances to the next
+    /// block. The frame saturates at `u64::MAX`.
+    ///
+    /// # Errors
+    /// Returns an error if `sequence` does not have the capacity for all the
+    /// events in the block. The events that did not fit are scheduled at the
+    /// start of the next block.
+    pub fn fill_block(
+        &mut self,
+        sequence: &mut LV2AtomSequence,
+        midi_urid: lv2_raw::LV2Urid,
+        samples: usize,
+    ) -> Result<(), EventError> {
+        sequence.clear();
+        let block_start = self.frame;
+        let block_end = block_start.saturating_add(u64::try_from(samples).unwrap_or(u64::MAX));
+        self.frame = block_end;
+        while let Some((frame, data)) = self.events.get(self.next_event) {
+            if *frame >= block_end {
+                break;
+            }
+            // Events before the block were delayed and are sent at its start.
+            let time_in_frames =
+                i64::try_from(frame.saturating_sub(block_start)).unwrap_or(i64::MAX);
+            sequence.push_raw_atom(time_in_frames, midi_urid, data)?;
+            self.next_event += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lazy_static::lazy_static;
+    use std::sync::Arc;
+
+    lazy_static! {
+        static ref TEST_WORLD: crate::World = crate::World::new();
+    }
+
+    fn test_features() -> Arc<crate::Features> {
+        TEST_WORLD.build_features(crate::features::FeaturesBuilder {
+            min_block_length: 1024,
+            max_block_length: 1024,
+            ..crate::features::FeaturesBuilder::default()
+        })
+    }
+
+    #[test]
+    fn test_fill_block_splits_events_by_block() {
+        let features = test_features();
+        let mut scheduler = MidiScheduler::new(vec![
+            (150, vec![0x80, 0x40, 0x00]),
+            (10, vec![0x90, 0x40, 0x7f]),
+            (99, vec![0x90, 0x41, 0x7f]),
+        ]);
+        let mut sequence = LV2AtomSequence::new(&features, 1024);
+
+        scheduler
+            .fill_block(&mut sequence, features.midi_urid(), 100)
+            .unwrap();
+        let got = sequence
+            .iter()
+            .map(|e| (e.event.time_in_frames, e.data.to_vec()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            got,
+            vec![(10, vec![0x90, 0x40, 0x7f]), (99, vec![0x90, 0x41, 0x7f])]
+        );
+        assert!(!scheduler.is_finished());
+
+        scheduler
+            .fill_block(&mut sequence, features.midi_urid(), 100)
+            .unwrap();
+        let got = sequence
+            .iter()
+            .map(|e| (e.event.time_in_frames, e.data.to_vec()))
+            .collect::<Vec<_>>();
+        assert_eq!(got, vec![(50, vec![0x80, 0x40, 0x00])]);
+        assert!(scheduler.is_finished());
+        assert_eq!(scheduler.frame(), 200);
+    }
+
+    #[test]
+    fn test_fill_block_saturates_frame() {
+        let features = test_features();
+        let mut scheduler = MidiScheduler::new(Vec::<(u64, Vec<u8>)>::new());
+        let mut sequence = LV2AtomSequence::new(&features, 1024);
+        for _ in 0..2 {
+            scheduler
+                .fill_block(&mut sequence, features.midi_urid(), usize::MAX)
+                .unwrap();
+        }
+        assert_eq!(scheduler.frame(), u64::MAX);
+    }
+}
@@ -1,37 +1,49 @@
 use std::boxed::Box;
+use std::ffi::CStr;
 use std::fmt::Debug;
 use std::sync::{Arc, Mutex};
 
+use crate::class_utils::{Class, ANALYSER_CLASS_URI, EFFECT_CLASS_URIS, INSTRUMENT_CLASS_URI};
 use crate::features::Features;
 use crate::port::{ControlPort, Controls};
 use crate::{
-    error::{InstantiateError, RunError},
+    error::{BlockLengthError, InstantiateError, OptionsError, RunError, StateError},
     event::LV2AtomSequence,
-    features::worker,
-    port::{DataType, IOType},
-    CommonUris, Port, PortConnections, PortCounts, PortIndex, PortType,
+    features::{options, worker},
+    midi::MidiScheduler,
+    port::{DataType, IOType, PortProperties},
+    preset::{Preset, UserPresetDirs},
+    state::{self, PluginState},
+    ChannelLayout, CommonUris, EmptyPortConnections, IoLayout, OptionValue, Port, PortConnections,
+    PortCounts, PortGroup, PortGroupMember, PortIndex, PortType, PortUnit, ScalePoint,
+    TransportState, WorkerManager,
 };
 use lv2_raw::LV2Feature;
 use lv2_sys::LV2_Worker_Schedule;
-use ringbuf::HeapProducer;
 
 /// A plugin that can be used to instantiate plugin instances.
 #[derive(Clone)]
 pub struct Plugin {
     pub(crate) inner: lilv::plugin::Plugin,
+    pub(crate) world: Arc<lilv::World>,
     pub(crate) common_uris: Arc<CommonUris>,
+    user_preset_dirs: UserPresetDirs,
+    uri: String,
+    name: String,
     port_counts: PortCounts,
-    classes: Vec<String>,
+    classes: Vec<Class>,
 }
 
 impl Plugin {
     pub(crate) fn from_raw(
         plugin: lilv::plugin::Plugin,
+        world: Arc<lilv::World>,
         common_uris: Arc<CommonUris>,
-        classes: Vec<String>,
+        user_preset_dirs: UserPresetDirs,
+        classes: Vec<Class>,
     ) -> Plugin {
         let mut port_counts = PortCounts::default();
-        for port in iter_ports_impl(&plugin, &common_uris) {
+        for port in iter_ports_impl(&plugin, &world, &common_uris) {
             match port.port_type {
                 PortType::ControlInput => port_counts.control_inputs += 1,
                 PortType::ControlOutput => port_counts.control_outputs += 1,
@@ -43,9 +55,17 @@ impl Plugin {
                 PortType::CVOutput => port_counts.cv_outputs += 1,
             }
         }
+        // The uri and name are queried once since they are used often, for
+        // example when searching for plugins.
+        let uri = plugin.uri().as_str().unwrap_or("BAD_URI").to_string();
+        let name = plugin.name().as_str().unwrap_or("BAD_NAME").to_string();
         Plugin {
             inner: plugin,
+            world,
             common_uris,
+            user_preset_dirs,
+            uri,
+            name,
             port_counts,
             classes,
         }
@@ -58,31 +78,290 @@ impl Plugin {
 
     /// A unique identifier for the plugin.
     #[must_use]
-    pub fn uri(&self) -> String {
-        self.inner.uri().as_str().unwrap_or("BAD_URI").to_string()
+    pub fn uri(&self) -> &str {
+        &self.uri
     }
 
     /// The name of the plugin.
     #[must_use]
-    pub fn name(&self) -> String {
-        self.inner.name().as_str().unwrap_or("BAD_NAME").to_string()
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The name of the plugin's author, as declared with `doap:maintainer` or
+    /// `doap:developer`.
+    #[must_use]
+    pub fn author_name(&self) -> Option<String> {
+        Some(self.inner.author_name()?.as_str()?.to_string())
+    }
+
+    /// The email address of the plugin's author.
+    #[must_use]
+    pub fn author_email(&self) -> Option<String> {
+        let email = self.inner.author_email()?;
+        let email = email.as_uri().or_else(|| email.as_str())?;
+        Some(email.to_string())
+    }
+
+    /// The homepage of the plugin's author, as declared with `foaf:homepage`.
+    #[must_use]
+    pub fn author_homepage(&self) -> Option<String> {
+        let homepage = self.inner.author_homepage()?;
+        let homepage = homepage.as_uri().or_else(|| homepage.as_str())?;
+        Some(homepage.to_string())
+    }
+
+    /// The URI of the project that the plugin belongs to, as declared with
+    /// `lv2:project`.
+    #[must_use]
+    pub fn project_uri(&self) -> Option<String> {
+        Some(self.inner.project()?.as_uri()?.to_string())
+    }
+
+    /// The description of the plugin, as declared with `rdfs:comment`. Hosts
+    /// may show this as help text, for example in a tooltip.
+    #[must_use]
+    pub fn comment(&self) -> Option<String> {
+        let comment = self
+            .inner
+            .value(&self.common_uris.rdfs_comment_uri)
+            .iter()
+            .next()?;
+        Some(comment.as_str()?.to_string())
+    }
+
+    /// The URI of the plugin's license, as declared with `doap:license` on the
+    /// plugin or on its project.
+    #[must_use]
+    pub fn license(&self) -> Option<String> {
+        let license = &self.common_uris.doap_license_uri;
+        let node = match self.inner.value(license).iter().next() {
+            Some(node) => node,
+            None => {
+                let project = self.inner.project()?;
+                self.world.get(Some(&project), Some(license), None)?
+            }
+        };
+        let license = node.as_uri().or_else(|| node.as_str())?;
+        Some(license.to_string())
+    }
+
+    /// The URI of the bundle that contains the plugin. For example:
+    /// `file:///usr/lib/lv2/mda.lv2/`.
+    #[must_use]
+    pub fn bundle_uri(&self) -> String {
+        self.inner
+            .bundle_uri()
+            .as_uri()
+            .unwrap_or("BAD_URI")
+            .to_string()
+    }
+
+    /// The block length requirement of the plugin, as declared by its required
+    /// `buf-size` features. Hosts should configure the block lengths of the
+    /// `Features` accordingly.
+    #[must_use]
+    pub fn block_length_mode(&self) -> BlockLengthMode {
+        let required = self.inner.required_features();
+        if required.contains(&self.common_uris.fixed_block_length_feature_uri) {
+            BlockLengthMode::Fixed
+        } else if required.contains(&self.common_uris.coarse_block_length_feature_uri) {
+            BlockLengthMode::Coarse
+        } else if required.contains(&self.common_uris.bounded_block_length_feature_uri) {
+            BlockLengthMode::Bounded
+        } else {
+            BlockLengthMode::Unbounded
+        }
+    }
+
+    /// The minimum and maximum sample rate that the plugin supports, as
+    /// declared with `lv2:minimum` and `lv2:maximum` on the plugin's
+    /// `param:sampleRate`. If only one bound is declared, the other is `0.0` or
+    /// infinity. Returns `None` if the plugin does not declare a range.
+    ///
+    /// LV2 has no standard property for this. This reads the range the same way
+    /// that the range of a parameter is declared on an `lv2:Parameter`, with
+    /// the plugin as the subject:
+    ///
+    /// ```turtle
+    /// <http://example.com/plugin> param:sampleRate [
+    ///     lv2:minimum 44100 ;
+    ///     lv2:maximum 96000
+    /// ] .
+    /// ```
+    #[must_use]
+    pub fn sample_rate_range(&self) -> Option<(f64, f64)> {
+        let sample_rate = self
+            .inner
+            .value(&self.common_uris.param_sample_rate_uri)
+            .iter()
+            .next()?;
+        let bound = |predicate: &lilv::node::Node| {
+            let n = self.world.get(Some(&sample_rate), Some(predicate), None)?;
+            n.as_float()
+                .map(f64::from)
+                .or_else(|| n.as_int().map(f64::from))
+        };
+        let min = bound(&self.common_uris.lv2_minimum_uri);
+        let max = bound(&self.common_uris.lv2_maximum_uri);
+        if min.is_none() && max.is_none() {
+            return None;
+        }
+        Some((min.unwrap_or(0.0), max.unwrap_or(f64::INFINITY)))
+    }
+
+    /// Returns `true` if the plugin has the `lv2:inPlaceBroken` feature. Such
+    /// plugins can not use the same buffer for an input and an output.
+    /// `Instance::run` returns `RunError::InPlaceNotSupported` if they do.
+    #[must_use]
+    pub fn in_place_broken(&self) -> bool {
+        self.inner
+            .has_feature(&self.common_uris.in_place_broken_feature_uri)
+    }
+
+    /// The URIs of the features that the plugin requires. Plugins with
+    /// required features that livi does not support are not included in the
+    /// `World`, see `World::unsupported_plugins`.
+    #[must_use]
+    pub fn required_features(&self) -> Vec<String> {
+        nodes_to_uris(&self.inner.required_features())
+    }
+
+    /// The URIs of the features that the plugin can use if they are provided
+    /// but does not require. For example
+    /// `http://lv2plug.in/ns/ext/worker#schedule`.
+    #[must_use]
+    pub fn optional_features(&self) -> Vec<String> {
+        nodes_to_uris(&self.inner.optional_features())
+    }
+
+    /// How the plugin uses the worker extension. See `WorkerUsage`.
+    #[must_use]
+    pub fn worker_usage(&self) -> WorkerUsage {
+        if !self
+            .inner
+            .has_feature(&self.common_uris.worker_schedule_feature_uri)
+        {
+            WorkerUsage::None
+        } else if self
+            .inner
+            .has_extension_data(&self.common_uris.worker_interface_uri)
+        {
+            WorkerUsage::ScheduleFromRun
+        } else {
+            WorkerUsage::MissingInterface
+        }
+    }
+
+    /// The factory presets of the plugin, sorted by name. The resources of the
+    /// presets are loaded into the world as needed. Presets that were loaded
+    /// with `World::load_user_presets` are returned by `user_presets` instead.
+    pub fn presets(&self) -> Vec<Preset> {
+        let user_preset_dirs = self.user_preset_dirs.lock().unwrap().clone();
+        self.all_presets()
+            .into_iter()
+            .filter(|p| !p.is_in_dirs(&user_preset_dirs))
+            .collect()
+    }
+
+    /// The presets of the plugin that were loaded with
+    /// `World::load_user_presets`, sorted by name.
+    pub fn user_presets(&self) -> Vec<Preset> {
+        let user_preset_dirs = self.user_preset_dirs.lock().unwrap().clone();
+        self.all_presets()
+            .into_iter()
+            .filter(|p| p.is_in_dirs(&user_preset_dirs))
+            .collect()
+    }
+
+    pub(crate) fn all_presets(&self) -> Vec<Preset> {
+        let nodes = match self.inner.related(Some(&self.common_uris.preset_uri)) {
+            Some(nodes) => nodes,
+            None => return Vec::new(),
+        };
+        let ports: Vec<Port> = self.ports().collect();
+        let mut presets: Vec<Preset> = nodes
+            .iter()
+            .map(|node| {
+                // Presets are usually in their own files which are only
+                // loaded on demand.
+                let _ = self.world.load_resource(&node);
+                Preset::from_node(&self.world, &self.common_uris, &node, &ports)
+            })
+            .collect();
+        presets.sort_by(|a, b| a.name.cmp(&b.name));
+        presets
     }
 
     /// Returns the classes of the plugin. For example: "Instrument Plugin" or
     /// "Delay Plugin".
     pub fn classes(&self) -> impl ExactSizeIterator<Item = &str> {
-        self.classes.iter().map(|s| s.as_str())
+        self.classes.iter().map(|c| c.name.as_str())
+    }
+
+    /// The URI of the plugin's class. For example:
+    /// "http://lv2plug.in/ns/lv2core#InstrumentPlugin".
+    #[must_use]
+    pub fn class_uri(&self) -> String {
+        self.class_uris().next().unwrap_or_default().to_string()
+    }
+
+    /// Returns the URIs of the plugin's class and all its parent classes,
+    /// starting with the most specific class.
+    pub fn class_uris(&self) -> impl ExactSizeIterator<Item = &str> {
+        self.classes.iter().map(|c| c.uri.as_str())
     }
 
     /// Returns true if the plugin is an instrument plugin.
     pub fn is_instrument(&self) -> bool {
-        self.classes().any(|c| c == "Instrument Plugin")
+        self.has_class(INSTRUMENT_CLASS_URI)
+    }
+
+    /// Returns true if the plugin is an effect that processes its input. This
+    /// includes delays, distortions, dynamics, filters, modulators, reverbs,
+    /// simulators, spatial and spectral plugins and their subclasses.
+    pub fn is_effect(&self) -> bool {
+        EFFECT_CLASS_URIS.iter().any(|uri| self.has_class(uri))
+    }
+
+    /// Returns true if the plugin is an analyser plugin.
+    pub fn is_analyser(&self) -> bool {
+        self.has_class(ANALYSER_CLASS_URI)
+    }
+
+    /// Returns true if the plugin's class is `class_uri` or a subclass of it.
+    pub(crate) fn has_class(&self, class_uri: &str) -> bool {
+        self.class_uris().any(|uri| uri == class_uri)
+    }
+
+    /// Create a new instance of the plugin with the control inputs in
+    /// `controls` set before the instance is returned. This is equivalent to
+    /// calling `instantiate` followed by `Instance::set_control_inputs` but
+    /// the instance is never observable with the default values.
+    ///
+    /// # Errors
+    /// Returns an error if the plugin could not be instantiated or if
+    /// `sample_rate` is outside of the plugin's `sample_rate_range`.
+    ///
+    /// # Safety
+    /// Running plugin code is unsafe.
+    pub unsafe fn instantiate_with_controls(
+        &self,
+        features: Arc<Features>,
+        sample_rate: f64,
+        controls: &[(PortIndex, f32)],
+    ) -> Result<Instance, InstantiateError> {
+        let mut instance = self.instantiate(features, sample_rate)?;
+        instance.set_control_inputs(controls);
+        Ok(instance)
     }
 
     /// Create a new instance of the plugin.
     ///
     /// # Errors
-    /// Returns an error if the plugin could not be instantiated.
+    /// Returns an error if the plugin could not be instantiated, if
+    /// `sample_rate` is outside of the plugin's `sample_rate_range`, or if
+    /// `features` does not provide a feature that the plugin requires.
     ///
     /// # Safety
     /// Running plugin code is unsafe.
@@ -91,16 +370,46 @@ impl Plugin {
         features: Arc<Features>,
         sample_rate: f64,
     ) -> Result<Instance, InstantiateError> {
+        if let Some((min_supported, max_supported)) = self.sample_rate_range() {
+            if !(min_supported..=max_supported).contains(&sample_rate) {
+                return Err(InstantiateError::SampleRateUnsupported {
+                    plugin_uri: self.uri().to_string(),
+                    sample_rate,
+                    min_supported,
+                    max_supported,
+                });
+            }
+        }
+        let provided_features = features.provided_features();
+        let missing_features: Vec<String> = self
+            .required_features()
+            .into_iter()
+            .filter(|f| !provided_features.contains(f.as_str()))
+            .collect();
+        if !missing_features.is_empty() {
+            return Err(InstantiateError::FeatureSetupFailed {
+                plugin_uri: self.uri().to_string(),
+                missing_features,
+            });
+        }
         let min_block_size = features.min_block_length();
         let max_block_size = features.max_block_length();
 
-        let (instance_to_worker_sender, instance_to_worker_receiver) = worker::instantiate_queue();
-        let (worker_to_instance_sender, worker_to_instance_receiver) = worker::instantiate_queue();
-        let mut instance_to_worker_sender = Box::new(instance_to_worker_sender);
-        let instance_to_worker_sender_ptr: *mut HeapProducer<u8> =
-            instance_to_worker_sender.as_mut();
+        let max_message_size = features.worker_max_message_size();
+        let queue_messages = features.worker_queue_messages();
+        let (instance_to_worker_sender, instance_to_worker_receiver) =
+            worker::instantiate_queue(max_message_size, queue_messages);
+        let (worker_to_instance_sender, worker_to_instance_receiver) =
+            worker::instantiate_queue(max_message_size, queue_messages);
+        // The sender is behind a mutex so that plugins that call
+        // `schedule_work` from unexpected threads can not corrupt the queue.
+        let work_scheduler = Box::new(worker::WorkScheduler::new(
+            worker::MessageSender::new(instance_to_worker_sender, max_message_size),
+            features.worker_manager().signal().clone(),
+        ));
+        let work_scheduler_ptr: *const worker::WorkScheduler = work_scheduler.as_ref();
         let mut worker_schedule = Box::new(lv2_sys::LV2_Worker_Schedule {
-            handle: instance_to_worker_sender_ptr.cast(),
+            handle: work_scheduler_ptr as *mut std::ffi::c_void,
             schedule_work: Some(worker::schedule_work),
         });
 
@@ -110,15 +419,21 @@ impl Plugin {
             data: worker_schedule_ptr.cast(),
         };
 
-        let iter_features = features.iter_features(&worker_feature);
+        // The options are per instance since they include the sample rate.
+        let options = features.build_options(Some(sample_rate));
+        let iter_features = features.iter_features_with_options(&worker_feature, &options);
 
         let mut instance = self
             .inner
             .instantiate(sample_rate, iter_features)
-            .ok_or(InstantiateError::UnknownError)?;
+            .ok_or_else(|| InstantiateError::NullInstance {
+                plugin_uri: self.uri().to_string(),
+            })?;
 
-        let control_inputs = Controls::new(self.ports_with_type(PortType::ControlInput));
-        let control_outputs = Controls::new(self.ports_with_type(PortType::ControlOutput));
+        let control_inputs =
+            Controls::new(self.ports_with_type(PortType::ControlInput), sample_rate);
+        let mut control_outputs =
+            Controls::new(self.ports_with_type(PortType::ControlOutput), sample_rate);
         let mut audio_inputs = Vec::new();
         let mut audio_outputs = Vec::new();
         let mut atom_sequence_inputs = Vec::new();
@@ -129,8 +444,12 @@ impl Plugin {
             match port.port_type {
                 PortType::ControlInput => instance
                     .connect_port(port.index.0, control_inputs.value_ptr(port.index).unwrap()),
-                PortType::ControlOutput => instance
-                    .connect_port(port.index.0, control_outputs.value_ptr(port.index).unwrap()),
+                // The plugin writes its control outputs to the values of
+                // `control_outputs` on every `run`.
+                PortType::ControlOutput => instance.connect_port_mut(
+                    port.index.0,
+                    control_outputs.value_mut_ptr(port.index).unwrap(),
+                ),
                 PortType::AudioInput => audio_inputs.push(port.index),
                 PortType::AudioOutput => audio_outputs.push(port.index),
                 PortType::AtomSequenceInput => atom_sequence_inputs.push(port.index),
@@ -139,6 +458,18 @@ impl Plugin {
                 PortType::CVOutput => cv_outputs.push(port.index),
             }
         }
+        // Ports are connected in order of their index, which is what
+        // determines the channel order of the plugin.
+        for ports in [
+            &mut audio_inputs,
+            &mut audio_outputs,
+            &mut atom_sequence_inputs,
+            &mut atom_sequence_outputs,
+            &mut cv_inputs,
+            &mut cv_outputs,
+        ] {
+            ports.sort_unstable();
+        }
 
         let mut inner = instance.activate();
         #[allow(clippy::mutex_atomic)]
@@ -146,41 +477,142 @@ impl Plugin {
 
         let worker_interface =
             worker::maybe_get_worker_interface(&self.inner, &self.common_uris, &mut inner);
-        if let Some(worker_interface) = worker_interface.as_ref() {
-            let worker = worker::Worker::new(
+        if worker_interface.is_none() && self.requires_worker_interface() {
+            return Err(InstantiateError::MissingWorkerInterface {
+                plugin_uri: self.uri().to_string(),
+            });
+        }
+        let options_interface = options::maybe_get_options_interface(&inner);
+        let state_interface = state::maybe_get_state_interface(&inner);
+        let worker = worker_interface.as_ref().map(|worker_interface| {
+            worker::Worker::new(
                 is_alive.clone(),
                 *worker_interface,
                 inner.instance().handle(),
                 instance_to_worker_receiver,
-                worker_to_instance_sender,
-            );
-            features.worker_manager().add_worker(worker);
-        }
+                max_message_size,
+                worker::MessageSender::new(worker_to_instance_sender, max_message_size),
+                work_scheduler.dropped_messages().clone(),
+            )
+        });
 
-        Ok(Instance {
-            inner,
+        let input_ranges = Vec::with_capacity(audio_inputs.len() + cv_inputs.len());
+        let output_ranges = Vec::with_capacity(audio_outputs.len() + cv_outputs.len());
+        let audio_output_ptrs = Vec::with_capacity(audio_outputs.len());
+        let buffer_port_ptrs = Vec::with_capacity(
+            audio_inputs.len() + audio_outputs.len() + cv_inputs.len() + cv_outputs.len(),
+        );
+        let last_output_peaks = vec![0.0; audio_outputs.len()];
+        let padded_inputs = vec![vec![0.0; max_block_size]; audio_inputs.len() + cv_inputs.len()];
+        let padded_outputs =
+            vec![vec![0.0; max_block_size]; audio_outputs.len() + cv_outputs.len()];
+        let padded_output_ptrs = Vec::with_capacity(audio_outputs.len() + cv_outputs.len());
+        let transport_sequence = LV2AtomSequence::new(
+            &features,
+            if atom_sequence_inputs.is_empty() {
+                0
+            } else {
+                TRANSPORT_SEQUENCE_CAPACITY
+            },
+        );
+        let mut instance = Instance {
+            inner: Some(InstanceState::Active(inner)),
             min_block_size,
             max_block_size,
+            plugin_min_block_size: min_block_size,
+            plugin_max_block_size: max_block_size,
+            fixed_block_length: features.provides_fixed_block_length(),
             control_inputs,
             control_outputs,
             audio_inputs,
             audio_outputs,
             atom_sequence_inputs,
+            atom_output_ptrs: Vec::with_capacity(atom_sequence_outputs.len()),
+            atom_sequence_output_sinks: atom_sequence_outputs
+                .iter()
+                .map(|_| LV2AtomSequence::new(&features, ATOM_SEQUENCE_OUTPUT_SINK_CAPACITY))
+                .collect(),
+            transport_sequence,
             atom_sequence_outputs,
+            port_symbols: self.ports().map(|p| p.symbol).collect(),
+            // Only plugins with state have a reason to notify that it changed.
+            state_changed_urids: state_interface
+                .is_some()
+                .then(|| state::StateChangedUrids::new(&features)),
+            state_changed: false,
+            in_place_broken: self.in_place_broken(),
+            // Plugins that only optionally support powers of 2 may still get
+            // other block lengths if the `Features` do not provide it.
+            power_of_2_block_length: self
+                .inner
+                .required_features()
+                .contains(&self.common_uris.power_of_2_block_length_feature_uri)
+                || (features.provides_power_of_2_block_length()
+                    && self
+                        .inner
+                        .has_feature(&self.common_uris.power_of_2_block_length_feature_uri)),
+            nominal_block_length: None,
+            input_ranges,
+            output_ranges,
+            cv_input_bounds: self
+                .sorted_ports_with_type(PortType::CVInput)
+                .into_iter()
+                .map(|p| {
+                    let min = p.min_value.unwrap_or(f32::NEG_INFINITY);
+                    let max = p.max_value.unwrap_or(f32::INFINITY);
+                    // Invalid ranges are not clamped.
+                    if min <= max {
+                        (min, max)
+                    } else {
+                        (f32::NEG_INFINITY, f32::INFINITY)
+                    }
+                })
+                .collect(),
+            cv_input_buffers: vec![vec![0.0; max_block_size]; cv_inputs.len()],
+            padded_inputs,
+            padded_outputs,
+            padded_output_ptrs,
+            cv_inputs_out_of_range: vec![false; cv_inputs.len()],
             cv_inputs,
             cv_outputs,
             worker_interface,
+            options_interface,
+            state_interface,
+            latency_port: self.inner.latency_port_index().map(PortIndex),
+            enabled_port: self
+                .designated_port("http://lv2plug.in/ns/lv2core#enabled")
+                .filter(|&index| {
+                    self.ports_with_type(PortType::ControlInput)
+                        .any(|p| p.index == index)
+                }),
+            seed_port: self
+                .ports_with_type(PortType::ControlInput)
+                .find(Port::is_random_seed)
+                .map(|p| p.index),
+            audio_output_ptrs,
+            buffer_port_ptrs,
+            last_output_peaks,
+            worker_response_buffer: worker::message_buffer(max_message_size),
             worker_to_instance_receiver,
+            auto_handle_worker_responses: true,
             _worker_schedule: worker_schedule,
-            _instance_to_worker_sender: instance_to_worker_sender,
+            _work_scheduler: work_scheduler,
+            worker: None,
+            options,
             is_alive,
             _features: features,
-        })
+        };
+        // The worker thread may run the worker as soon as it is added, so it is
+        // only added once the instance is activated and fully constructed.
+        if let Some(worker) = worker {
+            instance.worker = Some(instance._features.worker_manager().add_worker(worker));
+        }
+        Ok(instance)
     }
 
     /// Iterate over all ports for the plugin.
     pub fn ports(&self) -> impl '_ + Iterator<Item = Port> {
-        iter_ports_impl(&self.inner, &self.common_uris)
+        iter_ports_impl(&self.inner, &self.world, &self.common_uris)
     }
 
     /// Get the number of ports for each type of port.
@@ -188,10 +620,172 @@ impl Plugin {
         &self.port_counts
     }
 
+    /// Get the channel layouts of the audio inputs and outputs. Hosts can use
+    /// this to decide how to route audio to and from the plugin before it is
+    /// instantiated.
+    pub fn io_layout(&self) -> IoLayout {
+        IoLayout::from_port_counts(&self.port_counts)
+    }
+
+    /// Iterate over the control input and output ports, sorted by their
+    /// `Port::display_priority` from highest to lowest. Ports without a
+    /// priority come last. Ports with the same priority are in index order.
+    pub fn ports_by_display_priority(&self) -> impl Iterator<Item = Port> {
+        let mut ports: Vec<Port> = self
+            .ports()
+            .filter(|p| {
+                matches!(
+                    p.port_type,
+                    PortType::ControlInput | PortType::ControlOutput
+                )
+            })
+            .collect();
+        ports.sort_by_key(|p| std::cmp::Reverse(p.display_priority));
+        ports.into_iter()
+    }
+
+    /// The index of the port that is designated with `designation_uri` through
+    /// `lv2:designation`. For example, "http://lv2plug.in/ns/lv2core#enabled"
+    /// for the port that bypasses the plugin or
+    /// "http://lv2plug.in/ns/lv2core#latency" for the port that reports
+    /// latency. Returns `None` if no port has the designation.
+    pub fn designated_port(&self, designation_uri: &str) -> Option<PortIndex> {
+        self.inner
+            .iter_ports()
+            .find(|p| {
+                p.value(&self.common_uris.lv2_designation_uri)
+                    .iter()
+                    .any(|d| d.as_uri() == Some(designation_uri))
+            })
+            .map(|p| PortIndex(p.index()))
+    }
+
+    /// Returns true if the plugin has a port that reports its latency. See
+    /// `Instance::latency_samples`.
+    pub fn has_latency_port(&self) -> bool {
+        self.inner.latency_port_index().is_some()
+    }
+
+    /// The index of the port with the given symbol, for example `"level_out"`.
+    /// Unlike the name, the symbol of a port is a stable identifier.
+    pub fn port_index_by_symbol(&self, symbol: &str) -> Option<PortIndex> {
+        self.inner
+            .port_by_symbol(&self.world.new_string(symbol))
+            .map(|p| PortIndex(p.index()))
+    }
+
+    /// The port groups of the plugin in the order that their first member
+    /// appears. Ports that are not in a group are not included.
+    pub fn port_groups(&self) -> Vec<PortGroup> {
+        let mut groups: Vec<PortGroup> = Vec::new();
+        for port in self.inner.iter_ports() {
+            let uri = match port
+                .get(&self.common_uris.pg_group_uri)
+                .and_then(|n| n.as_uri().map(str::to_string))
+            {
+                Some(uri) => uri,
+                None => continue,
+            };
+            let member = PortGroupMember {
+                index: PortIndex(port.index()),
+                designation: port
+                    .get(&self.common_uris.lv2_designation_uri)
+                    .and_then(|n| n.as_uri().map(str::to_string)),
+            };
+            match groups.iter_mut().find(|g| g.uri == uri) {
+                Some(group) => group.members.push(member),
+                None => {
+                    let symbol = self
+                        .world
+                        .get(
+                            Some(&self.world.new_uri(&uri)),
+                            Some(&self.common_uris.lv2_symbol_uri),
+                            None,
+                        )
+                        .and_then(|n| n.as_str().map(str::to_string));
+                    groups.push(PortGroup {
+                        uri,
+                        symbol,
+                        members: vec![member],
+                    });
+                }
+            }
+        }
+        groups
+    }
+
     /// Return all ports with the given type.
     pub fn ports_with_type(&self, port_type: PortType) -> impl '_ + Iterator<Item = Port> {
         self.ports().filter(move |p| p.port_type == port_type)
     }
+
+    /// Returns true if the plugin can not run without its `worker:interface`.
+    fn requires_worker_interface(&self) -> bool {
+        self.inner
+            .required_features()
+            .contains(&self.common_uris.worker_schedule_feature_uri)
+            || self
+                .inner
+                .has_extension_data(&self.common_uris.worker_interface_uri)
+    }
+
+    /// Get the ports of a specific type sorted by their index.
+    fn sorted_ports_with_type(&self, port_type: PortType) -> Vec<Port> {
+        let mut ports: Vec<Port> = self.ports_with_type(port_type).collect();
+        ports.sort_by_key(|p| p.index);
+        ports
+    }
+}
+
+/// The capacity of the sequences that atom sequence outputs are connected to
+/// when they are not connected by the host.
+const ATOM_SEQUENCE_OUTPUT_SINK_CAPACITY: usize = 8192;
+
+/// The capacity of the sequence that holds the transport position and the
+/// events of the first atom sequence input in `Instance::run_with_transport`.
+const TRANSPORT_SEQUENCE_CAPACITY: usize = 16384;
+
+/// The block length requirement of a plugin.
+/// See http://lv2plug.in/ns/ext/buf-size.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BlockLengthMode {
+    /// The plugin requires `buf-size:boundedBlockLength`. The block length of
+    /// each run must be between the minimum and maximum block length.
+    Bounded,
+
+    /// The plugin requires `buf-size:fixedBlockLength`. Every run must use
+    /// the same block length, so the minimum and maximum block length should
+    /// be equal.
+    Fixed,
+
+    /// The plugin requires `buf-size:coarseBlockLength`. The block length
+    /// should not be split into smaller blocks, for example around events.
+    Coarse,
+
+    /// The plugin does not require any block length feature.
+    Unbounded,
+}
+
+/// How a plugin uses the worker extension.
+/// See http://lv2plug.in/ns/ext/worker.
+///
+/// livi provides `worker:schedule` with the expectation that `schedule_work` is
+/// called from `run`. Scheduled work is done by a `Worker` when
+/// `WorkerManager::run_workers` is called and the responses are delivered at
+/// the start of the next `run`. `schedule_work` is safe to call from other
+/// threads, but concurrent calls fail instead of blocking.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WorkerUsage {
+    /// The plugin does not use `worker:schedule`.
+    None,
+
+    /// The plugin uses `worker:schedule` and provides `worker:interface`. This
+    /// matches how livi runs workers.
+    ScheduleFromRun,
+
+    /// The plugin uses `worker:schedule` but does not declare
+    /// `worker:interface`. Work that it schedules can not be done.
+    MissingInterface,
 }
 
 impl Debug for Plugin {
@@ -208,14 +802,13 @@ impl Debug for Plugin {
 
 impl std::fmt::Display for Plugin {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let name = self.inner.name();
-        f.write_str(name.as_str().unwrap_or("BAD_NAME"))
+        f.write_str(&self.name)
     }
 }
 
 impl PartialEq for Plugin {
     fn eq(&self, other: &Self) -> bool {
-        self.inner.uri() == other.inner.uri()
+        self.uri == other.uri
     }
 }
 
@@ -229,23 +822,104 @@ impl<'a> Debug for PortsDebug<'a> {
     }
 }
 
+/// The lilv instance of an `Instance`. lilv uses different types for active
+/// and inactive instances.
+enum InstanceState {
+    Active(lilv::instance::ActiveInstance),
+    Inactive(lilv::instance::Instance),
+}
+
+impl InstanceState {
+    fn instance(&self) -> &lilv::instance::Instance {
+        match self {
+            InstanceState::Active(instance) => instance.instance(),
+            InstanceState::Inactive(instance) => instance,
+        }
+    }
+
+    fn instance_mut(&mut self) -> &mut lilv::instance::Instance {
+        match self {
+            InstanceState::Active(instance) => instance.instance_mut(),
+            InstanceState::Inactive(instance) => instance,
+        }
+    }
+}
+
 /// An instance of a plugin that can process inputs and outputs.
 pub struct Instance {
-    inner: lilv::instance::ActiveInstance,
+    // Only `None` while `activate` and `deactivate` move the instance between
+    // states.
+    inner: Option<InstanceState>,
     min_block_size: usize,
     max_block_size: usize,
+    // The block length bounds the plugin has been told about.
+    plugin_min_block_size: usize,
+    plugin_max_block_size: usize,
+    // Set if `buf-size:fixedBlockLength` was provided to the plugin.
+    fixed_block_length: bool,
     control_inputs: Controls,
     control_outputs: Controls,
     audio_inputs: Vec<PortIndex>,
     audio_outputs: Vec<PortIndex>,
     atom_sequence_inputs: Vec<PortIndex>,
     atom_sequence_outputs: Vec<PortIndex>,
+    atom_sequence_output_sinks: Vec<LV2AtomSequence>,
+    // The first atom sequence input with the transport position prepended. See
+    // `run_with_transport`.
+    transport_sequence: LV2AtomSequence,
     cv_inputs: Vec<PortIndex>,
+    // The minimum and maximum values of the CV inputs and the buffers that
+    // clamped CV inputs are written to.
+    cv_input_bounds: Vec<(f32, f32)>,
+    cv_input_buffers: Vec<Vec<f32>>,
+    // Set for the CV inputs that had values outside of their bounds. Only
+    // checked in debug builds.
+    cv_inputs_out_of_range: Vec<bool>,
     cv_outputs: Vec<PortIndex>,
+    // The buffers that `run_padded` pads short blocks into. The audio ports
+    // come first, followed by the CV ports.
+    padded_inputs: Vec<Vec<f32>>,
+    padded_outputs: Vec<Vec<f32>>,
+    // The outputs of the current `run_padded` and the number of samples that
+    // are copied into them.
+    padded_output_ptrs: Vec<(*mut f32, usize)>,
+    port_symbols: Vec<String>,
+    // The atom sequences that the atom outputs are connected to in the current
+    // `run`.
+    atom_output_ptrs: Vec<*const LV2AtomSequence>,
+    state_changed_urids: Option<state::StateChangedUrids>,
+    state_changed: bool,
+    in_place_broken: bool,
+    power_of_2_block_length: bool,
+    nominal_block_length: Option<usize>,
+    // The address ranges of the inputs and outputs of the current `run`. These
+    // are only filled if `in_place_broken` is set.
+    input_ranges: Vec<std::ops::Range<usize>>,
+    output_ranges: Vec<std::ops::Range<usize>>,
     worker_interface: Option<lv2_sys::LV2_Worker_Interface>,
+    options_interface: Option<lv2_sys::LV2_Options_Interface>,
+    state_interface: Option<lv2_sys::LV2_State_Interface>,
+    latency_port: Option<PortIndex>,
+    // The control input that is designated with `lv2:enabled`.
+    enabled_port: Option<PortIndex>,
+    seed_port: Option<PortIndex>,
+    // The audio outputs that are connected during `run`. Used to measure
+    // `last_output_peaks` once the plugin has run.
+    audio_output_ptrs: Vec<*const f32>,
+    // The port indices and buffers of the audio and CV ports for the current
+    // `run`.
+    buffer_port_ptrs: Vec<(usize, *mut f32)>,
+    last_output_peaks: Vec<f32>,
     worker_to_instance_receiver: worker::WorkerMessageReceiver,
+    // Holds each worker response while it is delivered to the plugin.
+    worker_response_buffer: Vec<u8>,
+    auto_handle_worker_responses: bool,
     _worker_schedule: Box<lv2_sys::LV2_Worker_Schedule>,
-    _instance_to_worker_sender: Box<worker::WorkerMessageSender>,
+    _work_scheduler: Box<worker::WorkScheduler>,
+    // The worker of this instance. It is also run by the worker manager of
+    // the features.
+    worker: Option<Arc<Mutex<worker::Worker>>>,
+    options: options::Options,
     is_alive: Arc<Mutex<bool>>,
     _features: Arc<Features>,
 }
@@ -256,11 +930,17 @@ unsafe impl Send for Instance {}
 impl Instance {
     /// Run the plugin for a given number of samples.
     ///
+    /// `ports` may have fewer atom sequence outputs than the plugin. The
+    /// remaining atom sequence outputs are connected to internal sequences and
+    /// their events are discarded.
+    ///
     /// # Safety
     /// Running plugin code is unsafe.
     ///
     /// # Errors
-    /// Returns an error if the plugin could not be run.
+    /// Returns an error if the plugin could not be run or if the instance is
+    /// deactivated. Errors for the block length and the number of connections
+    /// can be checked ahead of time with `validate_ports`.
     pub unsafe fn run<
         'a,
         AudioInputs,
@@ -290,80 +970,404 @@ impl Instance {
         CVInputs: ExactSizeIterator + Iterator<Item = &'a [f32]>,
         CVOutputs: ExactSizeIterator + Iterator<Item = &'a mut [f32]>,
     {
-        if samples < self.min_block_size {
-            return Err(RunError::SampleCountTooSmall {
-                min_supported: self.min_block_size,
-                actual: samples,
-            });
-        }
-        if samples > self.max_block_size {
-            return Err(RunError::SampleCountTooLarge {
-                max_supported: self.max_block_size,
-                actual: samples,
-            });
-        }
-        if ports.audio_inputs.len() != self.audio_inputs.len() {
-            return Err(RunError::AudioInputsSizeMismatch {
-                expected: self.audio_inputs.len(),
-                actual: ports.audio_inputs.len(),
-            });
-        }
-        for (data, index) in ports.audio_inputs.zip(self.audio_inputs.iter()) {
-            if data.len() < samples {
-                return Err(RunError::AudioInputSampleCountTooSmall {
-                    expected: samples,
-                    actual: data.len(),
-                });
-            }
-            self.inner
-                .instance_mut()
-                .connect_port(index.0, data.as_ptr());
-        }
-        if ports.audio_outputs.len() != self.audio_outputs.len() {
-            return Err(RunError::AudioOutputsSizeMismatch {
-                expected: self.audio_outputs.len(),
-                actual: ports.audio_outputs.len(),
-            });
+        self.run_impl(samples, ports, None, None)
+    }
+
+    /// Run the plugin like `run` and send `transport` to the plugin as a
+    /// `time:Position` object at the start of the block. The position is
+    /// prepended to the events of the first atom sequence input, which is
+    /// copied into a sequence owned by the instance. The transport is ignored
+    /// if the plugin does not have any atom sequence inputs.
+    ///
+    /// # Safety
+    /// Running plugin code is unsafe.
+    ///
+    /// # Errors
+    /// Returns the same errors as `run`. Returns
+    /// `RunError::TransportEventsFailed` if the events of the first atom
+    /// sequence input and the position do not fit into the internal sequence.
+    pub unsafe fn run_with_transport<
+        'a,
+        AudioInputs,
+        AudioOutputs,
+        AtomSequenceInputs,
+        AtomSequenceOutputs,
+        CVInputs,
+        CVOutputs,
+    >(
+        &mut self,
+        samples: usize,
+        ports: PortConnections<
+            'a,
+            AudioInputs,
+            AudioOutputs,
+            AtomSequenceInputs,
+            AtomSequenceOutputs,
+            CVInputs,
+            CVOutputs,
+        >,
+        transport: &TransportState,
+    ) -> Result<(), RunError>
+    where
+        AudioInputs: ExactSizeIterator + Iterator<Item = &'a [f32]>,
+        AudioOutputs: ExactSizeIterator + Iterator<Item = &'a mut [f32]>,
+        AtomSequenceInputs: ExactSizeIterator + Iterator<Item = &'a LV2AtomSequence>,
+        AtomSequenceOutputs: ExactSizeIterator + Iterator<Item = &'a mut LV2AtomSequence>,
+        CVInputs: ExactSizeIterator + Iterator<Item = &'a [f32]>,
+        CVOutputs: ExactSizeIterator + Iterator<Item = &'a mut [f32]>,
+    {
+        self.run_impl(samples, ports, Some(transport), None)
+    }
+
+    unsafe fn run_impl<
+        'a,
+        AudioInputs,
+        AudioOutputs,
+        AtomSequenceInputs,
+        AtomSequenceOutputs,
+        CVInputs,
+        CVOutputs,
+    >(
+        &mut self,
+        samples: usize,
+        ports: PortConnections<
+            'a,
+            AudioInputs,
+            AudioOutputs,
+            AtomSequenceInputs,
+            AtomSequenceOutputs,
+            CVInputs,
+            CVOutputs,
+        >,
+        transport: Option<&TransportState>,
+        inline_worker: Option<&WorkerManager>,
+    ) -> Result<(), RunError>
+    where
+        AudioInputs: ExactSizeIterator + Iterator<Item = &'a [f32]>,
+        AudioOutputs: ExactSizeIterator + Iterator<Item = &'a mut [f32]>,
+        AtomSequenceInputs: ExactSizeIterator + Iterator<Item = &'a LV2AtomSequence>,
+        AtomSequenceOutputs: ExactSizeIterator + Iterator<Item = &'a mut LV2AtomSequence>,
+        CVInputs: ExactSizeIterator + Iterator<Item = &'a [f32]>,
+        CVOutputs: ExactSizeIterator + Iterator<Item = &'a mut [f32]>,
+    {
+        self.run_plugin(samples, ports, transport, inline_worker)?;
+        self.record_run(samples);
+        Ok(())
+    }
+
+    /// Update the output peaks and the state changed flag after the plugin was
+    /// run by `run_plugin` for `samples` samples.
+    ///
+    /// # Safety
+    /// The outputs of the last `run_plugin` must still be borrowed.
+    unsafe fn record_run(&mut self, samples: usize) {
+        for (peak, &ptr) in self
+            .last_output_peaks
+            .iter_mut()
+            .zip(self.audio_output_ptrs.iter())
+        {
+            // The outputs were checked to have at least `samples` samples and
+            // are borrowed for the duration of `run`.
+            let data = std::slice::from_raw_parts(ptr, samples);
+            *peak = data.iter().fold(0.0, |peak, x| peak.max(x.abs()));
         }
-        for (data, index) in ports.audio_outputs.zip(self.audio_outputs.iter()) {
-            if data.len() < samples {
-                return Err(RunError::AudioOutputSampleCountTooSmall {
-                    expected: samples,
-                    actual: data.len(),
-                });
+
+        if let Some(urids) = self.state_changed_urids.as_ref() {
+            // The plugin wrote the outputs so they are validated before they
+            // are read. Malformed outputs are not scanned.
+            if self
+                .atom_output_ptrs
+                .iter()
+                .filter_map(|s| (**s).try_iter().ok())
+                .any(|mut events| events.any(|e| urids.is_state_changed(&e)))
+            {
+                self.state_changed = true;
             }
-            self.inner
-                .instance_mut()
-                .connect_port_mut(index.0, data.as_mut_ptr());
         }
-        if ports.atom_sequence_inputs.len() != self.atom_sequence_inputs.len() {
-            return Err(RunError::AtomSequenceInputsSizeMismatch {
-                expected: self.atom_sequence_inputs.len(),
-                actual: ports.atom_sequence_inputs.len(),
-            });
+    }
+
+    /// Connect the ports, run the plugin and handle its worker. Unlike
+    /// `run_impl`, this does not update the output peaks or the state changed
+    /// flag, so it can be used for runs that the user did not request.
+    unsafe fn run_plugin<
+        'a,
+        AudioInputs,
+        AudioOutputs,
+        AtomSequenceInputs,
+        AtomSequenceOutputs,
+        CVInputs,
+        CVOutputs,
+    >(
+        &mut self,
+        samples: usize,
+        ports: PortConnections<
+            'a,
+            AudioInputs,
+            AudioOutputs,
+            AtomSequenceInputs,
+            AtomSequenceOutputs,
+            CVInputs,
+            CVOutputs,
+        >,
+        transport: Option<&TransportState>,
+        inline_worker: Option<&WorkerManager>,
+    ) -> Result<(), RunError>
+    where
+        AudioInputs: ExactSizeIterator + Iterator<Item = &'a [f32]>,
+        AudioOutputs: ExactSizeIterator + Iterator<Item = &'a mut [f32]>,
+        AtomSequenceInputs: ExactSizeIterator + Iterator<Item = &'a LV2AtomSequence>,
+        AtomSequenceOutputs: ExactSizeIterator + Iterator<Item = &'a mut LV2AtomSequence>,
+        CVInputs: ExactSizeIterator + Iterator<Item = &'a [f32]>,
+        CVOutputs: ExactSizeIterator + Iterator<Item = &'a mut [f32]>,
+    {
+        if !self.is_active() {
+            return Err(RunError::InstanceNotActive);
         }
-        for (data, index) in ports
+        self.validate_ports(samples, &ports)?;
+        self.input_ranges.clear();
+        self.output_ranges.clear();
+        self.audio_output_ptrs.clear();
+        // The audio and CV ports are only connected once all their buffers are
+        // checked.
+        self.buffer_port_ptrs.clear();
+        for (data, index) in ports.audio_inputs.zip(self.audio_inputs.iter()) {
+            if data.len() < samples {
+                return Err(RunError::AudioInputSampleCountTooSmall {
+                    expected: samples,
+                    actual: data.len(),
+                });
+            }
+            if self.in_place_broken {
+                self.input_ranges.push(buffer_range(data, samples));
+            }
+            self.buffer_port_ptrs
+                .push((index.0, data.as_ptr() as *mut f32));
+        }
+        for (data, index) in ports.audio_outputs.zip(self.audio_outputs.iter()) {
+            if data.len() < samples {
+                return Err(RunError::AudioOutputSampleCountTooSmall {
+                    expected: samples,
+                    actual: data.len(),
+                });
+            }
+            if self.in_place_broken {
+                self.output_ranges.push(buffer_range(data, samples));
+            }
+            self.audio_output_ptrs.push(data.as_ptr());
+            self.buffer_port_ptrs.push((index.0, data.as_mut_ptr()));
+        }
+        let cv_clamping = ports.cv_clamping;
+        for (((data, index), (buffer, &(min, max))), out_of_range) in ports
+            .cv_inputs
+            .zip(self.cv_inputs.iter())
+            .zip(
+                self.cv_input_buffers
+                    .iter_mut()
+                    .zip(self.cv_input_bounds.iter()),
+            )
+            .zip(self.cv_inputs_out_of_range.iter_mut())
+        {
+            if data.len() < samples {
+                return Err(RunError::CVInputSampleCountTooSmall {
+                    expected: samples,
+                    actual: data.len(),
+                });
+            }
+            if cfg!(debug_assertions) && data[..samples].iter().any(|x| !(min..=max).contains(x)) {
+                *out_of_range = true;
+            }
+            let data: &[f32] = if cv_clamping {
+                for (dst, src) in buffer[..samples].iter_mut().zip(data.iter()) {
+                    *dst = src.clamp(min, max);
+                }
+                &buffer[..samples]
+            } else {
+                data
+            };
+            if self.in_place_broken {
+                self.input_ranges.push(buffer_range(data, samples));
+            }
+            self.buffer_port_ptrs
+                .push((index.0, data.as_ptr() as *mut f32));
+        }
+        for (data, index) in ports.cv_outputs.zip(self.cv_outputs.iter()) {
+            if data.len() < samples {
+                return Err(RunError::CVOutputSampleCountTooSmall {
+                    expected: samples,
+                    actual: data.len(),
+                });
+            }
+            if self.in_place_broken {
+                self.output_ranges.push(buffer_range(data, samples));
+            }
+            self.buffer_port_ptrs.push((index.0, data.as_mut_ptr()));
+        }
+        let overlaps = |a: &std::ops::Range<usize>, b: &std::ops::Range<usize>| {
+            a.start < b.end && b.start < a.end
+        };
+        if self
+            .input_ranges
+            .iter()
+            .any(|i| self.output_ranges.iter().any(|o| overlaps(i, o)))
+        {
+            return Err(RunError::InPlaceNotSupported);
+        }
+        let inner = match self.inner.as_mut() {
+            Some(InstanceState::Active(inner)) => inner,
+            _ => return Err(RunError::InstanceNotActive),
+        };
+        for &(index, ptr) in self.buffer_port_ptrs.iter() {
+            inner.instance_mut().connect_port_mut(index, ptr);
+        }
+        for (i, (data, index)) in ports
             .atom_sequence_inputs
             .zip(self.atom_sequence_inputs.iter())
+            .enumerate()
         {
-            self.inner
-                .instance_mut()
-                .connect_port(index.0, data.as_ptr());
-        }
-        if ports.atom_sequence_outputs.len() != self.atom_sequence_outputs.len() {
-            return Err(RunError::AtomSequenceOutputsSizeMismatch {
-                expected: self.atom_sequence_outputs.len(),
-                actual: ports.atom_sequence_outputs.len(),
-            });
+            let data = match transport {
+                Some(transport) if i == 0 => {
+                    let sequence = &mut self.transport_sequence;
+                    sequence.clear();
+                    transport
+                        .push_position(&self._features, sequence, 0)
+                        .and_then(|_| sequence.extend_from(data))
+                        .map_err(|error| RunError::TransportEventsFailed { error })?;
+                    sequence
+                }
+                _ => data,
+            };
+            inner.instance_mut().connect_port(index.0, data.as_ptr());
         }
+        let connected_atom_sequence_outputs = ports.atom_sequence_outputs.len();
+        self.atom_output_ptrs.clear();
         for (data, index) in ports
             .atom_sequence_outputs
             .zip(self.atom_sequence_outputs.iter())
         {
             data.clear_as_chunk();
-            self.inner
+            inner
                 .instance_mut()
                 .connect_port_mut(index.0, data.as_mut_ptr());
+            self.atom_output_ptrs.push(data);
+        }
+        for (sink, index) in self
+            .atom_sequence_output_sinks
+            .iter_mut()
+            .zip(self.atom_sequence_outputs.iter())
+            .skip(connected_atom_sequence_outputs)
+        {
+            sink.clear_as_chunk();
+            inner
+                .instance_mut()
+                .connect_port_mut(index.0, sink.as_mut_ptr());
+            self.atom_output_ptrs.push(sink);
+        }
+        for (idx, &value) in ports.control_inputs.iter().enumerate() {
+            self.control_inputs.set_at(idx, value);
+        }
+        inner.run(samples);
+
+        for (value, port) in ports
+            .control_outputs
+            .iter_mut()
+            .zip(self.control_outputs.iter_ports())
+        {
+            *value = port.value;
+        }
+        if let (Some(manager), Some(_)) = (inline_worker, self.worker_interface.as_ref()) {
+            manager.run_workers();
+        }
+        if self.auto_handle_worker_responses {
+            self.handle_worker_responses();
+        }
+        let handle = self.lilv_instance().handle();
+        if let Some(interface) = self.worker_interface.as_mut() {
+            worker::end_run(interface, handle);
+        }
+
+        Ok(())
+    }
+
+    /// Check the block length and the number of connections of each type of
+    /// port that `run` would check, without running any plugin code.
+    ///
+    /// This does not check the individual buffers. Reading them would consume
+    /// the iterators of `ports`, so their lengths and whether they overlap are
+    /// only checked by `run`. A `run` that follows a successful
+    /// `validate_ports` may still fail with, for example,
+    /// `RunError::AudioOutputSampleCountTooSmall` or
+    /// `RunError::InPlaceNotSupported`.
+    ///
+    /// # Errors
+    /// Returns the error that `run` would return for the block length or for
+    /// the number of connections.
+    pub fn validate_ports<
+        'a,
+        AudioInputs,
+        AudioOutputs,
+        AtomSequenceInputs,
+        AtomSequenceOutputs,
+        CVInputs,
+        CVOutputs,
+    >(
+        &self,
+        samples: usize,
+        ports: &PortConnections<
+            'a,
+            AudioInputs,
+            AudioOutputs,
+            AtomSequenceInputs,
+            AtomSequenceOutputs,
+            CVInputs,
+            CVOutputs,
+        >,
+    ) -> Result<(), RunError>
+    where
+        AudioInputs: ExactSizeIterator + Iterator<Item = &'a [f32]>,
+        AudioOutputs: ExactSizeIterator + Iterator<Item = &'a mut [f32]>,
+        AtomSequenceInputs: ExactSizeIterator + Iterator<Item = &'a LV2AtomSequence>,
+        AtomSequenceOutputs: ExactSizeIterator + Iterator<Item = &'a mut LV2AtomSequence>,
+        CVInputs: ExactSizeIterator + Iterator<Item = &'a [f32]>,
+        CVOutputs: ExactSizeIterator + Iterator<Item = &'a mut [f32]>,
+    {
+        if samples < self.min_block_size {
+            return Err(RunError::SampleCountTooSmall {
+                min_supported: self.min_block_size,
+                actual: samples,
+            });
+        }
+        if samples > self.max_block_size {
+            return Err(RunError::SampleCountTooLarge {
+                max_supported: self.max_block_size,
+                actual: samples,
+            });
+        }
+        if self.power_of_2_block_length && !samples.is_power_of_two() {
+            return Err(RunError::SampleCountNotPowerOf2 { actual: samples });
+        }
+        if ports.audio_inputs.len() != self.audio_inputs.len() {
+            return Err(RunError::AudioInputsSizeMismatch {
+                expected: self.audio_inputs.len(),
+                actual: ports.audio_inputs.len(),
+            });
+        }
+        if ports.audio_outputs.len() != self.audio_outputs.len() {
+            return Err(RunError::AudioOutputsSizeMismatch {
+                expected: self.audio_outputs.len(),
+                actual: ports.audio_outputs.len(),
+            });
+        }
+        if ports.atom_sequence_inputs.len() != self.atom_sequence_inputs.len() {
+            return Err(RunError::AtomSequenceInputsSizeMismatch {
+                expected: self.atom_sequence_inputs.len(),
+                actual: ports.atom_sequence_inputs.len(),
+            });
+        }
+        if ports.atom_sequence_outputs.len() > self.atom_sequence_outputs.len() {
+            return Err(RunError::AtomSequenceOutputsSizeMismatch {
+                expected: self.atom_sequence_outputs.len(),
+                actual: ports.atom_sequence_outputs.len(),
+            });
         }
         if ports.cv_inputs.len() != self.cv_inputs.len() {
             return Err(RunError::CVInputsSizeMismatch {
@@ -371,63 +1375,911 @@ impl Instance {
                 actual: ports.cv_inputs.len(),
             });
         }
-        for (data, index) in ports.cv_inputs.zip(self.cv_inputs.iter()) {
-            self.inner
-                .instance_mut()
-                .connect_port(index.0, data.as_ptr());
-        }
         if ports.cv_outputs.len() != self.cv_outputs.len() {
             return Err(RunError::CVOutputsSizeMismatch {
                 expected: self.cv_outputs.len(),
                 actual: ports.cv_outputs.len(),
             });
         }
-        for (data, index) in ports.cv_outputs.zip(self.cv_outputs.iter()) {
-            self.inner
-                .instance_mut()
-                .connect_port_mut(index.0, data.as_mut_ptr());
+        // Control ports do not have to be connected.
+        let control_inputs = ports.control_inputs.len();
+        if control_inputs != 0 && control_inputs != self.control_inputs.len() {
+            return Err(RunError::ControlInputsSizeMismatch {
+                expected: self.control_inputs.len(),
+                actual: control_inputs,
+            });
         }
-        self.inner.run(samples);
-
-        if let Some(interface) = self.worker_interface.as_mut() {
-            worker::handle_work_responses(
-                interface,
-                &mut self.worker_to_instance_receiver,
-                self.inner.instance().handle(),
-            );
-            worker::end_run(interface, self.inner.instance().handle());
+        let control_outputs = ports.control_outputs.len();
+        if control_outputs != 0 && control_outputs != self.control_outputs.len() {
+            return Err(RunError::ControlOutputsSizeMismatch {
+                expected: self.control_outputs.len(),
+                actual: control_outputs,
+            });
         }
-
         Ok(())
     }
 
-    /// Get the underlying `lilv::instance::ActiveInstance`.
-    pub fn raw(&self) -> &lilv::instance::ActiveInstance {
-        &self.inner
-    }
-
-    /// Get the underlying `lilv::instance::ActiveInstance`.
-    pub fn raw_mut(&mut self) -> &mut lilv::instance::ActiveInstance {
-        &mut self.inner
+    /// Run the plugin like `run`, then do the work that it scheduled with the
+    /// workers of `manager` and deliver the responses to the plugin before
+    /// returning. This is for single threaded hosts that can not run workers on
+    /// a separate thread.
+    ///
+    /// The work is done on the calling thread, so this is not real-time safe.
+    /// It is appropriate for offline rendering and other non real-time use.
+    /// `manager` should be the `WorkerManager` of the `Features` that the
+    /// instance was created with. All workers of `manager` are run, including
+    /// the workers of other instances.
+    ///
+    /// # Safety
+    /// Running plugin code is unsafe.
+    ///
+    /// # Errors
+    /// Returns an error if the plugin could not be run.
+    pub unsafe fn run_with_inline_worker<
+        'a,
+        AudioInputs,
+        AudioOutputs,
+        AtomSequenceInputs,
+        AtomSequenceOutputs,
+        CVInputs,
+        CVOutputs,
+    >(
+        &mut self,
+        samples: usize,
+        ports: PortConnections<
+            'a,
+            AudioInputs,
+            AudioOutputs,
+            AtomSequenceInputs,
+            AtomSequenceOutputs,
+            CVInputs,
+            CVOutputs,
+        >,
+        manager: &WorkerManager,
+    ) -> Result<(), RunError>
+    where
+        AudioInputs: ExactSizeIterator + Iterator<Item = &'a [f32]>,
+        AudioOutputs: ExactSizeIterator + Iterator<Item = &'a mut [f32]>,
+        AtomSequenceInputs: ExactSizeIterator + Iterator<Item = &'a LV2AtomSequence>,
+        AtomSequenceOutputs: ExactSizeIterator + Iterator<Item = &'a mut LV2AtomSequence>,
+        CVInputs: ExactSizeIterator + Iterator<Item = &'a [f32]>,
+        CVOutputs: ExactSizeIterator + Iterator<Item = &'a mut [f32]>,
+    {
+        self.run_impl(samples, ports, None, Some(manager))
     }
 
-    /// Get the value of the control port at `index`. If `index` is not a valid
-    /// control port index, then `None` is returned.
-    pub fn control_output(&self, index: PortIndex) -> Option<f32> {
-        self.control_outputs.get(index)
+    /// Run the plugin like `run`, but allow `samples` to be smaller than the
+    /// minimum block size. This is useful for the final block when rendering
+    /// material whose length is not a multiple of the block size.
+    ///
+    /// If `samples` is smaller than the minimum block size, the audio and CV
+    /// inputs are copied into buffers that are padded with silence up to the
+    /// minimum block size and the plugin is run for the minimum block size.
+    /// Only the first `samples` samples of the results are copied into the
+    /// audio and CV outputs. The padded buffers are allocated with the instance,
+    /// so this does not allocate.
+    ///
+    /// Returns the number of valid samples in the outputs. This is always
+    /// `samples`; any samples after it in the outputs should be ignored.
+    ///
+    /// # Safety
+    /// Running plugin code is unsafe.
+    ///
+    /// # Errors
+    /// Returns an error if the plugin could not be run.
+    pub unsafe fn run_padded<
+        'a,
+        AudioInputs,
+        AudioOutputs,
+        AtomSequenceInputs,
+        AtomSequenceOutputs,
+        CVInputs,
+        CVOutputs,
+    >(
+        &mut self,
+        samples: usize,
+        ports: PortConnections<
+            'a,
+            AudioInputs,
+            AudioOutputs,
+            AtomSequenceInputs,
+            AtomSequenceOutputs,
+            CVInputs,
+            CVOutputs,
+        >,
+    ) -> Result<usize, RunError>
+    where
+        AudioInputs: ExactSizeIterator + Iterator<Item = &'a [f32]>,
+        AudioOutputs: ExactSizeIterator + Iterator<Item = &'a mut [f32]>,
+        AtomSequenceInputs: ExactSizeIterator + Iterator<Item = &'a LV2AtomSequence>,
+        AtomSequenceOutputs: ExactSizeIterator + Iterator<Item = &'a mut LV2AtomSequence>,
+        CVInputs: ExactSizeIterator + Iterator<Item = &'a [f32]>,
+        CVOutputs: ExactSizeIterator + Iterator<Item = &'a mut [f32]>,
+    {
+        if samples >= self.min_block_size {
+            self.run(samples, ports)?;
+            return Ok(samples);
+        }
+        // The buffers are taken so that they can be connected while `self` is
+        // borrowed by `run`. Taking them does not allocate.
+        let mut padded_inputs = std::mem::take(&mut self.padded_inputs);
+        let mut padded_outputs = std::mem::take(&mut self.padded_outputs);
+        let result = self.run_padded_impl(samples, ports, &mut padded_inputs, &mut padded_outputs);
+        self.padded_inputs = padded_inputs;
+        self.padded_outputs = padded_outputs;
+        result.map(|()| samples)
     }
 
-    /// Get the value of the control port at `index`. If `index` is not a valid
+    /// Run the plugin for the minimum block size with the first `samples`
+    /// samples of `ports` padded into `padded_inputs` and `padded_outputs`.
+    unsafe fn run_padded_impl<
+        'a,
+        AudioInputs,
+        AudioOutputs,
+        AtomSequenceInputs,
+        AtomSequenceOutputs,
+        CVInputs,
+        CVOutputs,
+    >(
+        &mut self,
+        samples: usize,
+        ports: PortConnections<
+            'a,
+            AudioInputs,
+            AudioOutputs,
+            AtomSequenceInputs,
+            AtomSequenceOutputs,
+            CVInputs,
+            CVOutputs,
+        >,
+        padded_inputs: &mut [Vec<f32>],
+        padded_outputs: &mut [Vec<f32>],
+    ) -> Result<(), RunError>
+    where
+        AudioInputs: ExactSizeIterator + Iterator<Item = &'a [f32]>,
+        AudioOutputs: ExactSizeIterator + Iterator<Item = &'a mut [f32]>,
+        AtomSequenceInputs: ExactSizeIterator + Iterator<Item = &'a LV2AtomSequence>,
+        AtomSequenceOutputs: ExactSizeIterator + Iterator<Item = &'a mut LV2AtomSequence>,
+        CVInputs: ExactSizeIterator + Iterator<Item = &'a [f32]>,
+        CVOutputs: ExactSizeIterator + Iterator<Item = &'a mut [f32]>,
+    {
+        let padded_samples = self.min_block_size;
+        // The padded buffers are zipped with the ports, so their counts are
+        // checked first.
+        self.validate_ports(padded_samples, &ports)?;
+        let (audio_inputs, cv_inputs) = padded_inputs.split_at_mut(self.audio_inputs.len());
+        for (data, buffer) in ports.audio_inputs.zip(audio_inputs.iter_mut()) {
+            let data = data
+                .get(..samples)
+                .ok_or(RunError::AudioInputSampleCountTooSmall {
+                    expected: samples,
+                    actual: data.len(),
+                })?;
+            buffer[..samples].copy_from_slice(data);
+            buffer[samples..padded_samples].fill(0.0);
+        }
+        for (data, buffer) in ports.cv_inputs.zip(cv_inputs.iter_mut()) {
+            let data = data
+                .get(..samples)
+                .ok_or(RunError::CVInputSampleCountTooSmall {
+                    expected: samples,
+                    actual: data.len(),
+                })?;
+            buffer[..samples].copy_from_slice(data);
+            buffer[samples..padded_samples].fill(0.0);
+        }
+        self.padded_output_ptrs.clear();
+        for data in ports.audio_outputs {
+            if data.len() < samples {
+                return Err(RunError::AudioOutputSampleCountTooSmall {
+                    expected: samples,
+                    actual: data.len(),
+                });
+            }
+            self.padded_output_ptrs.push((data.as_mut_ptr(), samples));
+        }
+        for data in ports.cv_outputs {
+            if data.len() < samples {
+                return Err(RunError::CVOutputSampleCountTooSmall {
+                    expected: samples,
+                    actual: data.len(),
+                });
+            }
+            self.padded_output_ptrs.push((data.as_mut_ptr(), samples));
+        }
+        let (audio_outputs, cv_outputs) = padded_outputs.split_at_mut(self.audio_outputs.len());
+        self.run(
+            padded_samples,
+            PortConnections {
+                audio_inputs: audio_inputs.iter().map(|b| &b[..padded_samples]),
+                audio_outputs: audio_outputs.iter_mut().map(|b| &mut b[..padded_samples]),
+                // Shorten the lifetimes to match the padded buffers.
+                atom_sequence_inputs: ports
+                    .atom_sequence_inputs
+                    .map(|s| -> &LV2AtomSequence { s }),
+                atom_sequence_outputs: ports
+                    .atom_sequence_outputs
+                    .map(|s| -> &mut LV2AtomSequence { s }),
+                cv_inputs: cv_inputs.iter().map(|b| &b[..padded_samples]),
+                cv_outputs: cv_outputs.iter_mut().map(|b| &mut b[..padded_samples]),
+                control_inputs: ports.control_inputs,
+                control_outputs: ports.control_outputs,
+                cv_clamping: ports.cv_clamping,
+            },
+        )?;
+        for (&(ptr, n), src) in self.padded_output_ptrs.iter().zip(padded_outputs.iter()) {
+            // The outputs are borrowed for the duration of `run_padded`.
+            std::slice::from_raw_parts_mut(ptr, n).copy_from_slice(&src[..n]);
+        }
+        Ok(())
+    }
+
+    /// Render `total_samples` samples of audio from the plugin while playing
+    /// `events`. Each event is a tuple of the absolute frame and the MIDI data.
+    /// The events are sent to the first atom sequence input, the plugin is run
+    /// block by block, and the audio of each output channel is returned.
+    ///
+    /// Audio and CV inputs are silent and other atom sequence inputs are empty.
+    /// The worker of the instance is run after each block so that the results
+    /// of any work that the plugin schedules are applied before the next
+    /// block. The workers of other instances are not run.
+    ///
+    /// # Safety
+    /// Running plugin code is unsafe.
+    ///
+    /// # Errors
+    /// Returns an error if there are events but the plugin has no atom
+    /// sequence inputs, if the events of a block do not fit in the atom
+    /// sequence input, or if the plugin could not be run.
+    pub unsafe fn render<M: AsRef<[u8]>>(
+        &mut self,
+        events: &[(u64, M)],
+        total_samples: usize,
+    ) -> Result<Vec<Vec<f32>>, RunError> {
+        // The capacity of the sequence that holds the MIDI events for each
+        // block. Events that do not fit are delayed to the next block.
+        const MIDI_SEQUENCE_CAPACITY: usize = 65536;
+        if self.atom_sequence_inputs.is_empty() && !events.is_empty() {
+            return Err(RunError::AtomSequenceInputsSizeMismatch {
+                expected: 1,
+                actual: 0,
+            });
+        }
+        let features = self._features.clone();
+        let block_size = self.max_block_size;
+        let mut scheduler = MidiScheduler::new(events.iter().map(|(f, data)| (*f, data.as_ref())));
+        let mut buffers = NonAudioBuffers::new(self);
+        if let Some(sequence) = buffers.atom_sequence_inputs.first_mut() {
+            *sequence = LV2AtomSequence::new(&features, MIDI_SEQUENCE_CAPACITY);
+        }
+        let inputs = vec![vec![0.0; block_size]; self.audio_inputs.len()];
+        let mut outputs = vec![vec![0.0; block_size]; self.audio_outputs.len()];
+        let mut rendered = vec![Vec::with_capacity(total_samples); self.audio_outputs.len()];
+        let mut frame = 0;
+        while frame < total_samples {
+            let samples = block_size.min(total_samples - frame);
+            if let Some(sequence) = buffers.atom_sequence_inputs.first_mut() {
+                scheduler
+                    .fill_block(sequence, features.midi_urid(), samples)
+                    .map_err(|error| RunError::MidiEventsFailed { error })?;
+            }
+            buffers.run_padded(
+                self,
+                samples,
+                inputs.iter().map(|b| b.as_slice()),
+                outputs.iter_mut().map(|b| b.as_mut_slice()),
+            )?;
+            for (dst, src) in rendered.iter_mut().zip(outputs.iter()) {
+                dst.extend_from_slice(&src[..samples]);
+            }
+            if let Some(worker) = self.worker.as_ref() {
+                worker.lock().unwrap().do_work();
+            }
+            frame += samples;
+        }
+        Ok(rendered)
+    }
+
+    /// Reset the instance to the state it had right after it was
+    /// instantiated. If the plugin has an atom sequence input, it is first run
+    /// for a single block with All Sound Off and All Notes Off messages on
+    /// every MIDI channel to release hanging notes. The plugin is then
+    /// deactivated and activated again, all control inputs are set to their
+    /// default values, and pending worker responses are discarded. The audio,
+    /// CV and atom sequence input ports are disconnected and are connected
+    /// again by the next `run`.
+    ///
+    /// LV2 plugins must reset their internal state, such as delay lines and
+    /// envelopes, when they are activated. Resetting these can only be
+    /// guaranteed as far as the plugin follows that rule. Data that the plugin
+    /// loaded through its worker or that was restored through the state
+    /// extension, like sample files, is not guaranteed to be reset. Plugins
+    /// that do not implement `deactivate` can not be deactivated, so they are
+    /// not activated again either.
+    ///
+    /// This allocates and runs the plugin, so it is not realtime safe.
+    ///
+    /// # Safety
+    /// Running plugin code is unsafe.
+    pub unsafe fn reset(&mut self) {
+        if self.is_active() {
+            if !self.atom_sequence_inputs.is_empty() {
+                self.release_notes();
+            }
+            self.deactivate();
+            self.activate();
+        }
+        self.worker_to_instance_receiver.clear();
+        self.control_inputs.reset();
+    }
+
+    /// Run the plugin for a single block with All Sound Off and All Notes Off
+    /// messages on every MIDI channel of the first atom sequence input. The
+    /// atom sequence outputs are written to the sinks of the instance.
+    unsafe fn release_notes(&mut self) {
+        // Fits the 32 messages with their event headers.
+        const SEQUENCE_CAPACITY: usize = 1024;
+        let samples = self.min_block_size;
+        let midi_urid = self._features.midi_urid();
+        let mut atom_sequence_inputs: Vec<LV2AtomSequence> = self
+            .atom_sequence_inputs
+            .iter()
+            .map(|_| LV2AtomSequence::new(&self._features, SEQUENCE_CAPACITY))
+            .collect();
+        if let Some(sequence) = atom_sequence_inputs.first_mut() {
+            for channel in 0..16u8 {
+                for controller in [0x78, 0x7B] {
+                    let _ = sequence.push_midi_event::<3>(
+                        0,
+                        midi_urid,
+                        &[0xB0 | channel, controller, 0],
+                    );
+                }
+            }
+        }
+        let audio_inputs = vec![vec![0.0; samples]; self.audio_inputs.len()];
+        let mut audio_outputs = vec![vec![0.0; samples]; self.audio_outputs.len()];
+        let cv_inputs = vec![vec![0.0; samples]; self.cv_inputs.len()];
+        let mut cv_outputs = vec![vec![0.0; samples]; self.cv_outputs.len()];
+        let ports = EmptyPortConnections::new()
+            .with_audio_inputs(audio_inputs.iter().map(|b| b.as_slice()))
+            .with_audio_outputs(audio_outputs.iter_mut().map(|b| b.as_mut_slice()))
+            .with_atom_sequence_inputs(atom_sequence_inputs.iter())
+            .with_cv_inputs(cv_inputs.iter().map(|b| b.as_slice()))
+            .with_cv_outputs(cv_outputs.iter_mut().map(|b| b.as_mut_slice()));
+        // The output is discarded and the plugin is reactivated afterwards, so
+        // errors from running it are not relevant.
+        let _ = self.run_plugin(samples, ports, None, None);
+        // The buffers are freed when this returns.
+        let inner = match self.inner.as_mut() {
+            Some(inner) => inner.instance_mut(),
+            None => return,
+        };
+        for index in self
+            .audio_inputs
+            .iter()
+            .chain(self.audio_outputs.iter())
+            .chain(self.atom_sequence_inputs.iter())
+            .chain(self.cv_inputs.iter())
+            .chain(self.cv_outputs.iter())
+        {
+            inner.connect_port(index.0, std::ptr::null::<u8>());
+        }
+    }
+
+    /// Deactivate the instance. The instance can not be run until it is
+    /// activated again with `activate`. Control values and port connections
+    /// are kept. Does nothing if the instance is already deactivated.
+    ///
+    /// Plugins that do not implement `deactivate` stay active, since lilv
+    /// frees instances that it can not deactivate.
+    ///
+    /// # Safety
+    /// Running plugin code is unsafe.
+    pub unsafe fn deactivate(&mut self) {
+        let has_deactivate = self
+            .lilv_instance()
+            .descriptor()
+            .and_then(|d| d.deactivate)
+            .is_some();
+        if !has_deactivate {
+            return;
+        }
+        self.inner = match self.inner.take() {
+            Some(InstanceState::Active(instance)) => {
+                instance.deactivate().map(InstanceState::Inactive)
+            }
+            inner => inner,
+        };
+    }
+
+    /// Activate an instance that was deactivated with `deactivate`. Plugins
+    /// reset their internal state, such as delay lines and envelopes, when
+    /// they are activated. Does nothing if the instance is already active.
+    ///
+    /// # Safety
+    /// Running plugin code is unsafe.
+    pub unsafe fn activate(&mut self) {
+        self.inner = match self.inner.take() {
+            Some(InstanceState::Inactive(instance)) => {
+                Some(InstanceState::Active(instance.activate()))
+            }
+            inner => inner,
+        };
+    }
+
+    /// Returns `true` if the instance is active and can be run. Instances are
+    /// active when they are created.
+    pub fn is_active(&self) -> bool {
+        matches!(self.inner, Some(InstanceState::Active(_)))
+    }
+
+    /// Get the underlying `lilv::instance::Instance`.
+    pub fn raw(&self) -> &lilv::instance::Instance {
+        self.lilv_instance()
+    }
+
+    /// Get the underlying `lilv::instance::Instance`.
+    pub fn raw_mut(&mut self) -> &mut lilv::instance::Instance {
+        self.inner
+            .as_mut()
+            .map(InstanceState::instance_mut)
+            .expect("instance is always set outside of activate and deactivate")
+    }
+
+    /// The lilv instance, whether it is active or not.
+    fn lilv_instance(&self) -> &lilv::instance::Instance {
+        self.inner
+            .as_ref()
+            .map(InstanceState::instance)
+            .expect("instance is always set outside of activate and deactivate")
+    }
+
+    /// Returns `true` if the plugin implements `options:interface`. Options
+    /// can only be changed on a running instance if the interface is
+    /// implemented. Otherwise, the instance has to be instantiated again for
+    /// new options to take effect.
+    pub fn supports_options_interface(&self) -> bool {
+        self.options_interface.is_some()
+    }
+
+    /// Returns `true` if the plugin may need to be configured through its atom
+    /// inputs before it produces any output. This is the case for plugins that
+    /// have atom sequence inputs and a worker, like samplers that stay silent
+    /// until a sample has been loaded and a note has been played.
+    ///
+    /// This is only a hint. Silence from such a plugin does not mean that it is
+    /// broken, and plugins without this hint may also need configuration.
+    pub fn requires_configuration(&self) -> bool {
+        !self.atom_sequence_inputs.is_empty() && self.worker_interface.is_some()
+    }
+
+    /// Set the block length that will usually be used for `run`. This is
+    /// provided to the plugin as the `buf-size:nominalBlockLength` option
+    /// through `options:interface`. If the plugin does not implement the
+    /// interface, the block length is only recorded. `run` still accepts any
+    /// block length between the minimum and maximum block length of the
+    /// instance.
+    ///
+    /// This is useful when the block length of the host changes, for example
+    /// in a JACK buffer size callback. Build the `Features` with the largest
+    /// block length that may be used as the maximum block length, or change
+    /// the bounds with `set_block_length_bounds`.
+    ///
+    /// # Errors
+    /// Returns an error if `samples` is outside of the block length bounds of
+    /// the instance, if the plugin requires `buf-size:powerOf2BlockLength`
+    /// and `samples` is not a power of 2, or if the plugin rejects the option.
+    pub fn set_block_length(&mut self, samples: usize) -> Result<(), BlockLengthError> {
+        if samples < self.min_block_size {
+            return Err(BlockLengthError::TooSmall {
+                min_supported: self.min_block_size,
+                actual: samples,
+            });
+        }
+        if samples > self.max_block_size {
+            return Err(BlockLengthError::TooLarge {
+                max_supported: self.max_block_size,
+                actual: samples,
+            });
+        }
+        if self.power_of_2_block_length && !samples.is_power_of_two() {
+            return Err(BlockLengthError::NotPowerOf2 { actual: samples });
+        }
+        let key = self._features.urid(
+            CStr::from_bytes_with_nul(b"http://lv2plug.in/ns/ext/buf-size#nominalBlockLength\0")
+                .unwrap(),
+        );
+        // Without the options interface, the block length is only recorded.
+        block_length_options_accepted(self.set_option(key, OptionValue::Int(samples as i32)))?;
+        self.nominal_block_length = Some(samples);
+        Ok(())
+    }
+
+    /// The value of the option with `key` that was provided to the plugin
+    /// through the `options:options` feature when it was instantiated. This
+    /// includes the block length bounds of the `Features` and
+    /// `param:sampleRate`.
+    pub fn provided_option(&self, key: lv2_raw::LV2Urid) -> Option<OptionValue> {
+        let urids = self._features.common_urids();
+        let option = self.options.get(key)?;
+        unsafe { OptionValue::from_option(option, urids.atom_int, urids.atom_float) }
+    }
+
+    /// Get the value of the option with `key` from the plugin through its
+    /// `options:interface`. `key` is a URID of the `Features` that the
+    /// instance was created with.
+    ///
+    /// # Errors
+    /// Returns an error if the plugin does not implement `options:interface`,
+    /// if the plugin does not have the option, or if the option is not an
+    /// `atom:Int` or `atom:Float`.
+    pub fn get_option(&self, key: lv2_raw::LV2Urid) -> Result<OptionValue, OptionsError> {
+        let get = self
+            .options_interface
+            .and_then(|i| i.get)
+            .ok_or(OptionsError::NotSupported)?;
+        let urids = self._features.common_urids();
+        let mut options = [empty_option(), empty_option()];
+        options[0].key = key;
+        let status = unsafe { get(self.lilv_instance().handle(), options.as_mut_ptr()) };
+        if status != lv2_sys::LV2_Options_Status_LV2_OPTIONS_SUCCESS {
+            return Err(OptionsError::PluginError { status });
+        }
+        unsafe { OptionValue::from_option(&options[0], urids.atom_int, urids.atom_float) }.ok_or(
+            OptionsError::UnsupportedType {
+                type_: options[0].type_,
+            },
+        )
+    }
+
+    /// Set the option with `key` on the plugin through its
+    /// `options:interface`. `key` is a URID of the `Features` that the
+    /// instance was created with.
+    ///
+    /// # Errors
+    /// Returns an error if the plugin does not implement `options:interface` or
+    /// if the plugin rejects the option.
+    pub fn set_option(
+        &mut self,
+        key: lv2_raw::LV2Urid,
+        value: OptionValue,
+    ) -> Result<(), OptionsError> {
+        self.set_options(&[(key, value)])
+    }
+
+    /// Set all `options` on the plugin with a single call to its
+    /// `options:interface`.
+    fn set_options(
+        &mut self,
+        options: &[(lv2_raw::LV2Urid, OptionValue)],
+    ) -> Result<(), OptionsError> {
+        let set = self
+            .options_interface
+            .and_then(|i| i.set)
+            .ok_or(OptionsError::NotSupported)?;
+        let urids = self._features.common_urids();
+        let values: Vec<u32> = options.iter().map(|(_, v)| v.to_bits()).collect();
+        let options: Vec<lv2_sys::LV2_Options_Option> = options
+            .iter()
+            .zip(values.iter())
+            .map(|((key, value), bits)| lv2_sys::LV2_Options_Option {
+                context: lv2_sys::LV2_Options_Context_LV2_OPTIONS_INSTANCE,
+                subject: 0,
+                key: *key,
+                size: std::mem::size_of::<u32>() as u32,
+                type_: match value {
+                    OptionValue::Int(_) => urids.atom_int,
+                    OptionValue::Float(_) => urids.atom_float,
+                },
+                value: (bits as *const u32).cast(),
+            })
+            .chain(std::iter::once(empty_option()))
+            .collect();
+        let status = unsafe { set(self.lilv_instance().handle(), options.as_ptr()) };
+        if status != lv2_sys::LV2_Options_Status_LV2_OPTIONS_SUCCESS {
+            return Err(OptionsError::PluginError { status });
+        }
+        Ok(())
+    }
+
+    /// Change the minimum and maximum block length of the instance, for
+    /// example when the buffer size of the host changes. The new bounds are
+    /// stored in the `buf-size:minBlockLength` and `buf-size:maxBlockLength`
+    /// options that were provided to the plugin and are sent to the plugin
+    /// through its `options:interface`. `run` accepts block lengths within the
+    /// new bounds.
+    ///
+    /// Plugins that do not implement `options:interface` only read the
+    /// options when they are instantiated, so the new bounds must be within
+    /// the bounds they were instantiated with. Their options are left
+    /// unchanged since the plugin is never told about the new bounds. Such
+    /// plugins should be instantiated again to use other block lengths.
+    ///
+    /// # Errors
+    /// Returns an error if `min` is 0 or larger than `max`, if the bounds break
+    /// the `buf-size:powerOf2BlockLength` or `buf-size:fixedBlockLength`
+    /// guarantees given to the plugin, if the bounds are outside of what the
+    /// plugin knows about, or if the plugin rejects the options. The bounds are
+    /// not changed on error.
+    pub fn set_block_length_bounds(
+        &mut self,
+        min: usize,
+        max: usize,
+    ) -> Result<(), BlockLengthError> {
+        if min == 0 || min > max {
+            return Err(BlockLengthError::InvalidBounds { min, max });
+        }
+        if self._features.provides_power_of_2_block_length() {
+            if let Some(actual) = [min, max].iter().copied().find(|n| !n.is_power_of_two()) {
+                return Err(BlockLengthError::NotPowerOf2 { actual });
+            }
+        }
+        if self.fixed_block_length && min != max {
+            return Err(BlockLengthError::NotFixed {
+                length: self.max_block_size,
+                min,
+                max,
+            });
+        }
+        let urid = |uri: &[u8]| self._features.urid(CStr::from_bytes_with_nul(uri).unwrap());
+        let options = [
+            (
+                urid(b"http://lv2plug.in/ns/ext/buf-size#minBlockLength\0"),
+                OptionValue::Int(min as i32),
+            ),
+            (
+                urid(b"http://lv2plug.in/ns/ext/buf-size#maxBlockLength\0"),
+                OptionValue::Int(max as i32),
+            ),
+        ];
+        let accepted = block_length_options_accepted(self.set_options(&options))?;
+        if !accepted {
+            if min < self.plugin_min_block_size {
+                return Err(BlockLengthError::TooSmall {
+                    min_supported: self.plugin_min_block_size,
+                    actual: min,
+                });
+            }
+            if max > self.plugin_max_block_size {
+                return Err(BlockLengthError::TooLarge {
+                    max_supported: self.plugin_max_block_size,
+                    actual: max,
+                });
+            }
+            // The plugin still assumes the length it was instantiated with.
+            if self.fixed_block_length && max != self.max_block_size {
+                return Err(BlockLengthError::NotFixed {
+                    length: self.max_block_size,
+                    min,
+                    max,
+                });
+            }
+        } else {
+            self.plugin_min_block_size = min;
+            self.plugin_max_block_size = max;
+            self._features
+                .set_block_length_options(&mut self.options, min, max);
+        }
+        for buffer in self
+            .cv_input_buffers
+            .iter_mut()
+            .chain(self.padded_inputs.iter_mut())
+            .chain(self.padded_outputs.iter_mut())
+        {
+            buffer.resize(max, 0.0);
+        }
+        self.min_block_size = min;
+        self.max_block_size = max;
+        Ok(())
+    }
+
+    /// Set whether `run` delivers the responses of the plugin's worker to the
+    /// plugin. This is `true` by default. If `false`, responses are kept until
+    /// `handle_worker_responses` is called. `run` still notifies the plugin of
+    /// the end of each run cycle.
+    pub fn set_auto_handle_worker_responses(&mut self, auto_handle: bool) {
+        self.auto_handle_worker_responses = auto_handle;
+    }
+
+    /// Deliver the pending responses of the plugin's worker to the plugin. This
+    /// is done by `run` unless disabled with
+    /// `set_auto_handle_worker_responses`. This is realtime safe but must not
+    /// be called concurrently with `run`.
+    ///
+    /// # Safety
+    /// Running plugin code is unsafe.
+    pub unsafe fn handle_worker_responses(&mut self) {
+        let handle = self.lilv_instance().handle();
+        if let Some(interface) = self.worker_interface.as_mut() {
+            worker::handle_work_responses(
+                interface,
+                &mut self.worker_to_instance_receiver,
+                &mut self.worker_response_buffer,
+                handle,
+            );
+        }
+    }
+
+    /// The number of messages that the plugin tried to schedule with
+    /// `worker:schedule` but that were dropped, for example because the worker
+    /// queue was full. Dropped messages are also logged as warnings by the
+    /// worker. Work that was dropped never happens, which may show up as
+    /// incomplete operations like a sample that never loads.
+    pub fn dropped_worker_messages(&self) -> usize {
+        self._work_scheduler
+            .dropped_messages()
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// The block length that was last set with `set_block_length`.
+    pub fn nominal_block_length(&self) -> Option<usize> {
+        self.nominal_block_length
+    }
+
+    /// Returns the CV inputs that were given values outside of the minimum and
+    /// maximum values of their ports since the last call. This is only checked
+    /// in debug builds, and is checked whether or not the CV inputs are
+    /// clamped with `PortConnections::with_cv_clamping`.
+    pub fn out_of_range_cv_inputs_since_last_check(&mut self) -> Vec<PortIndex> {
+        self.cv_inputs
+            .iter()
+            .zip(self.cv_inputs_out_of_range.iter_mut())
+            .filter_map(|(&index, out_of_range)| std::mem::take(out_of_range).then_some(index))
+            .collect()
+    }
+
+    /// Returns `true` if the plugin sent a `state:StateChanged` notification
+    /// on one of its atom outputs since the last call. Hosts can use this to
+    /// mark the session as modified. The flag is reset when queried. Only
+    /// plugins that implement `state:interface` are checked for the
+    /// notification.
+    pub fn state_changed_since_last_check(&mut self) -> bool {
+        std::mem::take(&mut self.state_changed)
+    }
+
+    /// Save the state of the instance through the plugin's `state:interface`.
+    /// This includes state that is not stored in control ports, such as the
+    /// files that a sampler has loaded.
+    ///
+    /// # Safety
+    /// Running plugin code is unsafe. This must not be called concurrently
+    /// with `run`.
+    ///
+    /// # Errors
+    /// Returns an error if the plugin does not support state or if it failed
+    /// to save its state.
+    pub unsafe fn save_state(&mut self) -> Result<PluginState, StateError> {
+        let interface = self.state_interface.ok_or(StateError::NotSupported)?;
+        let features = self.state_features();
+        PluginState::save(&interface, self.lilv_instance().handle(), &features)
+    }
+
+    /// Restore `state` through the plugin's `state:interface`. `state` should
+    /// have been saved from an instance of the same plugin that uses the same
+    /// `Features`.
+    ///
+    /// # Safety
+    /// Running plugin code is unsafe. This must not be called concurrently
+    /// with `run`.
+    ///
+    /// # Errors
+    /// Returns an error if the plugin does not support state or if it failed
+    /// to restore the state.
+    pub unsafe fn restore_state(&mut self, state: &PluginState) -> Result<(), StateError> {
+        let interface = self.state_interface.ok_or(StateError::NotSupported)?;
+        let features = self.state_features();
+        state.restore(&interface, self.lilv_instance().handle(), &features)
+    }
+
+    /// Apply `preset` to the instance. The control inputs that the preset
+    /// declares values for are set and the rest are left unchanged. If the
+    /// preset contains state, then it is restored through the plugin's
+    /// `state:interface`.
+    ///
+    /// # Safety
+    /// Running plugin code is unsafe. This must not be called concurrently
+    /// with `run`.
+    pub unsafe fn apply_preset(&mut self, preset: &Preset) {
+        self.set_control_inputs(preset.port_values());
+        if self.state_interface.is_none() {
+            return;
+        }
+        let features = self.state_features();
+        let instance = self.lilv_instance();
+        if let Some(descriptor) = instance.descriptor() {
+            preset.restore_state(&self._features, descriptor, instance.handle(), &features);
+        }
+    }
+
+    /// The null terminated array of features for `state:interface` functions.
+    fn state_features(&self) -> Vec<*const LV2Feature> {
+        self._features
+            .iter_state_features()
+            .map(|f| f as *const LV2Feature)
+            .chain(std::iter::once(std::ptr::null()))
+            .collect()
+    }
+
+    /// The peak absolute value of each audio output during the last successful
+    /// `run`, in port index order. The peaks are measured as part of `run` so
+    /// hosts do not have to scan the outputs again for metering. All peaks are
+    /// 0 before the first `run`.
+    pub fn last_output_peaks(&self) -> &[f32] {
+        &self.last_output_peaks
+    }
+
+    /// Get the value of the control port at `index`. If `index` is not a valid
+    /// control port index, then `None` is returned.
+    pub fn control_output(&self, index: PortIndex) -> Option<f32> {
+        self.control_outputs.get(index)
+    }
+
+    /// Get the value of the control output port with the given symbol. If there
+    /// is no control output with the symbol, then `None` is returned.
+    pub fn control_output_by_symbol(&self, symbol: &str) -> Option<f32> {
+        let index = self.port_symbols.iter().position(|s| s == symbol)?;
+        self.control_output(PortIndex(index))
+    }
+
+    /// Get the value of the control port at `index`. If `index` is not a valid
     /// control port index, then `None` is returned.
     pub fn control_input(&self, index: PortIndex) -> Option<f32> {
         self.control_inputs.get(index)
     }
 
+    /// Set the random seed of the plugin to `seed`. This is best effort.
+    /// LV2 has no way to declare a seed port, so the port is guessed: the seed
+    /// is written to the first control input whose symbol or name contains
+    /// "seed". This may pick a port that is not a seed, or miss a seed port
+    /// with a different name. Use `set_control_input` to set a known seed
+    /// port instead.
+    ///
+    /// The seed is wrapped to fit within the bounds of the port. Only the
+    /// first 2^24 values of a port are used since larger integers can not be
+    /// represented exactly as `f32`.
+    ///
+    /// Returns the index of the port that was set or `None` if the plugin does
+    /// not have a seed control. The output of plugins that use randomness but
+    /// do not expose a seed can not be made deterministic.
+    pub fn set_random_seed(&mut self, seed: u64) -> Option<PortIndex> {
+        // The number of consecutive integers that `f32` represents exactly.
+        const EXACT_F32_INTEGERS: u64 = 1 << f32::MANTISSA_DIGITS;
+        let index = self.seed_port?;
+        let port = self
+            .control_inputs
+            .iter_ports()
+            .find(|p| p.port_index == index)?;
+        let value = if port.minimum.is_finite() && port.maximum.is_finite() {
+            // The range may not fit in `f32` or `u64`. The cast saturates.
+            let range = (f64::from(port.maximum) - f64::from(port.minimum)).floor() as u64;
+            let span = range.saturating_add(1).min(EXACT_F32_INTEGERS);
+            port.minimum + (seed % span) as f32
+        } else {
+            (seed % EXACT_F32_INTEGERS) as f32
+        };
+        self.set_control_input(index, value)?;
+        Some(index)
+    }
+
     /// Iterate through all control input ports.
     pub fn iter_control_inputs(&self) -> impl Iterator<Item = &'_ ControlPort> {
         self.control_inputs.iter_ports()
     }
 
+    /// The number of control input ports.
+    pub fn control_input_count(&self) -> usize {
+        self.control_inputs.len()
+    }
+
+    /// Get up to `count` control input ports, starting at the `start`th
+    /// control input. The ports are sorted by port index like in
+    /// `iter_control_inputs`. This allows a UI to show large numbers of
+    /// controls one page at a time.
+    pub fn control_inputs_range(&self, start: usize, count: usize) -> &[ControlPort] {
+        self.control_inputs.range(start, count)
+    }
+
     /// Iterate through all control output ports.
     pub fn iter_control_outputs(&self) -> impl Iterator<Item = &'_ ControlPort> {
         self.control_outputs.iter_ports()
@@ -438,12 +2290,23 @@ impl Instance {
     /// the value is returned.
     ///
     /// Note: This may be different than the passed in value in cases the input
-    /// `value` is out of bounds of allowed values.
+    /// `value` is out of bounds of allowed values. Values of `lv2:toggled`
+    /// ports are set to `0.0` or `1.0` and values of `lv2:integer` ports are
+    /// rounded.
     pub fn set_control_input(&mut self, index: PortIndex, value: f32) -> Option<f32> {
-        self.control_inputs.set(index, value)?;
-        let ptr = self.control_inputs.value_ptr(index)?;
-        unsafe { self.inner.instance_mut().connect_port(index.0, ptr) };
-        Some(unsafe { *ptr })
+        // The control ports are connected to their values when the instance is
+        // created so only the value has to be updated.
+        self.control_inputs.set(index, value)
+    }
+
+    /// Set the values of several control ports at once. Values are clamped and
+    /// rounded like `set_control_input`. Invalid control port indices are
+    /// ignored. Returns the number of control ports that were set.
+    ///
+    /// The values are applied in a single pass which is fastest when `values`
+    /// is sorted by port index.
+    pub fn set_control_inputs(&mut self, values: &[(PortIndex, f32)]) -> usize {
+        self.control_inputs.set_sorted(values)
     }
 
     /// Get the number of ports for a specific type of port.
@@ -460,25 +2323,290 @@ impl Instance {
         }
     }
 
-    /// Get the number of ports for each type of port.
-    pub fn port_counts(&self) -> PortCounts {
-        PortCounts {
-            control_inputs: self.port_counts_for_type(PortType::ControlInput),
-            control_outputs: self.port_counts_for_type(PortType::ControlOutput),
-            audio_inputs: self.port_counts_for_type(PortType::AudioInput),
-            audio_outputs: self.port_counts_for_type(PortType::AudioOutput),
-            atom_sequence_inputs: self.port_counts_for_type(PortType::AtomSequenceInput),
-            atom_sequence_outputs: self.port_counts_for_type(PortType::AtomSequenceOutput),
-            cv_inputs: self.port_counts_for_type(PortType::CVInput),
-            cv_outputs: self.port_counts_for_type(PortType::CVOutput),
+    /// The symbols of the ports that were not connected when `run` returned
+    /// `error`. This is empty if `error` is not a size mismatch or if too many
+    /// ports were connected.
+    ///
+    /// `RunError` does not contain the symbols itself since `run` must not
+    /// allocate.
+    pub fn missing_port_symbols(&self, error: &RunError) -> Vec<&str> {
+        let actual = match *error {
+            RunError::AudioInputsSizeMismatch { actual, .. }
+            | RunError::AudioOutputsSizeMismatch { actual, .. }
+            | RunError::AtomSequenceInputsSizeMismatch { actual, .. }
+            | RunError::AtomSequenceOutputsSizeMismatch { actual, .. }
+            | RunError::CVInputsSizeMismatch { actual, .. }
+            | RunError::CVOutputsSizeMismatch { actual, .. }
+            | RunError::ControlInputsSizeMismatch { actual, .. }
+            | RunError::ControlOutputsSizeMismatch { actual, .. } => actual,
+            _ => return Vec::new(),
+        };
+        let controls = match error.port_type() {
+            Some(PortType::ControlInput) => Some(&self.control_inputs),
+            Some(PortType::ControlOutput) => Some(&self.control_outputs),
+            _ => None,
+        };
+        if let Some(controls) = controls {
+            return controls
+                .iter_ports()
+                .skip(actual)
+                .map(|p| self.port_symbols[p.port_index.0].as_str())
+                .collect();
         }
+        let ports = match error.port_type() {
+            Some(PortType::AudioInput) => &self.audio_inputs,
+            Some(PortType::AudioOutput) => &self.audio_outputs,
+            Some(PortType::AtomSequenceInput) => &self.atom_sequence_inputs,
+            // Atom sequence outputs do not have to be connected.
+            Some(PortType::AtomSequenceOutput) => return Vec::new(),
+            Some(PortType::CVInput) => &self.cv_inputs,
+            Some(PortType::CVOutput) => &self.cv_outputs,
+            _ => return Vec::new(),
+        };
+        ports
+            .iter()
+            .skip(actual)
+            .map(|index| self.port_symbols[index.0].as_str())
+            .collect()
     }
-}
 
-impl Debug for Instance {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    /// The raw `LV2_Handle` of the instance. This is what plugin UIs that
+    /// require the `instance-access` feature are given.
+    ///
+    /// # Safety
+    /// The handle must not be used after the instance is dropped. Calling
+    /// plugin functions with the handle while the instance is running is not
+    /// thread safe.
+    pub unsafe fn lv2_handle(&self) -> *mut std::ffi::c_void {
+        self.lilv_instance().handle()
+    }
+
+    /// Get the extension data of the plugin for `uri`, or `None` if the plugin
+    /// does not provide it. This is what the `data-access` feature provides
+    /// to plugin UIs.
+    ///
+    /// # Safety
+    /// Getting extension data calls plugin code. The returned data must not be
+    /// used after the instance is dropped.
+    pub unsafe fn extension_data(&self, uri: &str) -> Option<*const std::ffi::c_void> {
+        if !has_extension_data_fn(self.lilv_instance()) {
+            return None;
+        }
+        self.lilv_instance()
+            .extension_data::<std::ffi::c_void>(uri)
+            .map(|data| data.as_ptr() as *const std::ffi::c_void)
+    }
+
+    /// The latency in samples that the plugin reported during the last `run`
+    /// or `0` if the plugin does not report latency. The latency is read from
+    /// the control output that is designated with `lv2:latency` or that has
+    /// the `lv2:reportsLatency` property. Plugins may change their latency
+    /// while running, so it should be checked after each `run`.
+    pub fn latency_samples(&self) -> u32 {
+        self.latency_port
+            .and_then(|port| self.control_outputs.get(port))
+            .map_or(0, |latency| latency.max(0.0).round() as u32)
+    }
+
+    /// Enable or bypass the plugin through its `lv2:enabled` control input.
+    /// Plugins that support this fade between the processed and unprocessed
+    /// signal so the change is click free. Returns `false` if the plugin has no
+    /// `lv2:enabled` control input, in which case hosts have to bypass the
+    /// plugin themselves, for example by not running it.
+    pub fn set_enabled(&mut self, enabled: bool) -> bool {
+        match self.enabled_port {
+            Some(index) => {
+                let value = if enabled { 1.0 } else { 0.0 };
+                self.control_inputs.set(index, value).is_some()
+            }
+            None => false,
+        }
+    }
+
+    /// Measure the impulse response of the plugin. A unit impulse is fed into
+    /// the first audio input and the first audio output is captured for
+    /// `length` samples. All other audio inputs are silent. The plugin is run
+    /// for as many blocks as needed.
+    ///
+    /// Atom sequence inputs are empty and CV inputs are silent while
+    /// measuring. Note that the plugin keeps any internal state, like reverb
+    /// tails, from previous runs.
+    ///
+    /// # Safety
+    /// Running plugin code is unsafe.
+    ///
+    /// # Errors
+    /// Returns an error if the plugin has no audio inputs or outputs, or if the
+    /// plugin could not be run.
+    pub unsafe fn measure_impulse_response(&mut self, length: usize) -> Result<Vec<f32>, RunError> {
+        if self.audio_inputs.is_empty() {
+            return Err(RunError::AudioInputsSizeMismatch {
+                expected: 1,
+                actual: 0,
+            });
+        }
+        if self.audio_outputs.is_empty() {
+            return Err(RunError::AudioOutputsSizeMismatch {
+                expected: 1,
+                actual: 0,
+            });
+        }
+        let block_size = self.max_block_size;
+        let mut buffers = NonAudioBuffers::new(self);
+        let mut inputs = vec![vec![0.0; block_size]; self.audio_inputs.len()];
+        let mut outputs = vec![vec![0.0; block_size]; self.audio_outputs.len()];
+        let mut response = Vec::with_capacity(length);
+        inputs[0][0] = 1.0;
+        while response.len() < length {
+            let remaining = length - response.len();
+            let samples = remaining.clamp(self.min_block_size, block_size);
+            buffers.run(
+                self,
+                samples,
+                inputs.iter().map(|b| b.as_slice()),
+                outputs.iter_mut().map(|b| b.as_mut_slice()),
+            )?;
+            response.extend_from_slice(&outputs[0][..samples.min(remaining)]);
+            inputs[0][0] = 0.0;
+        }
+        Ok(response)
+    }
+
+    /// The maximum number of samples that can be processed in a single run.
+    pub(crate) fn max_block_size(&self) -> usize {
+        self.max_block_size
+    }
+
+    /// The features that the instance was instantiated with.
+    pub(crate) fn features(&self) -> &Arc<Features> {
+        &self._features
+    }
+
+    /// Get the channel layout of the audio outputs. Hosts can use this to
+    /// decide how many channels a track that holds the plugin needs.
+    pub fn output_channel_layout(&self) -> ChannelLayout {
+        ChannelLayout::from_channel_count(self.audio_outputs.len())
+    }
+
+    /// Get the number of ports for each type of port.
+    pub fn port_counts(&self) -> PortCounts {
+        PortCounts {
+            control_inputs: self.port_counts_for_type(PortType::ControlInput),
+            control_outputs: self.port_counts_for_type(PortType::ControlOutput),
+            audio_inputs: self.port_counts_for_type(PortType::AudioInput),
+            audio_outputs: self.port_counts_for_type(PortType::AudioOutput),
+            atom_sequence_inputs: self.port_counts_for_type(PortType::AtomSequenceInput),
+            atom_sequence_outputs: self.port_counts_for_type(PortType::AtomSequenceOutput),
+            cv_inputs: self.port_counts_for_type(PortType::CVInput),
+            cv_outputs: self.port_counts_for_type(PortType::CVOutput),
+        }
+    }
+}
+
+/// Buffers for the atom sequence and CV ports of an instance. These are used by
+/// helpers that only deal with the audio ports of an instance. Atom sequence
+/// inputs are empty and CV inputs are silent.
+pub(crate) struct NonAudioBuffers {
+    atom_sequence_inputs: Vec<LV2AtomSequence>,
+    atom_sequence_outputs: Vec<LV2AtomSequence>,
+    cv_inputs: Vec<Vec<f32>>,
+    cv_outputs: Vec<Vec<f32>>,
+}
+
+impl NonAudioBuffers {
+    /// The capacity in bytes of the atom sequences.
+    const ATOM_SEQUENCE_CAPACITY: usize = 1024;
+
+    /// Create buffers for all the atom sequence and CV ports of `instance`.
+    pub(crate) fn new(instance: &Instance) -> NonAudioBuffers {
+        let block_size = instance.max_block_size;
+        let new_sequence =
+            |_| LV2AtomSequence::new(&instance._features, Self::ATOM_SEQUENCE_CAPACITY);
+        NonAudioBuffers {
+            atom_sequence_inputs: (0..instance.atom_sequence_inputs.len())
+                .map(new_sequence)
+                .collect(),
+            atom_sequence_outputs: (0..instance.atom_sequence_outputs.len())
+                .map(new_sequence)
+                .collect(),
+            cv_inputs: vec![vec![0.0; block_size]; instance.cv_inputs.len()],
+            cv_outputs: vec![vec![0.0; block_size]; instance.cv_outputs.len()],
+        }
+    }
+
+    /// Run `instance` with the given audio ports and these buffers for all
+    /// other ports.
+    ///
+    /// # Safety
+    /// Running plugin code is unsafe.
+    pub(crate) unsafe fn run<'i, 'o, AudioInputs, AudioOutputs>(
+        &mut self,
+        instance: &mut Instance,
+        samples: usize,
+        audio_inputs: AudioInputs,
+        audio_outputs: AudioOutputs,
+    ) -> Result<(), RunError>
+    where
+        AudioInputs: ExactSizeIterator + Iterator<Item = &'i [f32]>,
+        AudioOutputs: ExactSizeIterator + Iterator<Item = &'o mut [f32]>,
+    {
+        self.run_impl(instance, samples, audio_inputs, audio_outputs, false)
+            .map(|_| ())
+    }
+
+    /// Like `run`, but uses `Instance::run_padded`.
+    pub(crate) unsafe fn run_padded<'i, 'o, AudioInputs, AudioOutputs>(
+        &mut self,
+        instance: &mut Instance,
+        samples: usize,
+        audio_inputs: AudioInputs,
+        audio_outputs: AudioOutputs,
+    ) -> Result<usize, RunError>
+    where
+        AudioInputs: ExactSizeIterator + Iterator<Item = &'i [f32]>,
+        AudioOutputs: ExactSizeIterator + Iterator<Item = &'o mut [f32]>,
+    {
+        self.run_impl(instance, samples, audio_inputs, audio_outputs, true)
+    }
+
+    /// Run `instance` with `Instance::run_padded` if `padded` is set and with
+    /// `Instance::run` otherwise. Returns the number of valid samples in the
+    /// outputs.
+    unsafe fn run_impl<'i, 'o, AudioInputs, AudioOutputs>(
+        &mut self,
+        instance: &mut Instance,
+        samples: usize,
+        audio_inputs: AudioInputs,
+        audio_outputs: AudioOutputs,
+        padded: bool,
+    ) -> Result<usize, RunError>
+    where
+        AudioInputs: ExactSizeIterator + Iterator<Item = &'i [f32]>,
+        AudioOutputs: ExactSizeIterator + Iterator<Item = &'o mut [f32]>,
+    {
+        for sequence in self.atom_sequence_outputs.iter_mut() {
+            sequence.clear_as_chunk();
+        }
+        let ports = EmptyPortConnections::new()
+            // Shorten the lifetimes to match the buffers owned by `self`.
+            .with_audio_inputs(audio_inputs.map(|b| -> &[f32] { b }))
+            .with_audio_outputs(audio_outputs.map(|b| -> &mut [f32] { b }))
+            .with_atom_sequence_inputs(self.atom_sequence_inputs.iter())
+            .with_atom_sequence_outputs(self.atom_sequence_outputs.iter_mut())
+            .with_cv_inputs(self.cv_inputs.iter().map(|b| b.as_slice()))
+            .with_cv_outputs(self.cv_outputs.iter_mut().map(|b| b.as_mut_slice()));
+        if padded {
+            instance.run_padded(samples, ports)
+        } else {
+            instance.run(samples, ports).map(|()| samples)
+        }
+    }
+}
+
+impl Debug for Instance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Instance")
-            .field("plugin", &self.inner.instance().uri())
+            .field("plugin", &self.lilv_instance().uri())
             .field("min_block_size", &self.min_block_size)
             .field("max_block_size", &self.max_block_size)
             .field("control_inputs", &self.control_inputs)
@@ -487,12 +2615,27 @@ impl Debug for Instance {
             .field("audio_outputs", &self.audio_outputs)
             .field("atom_sequence_inputs", &self.atom_sequence_inputs)
             .field("atom_sequence_outputs", &self.atom_sequence_outputs)
+            .field(
+                "atom_sequence_output_sinks",
+                &self.atom_sequence_output_sinks,
+            )
             .field("cv_inputs", &self.cv_inputs)
+            .field("cv_input_bounds", &self.cv_input_bounds)
             .field("cv_outputs", &self.cv_outputs)
+            .field("port_symbols", &self.port_symbols)
+            .field("state_changed", &self.state_changed)
+            .field("in_place_broken", &self.in_place_broken)
+            .field("power_of_2_block_length", &self.power_of_2_block_length)
+            .field("nominal_block_length", &self.nominal_block_length)
             .field("worker_interface", &self.worker_interface)
+            .field("options_interface", &self.options_interface)
+            .field("state_interface", &self.state_interface)
+            .field("latency_port", &self.latency_port)
+            .field("enabled_port", &self.enabled_port)
+            .field("seed_port", &self.seed_port)
             .field("worker_to_instance_receiver", &"__ringbuf_receiver__")
             .field("_worker_schedule", &self._worker_schedule)
-            .field("_instance_to_worker_sender", &"__ringbuf_sender__")
+            .field("_work_scheduler", &"__ringbuf_sender__")
             .field("is_alive", &self.is_alive)
             .field("_features", &self._features)
             .finish()
@@ -501,13 +2644,69 @@ impl Debug for Instance {
 
 impl Drop for Instance {
     fn drop(&mut self) {
-        let mut is_alive = self.is_alive.lock().unwrap();
-        *is_alive = false;
+        // Blocks until the worker is done with any message it is currently
+        // handling. Afterwards, the worker will not use the instance handle.
+        *self.is_alive.lock().unwrap() = false;
+        // Responses can no longer be applied to the instance.
+        self.worker_to_instance_receiver.clear();
+        // Active instances are deactivated before they are freed.
+        drop(self.inner.take());
+    }
+}
+
+/// Returns `true` if the plugin descriptor of `instance` has an
+/// `extension_data` function. `lilv` calls it without checking for null.
+///
+/// TODO: Remove after https://github.com/poidl/lv2_raw/issues/4 is fixed.
+pub(crate) unsafe fn has_extension_data_fn(instance: &lilv::instance::Instance) -> bool {
+    let descriptor = match instance.descriptor() {
+        Some(d) => d,
+        None => return false,
+    };
+    type ExtDataFn = extern "C" fn(uri: *const u8) -> *const std::ffi::c_void;
+    let extension_data: Option<ExtDataFn> = std::mem::transmute(descriptor.extension_data);
+    extension_data.is_some()
+}
+
+/// The URIs of the URI nodes in `nodes`.
+fn nodes_to_uris(nodes: &lilv::node::Nodes) -> Vec<String> {
+    nodes
+        .iter()
+        .filter_map(|n| n.as_uri().map(str::to_string))
+        .collect()
+}
+
+/// An option with all fields set to 0. Arrays of options end with an empty
+/// option.
+fn empty_option() -> lv2_sys::LV2_Options_Option {
+    lv2_sys::LV2_Options_Option {
+        context: 0,
+        subject: 0,
+        key: 0,
+        size: 0,
+        type_: 0,
+        value: std::ptr::null(),
+    }
+}
+
+/// Returns `true` if the plugin accepted the block length options and `false`
+/// if it does not implement `options:interface`.
+fn block_length_options_accepted(
+    result: Result<(), OptionsError>,
+) -> Result<bool, BlockLengthError> {
+    match result {
+        Ok(()) => Ok(true),
+        Err(OptionsError::NotSupported) => Ok(false),
+        Err(OptionsError::PluginError { status }) => Err(BlockLengthError::PluginError { status }),
+        Err(OptionsError::UnsupportedType { type_ }) => {
+            Err(BlockLengthError::UnsupportedType { type_ })
+        }
     }
 }
 
 fn iter_ports_impl<'a>(
     plugin: &'a lilv::plugin::Plugin,
+    world: &'a lilv::World,
     common_uris: &'a CommonUris,
 ) -> impl 'a + Iterator<Item = Port> {
     plugin.iter_ports().map(move |p| {
@@ -529,228 +2728,2167 @@ fn iter_ports_impl<'a>(
         } else {
             unreachable!("Port is not an audio, control, or atom sequence port.")
         };
-        let port_type = match (io_type, data_type) {
-            (IOType::Input, DataType::Control) => PortType::ControlInput,
-            (IOType::Output, DataType::Control) => PortType::ControlOutput,
-            (IOType::Input, DataType::Audio) => PortType::AudioInput,
-            (IOType::Output, DataType::Audio) => PortType::AudioOutput,
-            (IOType::Input, DataType::AtomSequence) => PortType::AtomSequenceInput,
-            (IOType::Output, DataType::AtomSequence) => PortType::AtomSequenceOutput,
-            (IOType::Input, DataType::CV) => PortType::CVInput,
-            (IOType::Output, DataType::CV) => PortType::CVOutput,
+        let port_type = match (io_type, data_type) {
+            (IOType::Input, DataType::Control) => PortType::ControlInput,
+            (IOType::Output, DataType::Control) => PortType::ControlOutput,
+            (IOType::Input, DataType::Audio) => PortType::AudioInput,
+            (IOType::Output, DataType::Audio) => PortType::AudioOutput,
+            (IOType::Input, DataType::AtomSequence) => PortType::AtomSequenceInput,
+            (IOType::Output, DataType::AtomSequence) => PortType::AtomSequenceOutput,
+            (IOType::Input, DataType::CV) => PortType::CVInput,
+            (IOType::Output, DataType::CV) => PortType::CVOutput,
+        };
+        let range = p.range();
+        Port {
+            port_type,
+            name: p
+                .name()
+                .expect("port has no name")
+                .as_str()
+                .unwrap_or("BAD_NAME")
+                .to_string(),
+            symbol: p
+                .symbol()
+                .expect("port has no symbol")
+                .as_str()
+                .unwrap_or("BAD_SYMBOL")
+                .to_string(),
+            default_value: node_to_value(&range.default),
+            min_value: range.minimum.map(|n| node_to_value(&Some(n))),
+            max_value: range.maximum.map(|n| node_to_value(&Some(n))),
+            index: PortIndex(p.index()),
+            properties: PortProperties {
+                trigger: p.has_property(&common_uris.trigger_port_property_uri),
+                sample_rate: p.has_property(&common_uris.sample_rate_port_property_uri),
+                toggled: p.has_property(&common_uris.toggled_port_property_uri),
+                integer: p.has_property(&common_uris.integer_port_property_uri),
+                logarithmic: p.has_property(&common_uris.logarithmic_port_property_uri),
+                enumeration: p.has_property(&common_uris.enumeration_port_property_uri),
+                expensive: p.has_property(&common_uris.expensive_port_property_uri),
+                not_on_gui: p.has_property(&common_uris.not_on_gui_port_property_uri),
+            },
+            cv_capable: matches!(data_type, DataType::Control)
+                && (p.is_a(&common_uris.cv_port_uri) || p.is_a(&common_uris.mod_cv_port_uri)),
+            atom_types: p
+                .value(&common_uris.atom_supports_uri)
+                .iter()
+                .filter_map(|n| n.as_uri().map(str::to_string))
+                .collect(),
+            unit: p
+                .get(&common_uris.units_unit_uri)
+                .map(|unit| PortUnit::from_node(world, common_uris, &unit)),
+            scale_points: p
+                .scale_points()
+                .iter()
+                .map(|point| ScalePoint {
+                    label: point.label().as_str().unwrap_or("BAD_LABEL").to_string(),
+                    value: node_to_value(&Some(point.value())),
+                })
+                .collect(),
+            display_priority: p
+                .get(&common_uris.display_priority_uri)
+                .and_then(|n| n.as_int()),
+            comment: p
+                .get(&common_uris.rdfs_comment_uri)
+                .and_then(|n| n.as_str().map(str::to_string)),
+        }
+    })
+}
+
+/// The address range of the first `samples` samples of `data`.
+fn buffer_range(data: &[f32], samples: usize) -> std::ops::Range<usize> {
+    let start = data.as_ptr() as usize;
+    start..start + samples.min(data.len()) * std::mem::size_of::<f32>()
+}
+
+fn node_to_value(maybe_node: &Option<lilv::node::Node>) -> f32 {
+    let n = match maybe_node {
+        Some(n) => n,
+        None => return 0.0,
+    };
+    if n.is_float() {
+        n.as_float().unwrap_or(0.0)
+    } else if n.is_int() {
+        n.as_int().unwrap_or(0) as f32
+    } else if n.as_bool().unwrap_or(false) {
+        1f32
+    } else {
+        0f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlockLengthMode, WorkerUsage};
+    use crate::error::{BlockLengthError, InstantiateError, OptionsError, RunError};
+    use crate::{
+        ChannelLayout, IoLayout, OptionValue, Port, PortCounts, PortIndex, PortProperties, PortType,
+    };
+    use std::ffi::CStr;
+
+    #[test]
+    fn test_metadata() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .unwrap();
+        assert_eq!(plugin.uri(), "http://drobilla.net/plugins/mda/EPiano");
+        assert_eq!(plugin.name(), "MDA ePiano");
+        assert!(plugin.bundle_uri().ends_with("mda.lv2/"));
+        assert_eq!(
+            plugin.classes().collect::<Vec<_>>(),
+            vec!["Instrument Plugin", "Generator Plugin", "Plugin"]
+        );
+        assert_eq!(
+            plugin.class_uri(),
+            "http://lv2plug.in/ns/lv2core#InstrumentPlugin"
+        );
+        assert_eq!(
+            plugin.class_uris().last(),
+            Some("http://lv2plug.in/ns/lv2core#Plugin")
+        );
+        assert!(plugin.is_instrument());
+        assert!(!plugin.is_effect());
+        assert!(!plugin.is_analyser());
+        assert_eq!(plugin.block_length_mode(), BlockLengthMode::Unbounded);
+        assert_eq!(plugin.worker_usage(), WorkerUsage::None);
+        assert_eq!(plugin.sample_rate_range(), None);
+        assert_eq!(
+            *plugin.port_counts(),
+            PortCounts {
+                control_inputs: 12,
+                control_outputs: 0,
+                audio_inputs: 0,
+                audio_outputs: 2,
+                atom_sequence_inputs: 1,
+                atom_sequence_outputs: 0,
+                cv_inputs: 0,
+                cv_outputs: 0
+            }
+        );
+        assert_eq!(
+            plugin.ports().collect::<Vec<_>>(),
+            vec![
+                Port {
+                    port_type: PortType::ControlInput,
+                    name: "Envelope Decay".to_string(),
+                    symbol: "env_decay".to_string(),
+                    default_value: 0.5,
+                    min_value: Some(0.0),
+                    max_value: Some(1.0),
+                    index: PortIndex(0),
+                    properties: PortProperties::default(),
+                    cv_capable: false,
+                    atom_types: Vec::new(),
+                    unit: None,
+                    scale_points: Vec::new(),
+                    display_priority: None,
+                    comment: None
+                },
+                Port {
+                    port_type: PortType::ControlInput,
+                    name: "Envelope Release".to_string(),
+                    symbol: "env_release".to_string(),
+                    default_value: 0.5,
+                    min_value: Some(0.0),
+                    max_value: Some(1.0),
+                    index: PortIndex(1),
+                    properties: PortProperties::default(),
+                    cv_capable: false,
+                    atom_types: Vec::new(),
+                    unit: None,
+                    scale_points: Vec::new(),
+                    display_priority: None,
+                    comment: None
+                },
+                Port {
+                    port_type: PortType::ControlInput,
+                    name: "Hardness".to_string(),
+                    symbol: "hardness".to_string(),
+                    default_value: 0.5,
+                    min_value: Some(0.0),
+                    max_value: Some(1.0),
+                    index: PortIndex(2),
+                    properties: PortProperties::default(),
+                    cv_capable: false,
+                    atom_types: Vec::new(),
+                    unit: None,
+                    scale_points: Vec::new(),
+                    display_priority: None,
+                    comment: None
+                },
+                Port {
+                    port_type: PortType::ControlInput,
+                    name: "Treble Boost".to_string(),
+                    symbol: "treble_boost".to_string(),
+                    default_value: 0.5,
+                    min_value: Some(0.0),
+                    max_value: Some(1.0),
+                    index: PortIndex(3),
+                    properties: PortProperties::default(),
+                    cv_capable: false,
+                    atom_types: Vec::new(),
+                    unit: None,
+                    scale_points: Vec::new(),
+                    display_priority: None,
+                    comment: None
+                },
+                Port {
+                    port_type: PortType::ControlInput,
+                    name: "Modulation".to_string(),
+                    symbol: "modulation".to_string(),
+                    default_value: 0.5,
+                    min_value: Some(0.0),
+                    max_value: Some(1.0),
+                    index: PortIndex(4),
+                    properties: PortProperties::default(),
+                    cv_capable: false,
+                    atom_types: Vec::new(),
+                    unit: None,
+                    scale_points: Vec::new(),
+                    display_priority: None,
+                    comment: None
+                },
+                Port {
+                    port_type: PortType::ControlInput,
+                    name: "LFO Rate".to_string(),
+                    symbol: "lfo_rate".to_string(),
+                    default_value: 0.65,
+                    min_value: Some(0.0),
+                    max_value: Some(1.0),
+                    index: PortIndex(5),
+                    properties: PortProperties::default(),
+                    cv_capable: false,
+                    atom_types: Vec::new(),
+                    unit: None,
+                    scale_points: Vec::new(),
+                    display_priority: None,
+                    comment: None
+                },
+                Port {
+                    port_type: PortType::ControlInput,
+                    name: "Velocity Sense".to_string(),
+                    symbol: "vel_sense".to_string(),
+                    default_value: 0.25,
+                    min_value: Some(0.0),
+                    max_value: Some(1.0),
+                    index: PortIndex(6),
+                    properties: PortProperties::default(),
+                    cv_capable: false,
+                    atom_types: Vec::new(),
+                    unit: None,
+                    scale_points: Vec::new(),
+                    display_priority: None,
+                    comment: None
+                },
+                Port {
+                    port_type: PortType::ControlInput,
+                    name: "Stereo Width".to_string(),
+                    symbol: "stereo_width".to_string(),
+                    default_value: 0.5,
+                    min_value: Some(0.0),
+                    max_value: Some(1.0),
+                    index: PortIndex(7),
+                    properties: PortProperties::default(),
+                    cv_capable: false,
+                    atom_types: Vec::new(),
+                    unit: None,
+                    scale_points: Vec::new(),
+                    display_priority: None,
+                    comment: None
+                },
+                Port {
+                    port_type: PortType::ControlInput,
+                    name: "Polyphonic".to_string(),
+                    symbol: "polyphonic".to_string(),
+                    default_value: 1.0,
+                    min_value: Some(0.0),
+                    max_value: Some(1.0),
+                    index: PortIndex(8),
+                    properties: PortProperties::default(),
+                    cv_capable: false,
+                    atom_types: Vec::new(),
+                    unit: None,
+                    scale_points: Vec::new(),
+                    display_priority: None,
+                    comment: None
+                },
+                Port {
+                    port_type: PortType::ControlInput,
+                    name: "Fine Tuning".to_string(),
+                    symbol: "fine_tuning".to_string(),
+                    default_value: 0.5,
+                    min_value: Some(0.0),
+                    max_value: Some(1.0),
+                    index: PortIndex(9),
+                    properties: PortProperties::default(),
+                    cv_capable: false,
+                    atom_types: Vec::new(),
+                    unit: None,
+                    scale_points: Vec::new(),
+                    display_priority: None,
+                    comment: None
+                },
+                Port {
+                    port_type: PortType::ControlInput,
+                    name: "Random Tuning".to_string(),
+                    symbol: "random_tuning".to_string(),
+                    default_value: 0.146,
+                    min_value: Some(0.0),
+                    max_value: Some(1.0),
+                    index: PortIndex(10),
+                    properties: PortProperties::default(),
+                    cv_capable: false,
+                    atom_types: Vec::new(),
+                    unit: None,
+                    scale_points: Vec::new(),
+                    display_priority: None,
+                    comment: None
+                },
+                Port {
+                    port_type: PortType::ControlInput,
+                    name: "Overdrive".to_string(),
+                    symbol: "overdrive".to_string(),
+                    default_value: 0.0,
+                    min_value: Some(0.0),
+                    max_value: Some(1.0),
+                    index: PortIndex(11),
+                    properties: PortProperties::default(),
+                    cv_capable: false,
+                    atom_types: Vec::new(),
+                    unit: None,
+                    scale_points: Vec::new(),
+                    display_priority: None,
+                    comment: None
+                },
+                Port {
+                    port_type: PortType::AudioOutput,
+                    name: "Left Out".to_string(),
+                    symbol: "left_out".to_string(),
+                    default_value: 0.0,
+                    min_value: None,
+                    max_value: None,
+                    index: PortIndex(12),
+                    properties: PortProperties::default(),
+                    cv_capable: false,
+                    atom_types: Vec::new(),
+                    unit: None,
+                    scale_points: Vec::new(),
+                    display_priority: None,
+                    comment: None
+                },
+                Port {
+                    port_type: PortType::AudioOutput,
+                    name: "Right Out".to_string(),
+                    symbol: "right_out".to_string(),
+                    default_value: 0.0,
+                    min_value: None,
+                    max_value: None,
+                    index: PortIndex(13),
+                    properties: PortProperties::default(),
+                    cv_capable: false,
+                    atom_types: Vec::new(),
+                    unit: None,
+                    scale_points: Vec::new(),
+                    display_priority: None,
+                    comment: None
+                },
+                Port {
+                    port_type: PortType::AtomSequenceInput,
+                    name: "Event In".to_string(),
+                    symbol: "event_in".to_string(),
+                    default_value: 0.0,
+                    min_value: None,
+                    max_value: None,
+                    index: PortIndex(14),
+                    properties: PortProperties::default(),
+                    cv_capable: false,
+                    atom_types: vec!["http://lv2plug.in/ns/ext/midi#MidiEvent".to_string()],
+                    unit: None,
+                    scale_points: Vec::new(),
+                    display_priority: None,
+                    comment: None
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_required_and_optional_features() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-sampler")
+            .unwrap();
+        let required = plugin.required_features();
+        assert!(required.contains(&"http://lv2plug.in/ns/ext/worker#schedule".to_string()));
+        assert!(required.contains(&"http://lv2plug.in/ns/ext/urid#map".to_string()));
+        assert!(plugin
+            .optional_features()
+            .iter()
+            .all(|f| !required.contains(f)));
+    }
+
+    #[test]
+    fn test_author_and_project_metadata() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-amp")
+            .unwrap();
+        assert_eq!(
+            plugin.license().as_deref(),
+            Some("http://opensource.org/licenses/isc")
+        );
+        assert_eq!(
+            plugin.project_uri().as_deref(),
+            Some("http://lv2plug.in/ns/lv2")
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        let world = crate::World::new();
+        for plugin in world.iter_plugins() {
+            if let Some(comment) = plugin.comment() {
+                assert!(!comment.is_empty(), "{}", plugin.uri());
+            }
+            for port in plugin.ports() {
+                if let Some(comment) = port.comment {
+                    assert!(!comment.is_empty(), "{} {}", plugin.uri(), port.symbol);
+                }
+            }
+        }
+        let epiano = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .unwrap();
+        assert!(epiano.ports().all(|p| p.comment.is_none()));
+
+        let dir = tempfile::tempdir().unwrap();
+        let uri = "http://example.com/plugins/commented";
+        crate::tests::write_test_bundle(
+            dir.path(),
+            "commented.lv2",
+            uri,
+            &format!(
+                r#"<{uri}>
+    a lv2:Plugin ;
+    doap:name "Commented" ;
+    rdfs:comment "Amplifies the input." ;
+    lv2:port [
+        a lv2:InputPort, lv2:ControlPort ;
+        lv2:index 0 ;
+        lv2:symbol "gain" ;
+        lv2:name "Gain" ;
+        rdfs:comment "The gain in decibels."
+    ] , [
+        a lv2:InputPort, lv2:AudioPort ;
+        lv2:index 1 ;
+        lv2:symbol "in" ;
+        lv2:name "In"
+    ] ."#
+            ),
+        );
+        let world = crate::World::with_search_path(&[dir.path()]);
+        let plugin = world.plugin_by_uri(uri).expect("Plugin not found.");
+        assert_eq!(plugin.comment().as_deref(), Some("Amplifies the input."));
+        assert_eq!(
+            plugin
+                .ports()
+                .map(|p| p.comment)
+                .collect::<Vec<Option<String>>>(),
+            vec![Some("The gain in decibels.".to_string()), None]
+        );
+    }
+
+    #[test]
+    fn test_effect_class() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-amp")
+            .unwrap();
+        assert_eq!(
+            plugin.class_uri(),
+            "http://lv2plug.in/ns/lv2core#AmplifierPlugin"
+        );
+        assert!(plugin.is_effect());
+        assert!(!plugin.is_instrument());
+    }
+
+    #[test]
+    fn test_ports_by_display_priority_without_priorities() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let ports: Vec<Port> = plugin.ports_by_display_priority().collect();
+        assert_eq!(ports.len(), plugin.port_counts().control_inputs);
+        assert!(ports.iter().all(|p| p.display_priority().is_none()));
+        assert!(ports.windows(2).all(|w| w[0].index < w[1].index));
+    }
+
+    #[test]
+    fn test_ports_by_display_priority() {
+        let dir = tempfile::tempdir().unwrap();
+        let uri = "http://example.com/plugins/priorities";
+        crate::tests::write_test_bundle(
+            dir.path(),
+            "priorities.lv2",
+            uri,
+            &format!(
+                r#"<{uri}>
+    a lv2:Plugin ;
+    doap:name "Priorities" ;
+    lv2:port [
+        a lv2:InputPort, lv2:ControlPort ;
+        lv2:index 0 ;
+        lv2:symbol "gain" ;
+        lv2:name "Gain" ;
+        <http://lv2plug.in/ns/ext/port-props#displayPriority> 1
+    ] , [
+        a lv2:InputPort, lv2:AudioPort ;
+        lv2:index 1 ;
+        lv2:symbol "in" ;
+        lv2:name "In"
+    ] , [
+        a lv2:InputPort, lv2:ControlPort ;
+        lv2:index 2 ;
+        lv2:symbol "tone" ;
+        lv2:name "Tone"
+    ] , [
+        a lv2:InputPort, lv2:ControlPort ;
+        lv2:index 3 ;
+        lv2:symbol "mix" ;
+        lv2:name "Mix" ;
+        <http://lv2plug.in/ns/ext/port-props#displayPriority> 5
+    ] ."#
+            ),
+        );
+        let world = crate::World::with_search_path(&[dir.path()]);
+        let plugin = world.plugin_by_uri(uri).expect("Plugin not found.");
+        let ports: Vec<(PortIndex, Option<i32>)> = plugin
+            .ports_by_display_priority()
+            .map(|p| (p.index, p.display_priority()))
+            .collect();
+        assert_eq!(
+            ports,
+            vec![
+                (PortIndex(3), Some(5)),
+                (PortIndex(0), Some(1)),
+                (PortIndex(2), None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_output_channel_layout() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        assert_eq!(instance.output_channel_layout(), ChannelLayout::Stereo);
+    }
+
+    #[test]
+    fn test_io_layout() {
+        let world = crate::World::new();
+        let epiano = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        assert_eq!(
+            epiano.io_layout(),
+            IoLayout {
+                inputs: ChannelLayout::Multi(0),
+                outputs: ChannelLayout::Stereo,
+            }
+        );
+        let amp = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-amp")
+            .expect("Plugin not found.");
+        assert_eq!(
+            amp.io_layout(),
+            IoLayout {
+                inputs: ChannelLayout::Mono,
+                outputs: ChannelLayout::Mono,
+            }
+        );
+    }
+
+    #[test]
+    fn test_supports_options_interface() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        assert!(!instance.supports_options_interface());
+    }
+
+    #[test]
+    fn test_requires_configuration() {
+        let world = crate::World::new();
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let instantiate = |uri: &str| unsafe {
+            world
+                .plugin_by_uri(uri)
+                .expect("Plugin not found.")
+                .instantiate(features.clone(), 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        assert!(!instantiate("http://drobilla.net/plugins/mda/EPiano").requires_configuration());
+        assert!(instantiate("http://lv2plug.in/plugins/eg-sampler").requires_configuration());
+    }
+
+    #[test]
+    fn test_port_index_by_symbol() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        assert_eq!(plugin.port_index_by_symbol("env_decay"), Some(PortIndex(0)));
+        assert_eq!(plugin.port_index_by_symbol("not_a_port"), None);
+
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        // Control inputs are not returned.
+        assert_eq!(instance.control_output_by_symbol("env_decay"), None);
+        assert_eq!(instance.control_output_by_symbol("not_a_port"), None);
+    }
+
+    #[test]
+    fn test_control_output_by_symbol() {
+        let dir = tempfile::tempdir().unwrap();
+        crate::tests::write_test_bundle(
+            dir.path(),
+            "meter-amp.lv2",
+            "http://lv2plug.in/plugins/eg-amp",
+            r#"<http://lv2plug.in/plugins/eg-amp>
+    a lv2:Plugin ;
+    doap:name "Meter Amp" ;
+    lv2:port [
+        a lv2:InputPort, lv2:ControlPort ;
+        lv2:index 0 ;
+        lv2:symbol "gain" ;
+        lv2:name "Gain" ;
+        lv2:default 0.0
+    ] , [
+        a lv2:InputPort, lv2:AudioPort ;
+        lv2:index 1 ;
+        lv2:symbol "in" ;
+        lv2:name "In"
+    ] , [
+        a lv2:OutputPort, lv2:AudioPort ;
+        lv2:index 2 ;
+        lv2:symbol "out" ;
+        lv2:name "Out"
+    ] , [
+        a lv2:OutputPort, lv2:ControlPort ;
+        lv2:index 3 ;
+        lv2:symbol "level" ;
+        lv2:name "Level"
+    ] ."#,
+        );
+        let world = crate::World::with_search_path(&[dir.path()]);
+        let plugin = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-amp")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        // eg-amp does not write the output, so it is set as if it had.
+        unsafe {
+            *instance
+                .control_outputs
+                .value_mut_ptr(PortIndex(3))
+                .unwrap() = 0.5
+        };
+        assert_eq!(instance.control_output_by_symbol("level"), Some(0.5));
+        assert_eq!(
+            instance.control_output_by_symbol("level"),
+            instance.control_output(PortIndex(3))
+        );
+        assert_eq!(instance.control_output_by_symbol("gain"), None);
+    }
+
+    #[test]
+    fn test_port_groups_reference_plugin_ports() {
+        let world = crate::World::new();
+        for plugin in world.iter_plugins() {
+            let port_count = plugin.ports().count();
+            let groups = plugin.port_groups();
+            for (i, group) in groups.iter().enumerate() {
+                assert!(!group.members.is_empty(), "{:?}", group);
+                assert!(
+                    group.members.iter().all(|p| p.index.0 < port_count),
+                    "{:?}",
+                    group
+                );
+                assert!(
+                    groups[i + 1..].iter().all(|g| g.uri != group.uri),
+                    "{:?}",
+                    groups
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_port_groups_have_member_designations() {
+        let dir = tempfile::tempdir().unwrap();
+        let uri = "http://example.com/plugins/stereo";
+        crate::tests::write_test_bundle(
+            dir.path(),
+            "stereo.lv2",
+            uri,
+            &format!(
+                r#"<{uri}#out>
+    a pg:StereoGroup, pg:OutputGroup ;
+    lv2:symbol "out" .
+
+<{uri}>
+    a lv2:Plugin ;
+    doap:name "Stereo" ;
+    lv2:port [
+        a lv2:InputPort, lv2:AudioPort ;
+        lv2:index 0 ;
+        lv2:symbol "in" ;
+        lv2:name "In"
+    ] , [
+        a lv2:OutputPort, lv2:AudioPort ;
+        lv2:index 1 ;
+        lv2:symbol "out_left" ;
+        lv2:name "Left Out" ;
+        pg:group <{uri}#out> ;
+        lv2:designation pg:left
+    ] , [
+        a lv2:OutputPort, lv2:AudioPort ;
+        lv2:index 2 ;
+        lv2:symbol "out_right" ;
+        lv2:name "Right Out" ;
+        pg:group <{uri}#out> ;
+        lv2:designation pg:right
+    ] ."#
+            ),
+        );
+        let world = crate::World::with_search_path(&[dir.path()]);
+        let plugin = world.plugin_by_uri(uri).expect("Plugin not found.");
+        assert_eq!(
+            plugin.port_groups(),
+            vec![PortGroup {
+                uri: format!("{}#out", uri),
+                symbol: Some("out".to_string()),
+                members: vec![
+                    PortGroupMember {
+                        index: PortIndex(1),
+                        designation: Some("http://lv2plug.in/ns/ext/port-groups#left".to_string()),
+                    },
+                    PortGroupMember {
+                        index: PortIndex(2),
+                        designation: Some("http://lv2plug.in/ns/ext/port-groups#right".to_string()),
+                    },
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_provided_options() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder {
+            min_block_length: 1,
+            max_block_length: 512,
+            ..crate::features::FeaturesBuilder::default()
+        });
+        let instance = unsafe {
+            plugin
+                .instantiate(features.clone(), 48000.0)
+                .expect("Could not instantiate plugin.")
+        };
+        let urid = |uri: &[u8]| features.urid(CStr::from_bytes_with_nul(uri).unwrap());
+        assert_eq!(
+            instance.provided_option(urid(b"http://lv2plug.in/ns/ext/parameters#sampleRate\0")),
+            Some(OptionValue::Float(48000.0))
+        );
+        assert_eq!(
+            instance.provided_option(urid(b"http://lv2plug.in/ns/ext/buf-size#maxBlockLength\0")),
+            Some(OptionValue::Int(512))
+        );
+        assert_eq!(
+            instance.get_option(urid(b"http://lv2plug.in/ns/ext/parameters#sampleRate\0")),
+            Err(OptionsError::NotSupported)
+        );
+    }
+
+    #[test]
+    fn test_missing_port_symbols() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features.clone(), 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        let input = crate::event::LV2AtomSequence::new(&features, 1024);
+        let mut left = vec![0.0; 64];
+        let ports = crate::EmptyPortConnections::new()
+            .with_atom_sequence_inputs(std::iter::once(&input))
+            .with_audio_outputs(std::iter::once(left.as_mut_slice()));
+        let error = unsafe { instance.run(64, ports) }.unwrap_err();
+        assert_eq!(error.port_type(), Some(PortType::AudioOutput));
+        assert_eq!(instance.missing_port_symbols(&error), ["right_out"]);
+        assert!(instance
+            .missing_port_symbols(&RunError::InPlaceNotSupported)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_sample_rate_range() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, range) in [
+            (
+                "both",
+                "param:sampleRate [ lv2:minimum 44100 ; lv2:maximum 96000.0 ] ;",
+            ),
+            ("min", "param:sampleRate [ lv2:minimum 48000 ] ;"),
+            ("max", "param:sampleRate [ lv2:maximum 48000 ] ;"),
+            ("none", ""),
+        ] {
+            let uri = format!("http://example.com/plugins/{}", name);
+            crate::tests::write_test_bundle(
+                dir.path(),
+                &format!("{}.lv2", name),
+                &uri,
+                &format!(
+                    r#"<{uri}>
+    a lv2:Plugin ;
+    doap:name "Sample Rate" ;
+    {range}
+    lv2:port [
+        a lv2:InputPort, lv2:ControlPort ;
+        lv2:index 0 ;
+        lv2:symbol "gain" ;
+        lv2:name "Gain"
+    ] ."#
+                ),
+            );
+        }
+        let world = crate::World::with_search_path(&[dir.path()]);
+        let range = |name: &str| {
+            world
+                .plugin_by_uri(&format!("http://example.com/plugins/{}", name))
+                .expect("Plugin not found.")
+                .sample_rate_range()
+        };
+        assert_eq!(range("both"), Some((44100.0, 96000.0)));
+        assert_eq!(range("min"), Some((48000.0, f64::INFINITY)));
+        assert_eq!(range("max"), Some((0.0, 48000.0)));
+        assert_eq!(range("none"), None);
+    }
+
+    #[test]
+    fn test_instantiate_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugin_ttl = |uri: &str, properties: &str| {
+            format!(
+                r#"<{uri}>
+    a lv2:Plugin ;
+    doap:name "Test" ;
+    {properties} ;
+    lv2:port [
+        a lv2:InputPort, lv2:ControlPort ;
+        lv2:index 0 ;
+        lv2:symbol "gain" ;
+        lv2:name "Gain"
+    ] ."#
+            )
+        };
+        let make_path_uri = "http://example.com/plugins/make-path";
+        crate::tests::write_test_bundle(
+            dir.path(),
+            "make-path.lv2",
+            make_path_uri,
+            &plugin_ttl(
+                make_path_uri,
+                "lv2:requiredFeature <http://lv2plug.in/ns/ext/state#makePath>",
+            ),
+        );
+        let sample_rate_uri = "http://example.com/plugins/sample-rate";
+        crate::tests::write_test_bundle(
+            dir.path(),
+            "sample-rate.lv2",
+            sample_rate_uri,
+            &plugin_ttl(
+                sample_rate_uri,
+                "param:sampleRate [ lv2:minimum 48000 ; lv2:maximum 96000 ]",
+            ),
+        );
+        let world = crate::World::with_search_path(&[dir.path()]);
+        let features = world.build_features(crate::features::FeaturesBuilder {
+            state_dir: None,
+            ..crate::features::FeaturesBuilder::default()
+        });
+
+        let plugin = world
+            .plugin_by_uri(make_path_uri)
+            .expect("Plugin not found.");
+        assert_eq!(
+            unsafe { plugin.instantiate(features.clone(), 44100.0) }.unwrap_err(),
+            InstantiateError::FeatureSetupFailed {
+                plugin_uri: make_path_uri.to_string(),
+                missing_features: vec!["http://lv2plug.in/ns/ext/state#makePath".to_string()],
+            }
+        );
+
+        let plugin = world
+            .plugin_by_uri(sample_rate_uri)
+            .expect("Plugin not found.");
+        assert_eq!(
+            unsafe { plugin.instantiate(features, 44100.0) }.unwrap_err(),
+            InstantiateError::SampleRateUnsupported {
+                plugin_uri: sample_rate_uri.to_string(),
+                sample_rate: 44100.0,
+                min_supported: 48000.0,
+                max_supported: 96000.0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_instantiate_many_worker_plugins() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-sampler")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder {
+            worker_thread_count: 2,
+            ..crate::features::FeaturesBuilder::default()
+        });
+        let mut instances = Vec::new();
+        for i in 0..100 {
+            let instance = unsafe {
+                plugin
+                    .instantiate(features.clone(), 44100.0)
+                    .expect("Could not instantiate plugin.")
+            };
+            // Drop some instances while the worker threads are running.
+            if i % 2 == 0 {
+                instances.push(instance);
+            }
+        }
+        features.worker_manager().run_workers();
+        assert_eq!(features.worker_manager().workers_count(), 50);
+        instances.clear();
+        features.worker_manager().run_workers();
+        assert_eq!(features.worker_manager().workers_count(), 0);
+    }
+
+    #[test]
+    fn test_shared_worker_manager() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-sampler")
+            .expect("Plugin not found.");
+        let manager = std::sync::Arc::new(crate::WorkerManager::default());
+        let build_features = || {
+            world.build_features(crate::features::FeaturesBuilder {
+                worker_manager: Some(manager.clone()),
+                ..crate::features::FeaturesBuilder::default()
+            })
+        };
+        let (features_a, features_b) = (build_features(), build_features());
+        assert!(std::sync::Arc::ptr_eq(
+            features_a.worker_manager(),
+            &manager
+        ));
+        assert!(std::sync::Arc::ptr_eq(
+            features_b.worker_manager(),
+            &manager
+        ));
+        let instances: Vec<_> = vec![features_a, features_b]
+            .into_iter()
+            .map(|features| unsafe {
+                plugin
+                    .instantiate(features, 44100.0)
+                    .expect("Could not instantiate plugin.")
+            })
+            .collect();
+        manager.run_workers();
+        assert_eq!(manager.workers_count(), 2);
+        drop(instances);
+        manager.run_workers();
+        assert_eq!(manager.workers_count(), 0);
+    }
+
+    #[test]
+    fn test_run_workers_without_worker_thread() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-sampler")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder {
+            min_block_length: 1,
+            max_block_length: 64,
+            run_worker_thread: false,
+            ..crate::features::FeaturesBuilder::default()
+        });
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features.clone(), 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        let before_load = unsafe { run_sampler_note(&mut instance, &features) };
+
+        let dir = tempfile::tempdir().unwrap();
+        let load = sampler_load_events(&features, &write_test_sample(dir.path()));
+        let mut output = vec![0.0; 64];
+        let ports = crate::EmptyPortConnections::new()
+            .with_atom_sequence_inputs(std::iter::once(&load))
+            .with_audio_outputs(std::iter::once(output.as_mut_slice()));
+        unsafe { instance.run(64, ports).unwrap() };
+        // There is no worker thread, so the sample is not loaded until the host
+        // runs the workers.
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        assert_eq!(
+            unsafe { run_sampler_note(&mut instance, &features) },
+            before_load
+        );
+
+        features.worker_manager().run_workers();
+        // The response is delivered at the end of the next run, so the block
+        // after it plays the loaded sample.
+        assert_eq!(
+            unsafe { run_sampler_note(&mut instance, &features) },
+            before_load
+        );
+        assert_ne!(
+            unsafe { run_sampler_note(&mut instance, &features) },
+            before_load
+        );
+
+        drop(instance);
+        features.worker_manager().run_workers();
+        assert_eq!(features.worker_manager().workers_count(), 0);
+    }
+
+    #[test]
+    fn test_run_with_cv_clamping() {
+        // eg-amp reads its gain from the first sample of a CV port.
+        let dir = tempfile::tempdir().unwrap();
+        crate::tests::write_test_bundle(
+            dir.path(),
+            "cv-amp.lv2",
+            "http://lv2plug.in/plugins/eg-amp",
+            r#"<http://lv2plug.in/plugins/eg-amp>
+    a lv2:Plugin ;
+    doap:name "CV Amp" ;
+    lv2:port [
+        a lv2:InputPort, lv2:CVPort ;
+        lv2:index 0 ;
+        lv2:symbol "gain" ;
+        lv2:name "Gain" ;
+        lv2:default 0.0 ;
+        lv2:minimum -90.0 ;
+        lv2:maximum 0.0
+    ] , [
+        a lv2:InputPort, lv2:AudioPort ;
+        lv2:index 1 ;
+        lv2:symbol "in" ;
+        lv2:name "In"
+    ] , [
+        a lv2:OutputPort, lv2:AudioPort ;
+        lv2:index 2 ;
+        lv2:symbol "out" ;
+        lv2:name "Out"
+    ] ."#,
+        );
+        let world = crate::World::with_search_path(&[dir.path()]);
+        let plugin = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-amp")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        let gain = [24.0f32; 4];
+        let input = [1.0f32; 4];
+        let mut output = [0.0f32; 4];
+
+        for (cv_clamping, want) in [(false, 10f32.powf(24.0 * 0.05)), (true, 1.0)] {
+            let ports = crate::EmptyPortConnections::new()
+                .with_cv_clamping(cv_clamping)
+                .with_cv_inputs(std::iter::once(&gain[..]))
+                .with_audio_inputs(std::iter::once(&input[..]))
+                .with_audio_outputs(std::iter::once(&mut output[..]));
+            unsafe { instance.run(input.len(), ports).unwrap() };
+            assert!((output[0] - want).abs() < 1e-3, "{} != {}", output[0], want);
+            assert_eq!(
+                instance.out_of_range_cv_inputs_since_last_check(),
+                vec![PortIndex(0)]
+            );
+            assert!(instance
+                .out_of_range_cv_inputs_since_last_check()
+                .is_empty());
+        }
+        assert_eq!(gain, [24.0; 4]);
+
+        let ports = crate::EmptyPortConnections::new()
+            .with_cv_inputs(std::iter::once(&gain[..2]))
+            .with_audio_inputs(std::iter::once(&input[..]))
+            .with_audio_outputs(std::iter::once(&mut output[..]));
+        assert_eq!(
+            unsafe { instance.run(input.len(), ports) },
+            Err(RunError::CVInputSampleCountTooSmall {
+                expected: 4,
+                actual: 2
+            })
+        );
+
+        // Short blocks are padded up to the minimum block length.
+        let features = world.build_features(crate::features::FeaturesBuilder {
+            min_block_length: 8,
+            max_block_length: 64,
+            ..crate::features::FeaturesBuilder::default()
+        });
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        let unity_gain = [0.0f32; 4];
+        let input = [0.5f32; 4];
+        let mut output = [0.0f32; 4];
+        let ports = crate::EmptyPortConnections::new()
+            .with_cv_inputs(std::iter::once(&unity_gain[..]))
+            .with_audio_inputs(std::iter::once(&input[..]))
+            .with_audio_outputs(std::iter::once(&mut output[..]));
+        assert_eq!(unsafe { instance.run_padded(input.len(), ports) }, Ok(4));
+        assert_eq!(output, input);
+        let ports = crate::EmptyPortConnections::new()
+            .with_cv_inputs(std::iter::once(&unity_gain[..2]))
+            .with_audio_inputs(std::iter::once(&input[..]))
+            .with_audio_outputs(std::iter::once(&mut output[..]));
+        assert_eq!(
+            unsafe { instance.run_padded(input.len(), ports) },
+            Err(RunError::CVInputSampleCountTooSmall {
+                expected: 4,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_run_in_place_broken() {
+        let dir = tempfile::tempdir().unwrap();
+        crate::tests::write_test_bundle(
+            dir.path(),
+            "in-place-broken-amp.lv2",
+            "http://lv2plug.in/plugins/eg-amp",
+            r#"<http://lv2plug.in/plugins/eg-amp>
+    a lv2:Plugin ;
+    doap:name "In Place Broken Amp" ;
+    lv2:requiredFeature lv2:inPlaceBroken ;
+    lv2:port [
+        a lv2:InputPort, lv2:ControlPort ;
+        lv2:index 0 ;
+        lv2:symbol "gain" ;
+        lv2:name "Gain" ;
+        lv2:default 0.0 ;
+        lv2:minimum -90.0 ;
+        lv2:maximum 24.0
+    ] , [
+        a lv2:InputPort, lv2:AudioPort ;
+        lv2:index 1 ;
+        lv2:symbol "in" ;
+        lv2:name "In"
+    ] , [
+        a lv2:OutputPort, lv2:AudioPort ;
+        lv2:index 2 ;
+        lv2:symbol "out" ;
+        lv2:name "Out"
+    ] ."#,
+        );
+        let world = crate::World::with_search_path(&[dir.path()]);
+        let plugin = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-amp")
+            .expect("Plugin not found.");
+        assert!(plugin.in_place_broken());
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+
+        // Hosts can get overlapping buffers from outside of Rust, for example
+        // when one buffer is used for both the input and the output.
+        let mut buffer = vec![1.0f32; 96];
+        let ptr = buffer.as_mut_ptr();
+        let (input, output) = unsafe {
+            (
+                std::slice::from_raw_parts(ptr, 64),
+                std::slice::from_raw_parts_mut(ptr.add(32), 64),
+            )
+        };
+        let ports = crate::EmptyPortConnections::new()
+            .with_audio_inputs(std::iter::once(input))
+            .with_audio_outputs(std::iter::once(output));
+        assert_eq!(
+            unsafe { instance.run(64, ports) },
+            Err(RunError::InPlaceNotSupported)
+        );
+        assert!(buffer.iter().all(|x| *x == 1.0));
+
+        let input = [1.0f32; 64];
+        let mut output = [0.0f32; 64];
+        let ports = crate::EmptyPortConnections::new()
+            .with_audio_inputs(std::iter::once(&input[..]))
+            .with_audio_outputs(std::iter::once(&mut output[..]));
+        assert_eq!(unsafe { instance.run(64, ports) }, Ok(()));
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_set_block_length_bounds() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder {
+            min_block_length: 2,
+            max_block_length: 256,
+            ..crate::features::FeaturesBuilder::default()
+        });
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features.clone(), 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        assert_eq!(
+            instance.set_block_length_bounds(64, 32),
+            Err(BlockLengthError::InvalidBounds { min: 64, max: 32 })
+        );
+        // The features are powers of 2 so plugins are given
+        // `buf-size:powerOf2BlockLength`.
+        assert_eq!(
+            instance.set_block_length_bounds(48, 128),
+            Err(BlockLengthError::NotPowerOf2 { actual: 48 })
+        );
+        // EPiano does not implement `options:interface` so it only knows about
+        // the block lengths it was instantiated with.
+        assert_eq!(
+            instance.set_block_length_bounds(32, 1024),
+            Err(BlockLengthError::TooLarge {
+                max_supported: 256,
+                actual: 1024
+            })
+        );
+        assert_eq!(
+            instance.set_block_length_bounds(1, 128),
+            Err(BlockLengthError::TooSmall {
+                min_supported: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(instance.set_block_length_bounds(32, 128), Ok(()));
+        // The plugin was not told about the new bounds.
+        let urid = |uri: &[u8]| features.urid(CStr::from_bytes_with_nul(uri).unwrap());
+        assert_eq!(
+            instance.provided_option(urid(b"http://lv2plug.in/ns/ext/buf-size#maxBlockLength\0")),
+            Some(OptionValue::Int(256))
+        );
+        assert_eq!(
+            instance.set_block_length(16),
+            Err(BlockLengthError::TooSmall {
+                min_supported: 32,
+                actual: 16
+            })
+        );
+        assert_eq!(instance.set_block_length(64), Ok(()));
+        assert_eq!(instance.set_block_length_bounds(32, 256), Ok(()));
+    }
+
+    #[test]
+    fn test_set_block_length_bounds_with_fixed_block_length() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder {
+            min_block_length: 64,
+            max_block_length: 64,
+            ..crate::features::FeaturesBuilder::default()
+        });
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        assert_eq!(
+            instance.set_block_length_bounds(32, 64),
+            Err(BlockLengthError::NotFixed {
+                length: 64,
+                min: 32,
+                max: 64
+            })
+        );
+        assert_eq!(
+            instance.set_block_length_bounds(32, 32),
+            Err(BlockLengthError::NotFixed {
+                length: 64,
+                min: 32,
+                max: 32
+            })
+        );
+        assert_eq!(instance.set_block_length_bounds(64, 64), Ok(()));
+    }
+
+    #[test]
+    fn test_set_block_length() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder {
+            min_block_length: 16,
+            max_block_length: 256,
+            ..crate::features::FeaturesBuilder::default()
+        });
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        assert_eq!(instance.nominal_block_length(), None);
+        assert_eq!(instance.set_block_length(100), Ok(()));
+        assert_eq!(instance.nominal_block_length(), Some(100));
+        assert_eq!(
+            instance.set_block_length(8),
+            Err(BlockLengthError::TooSmall {
+                min_supported: 16,
+                actual: 8
+            })
+        );
+        assert_eq!(
+            instance.set_block_length(512),
+            Err(BlockLengthError::TooLarge {
+                max_supported: 256,
+                actual: 512
+            })
+        );
+        assert_eq!(instance.nominal_block_length(), Some(100));
+    }
+
+    #[test]
+    fn test_set_random_seed_with_wide_range() {
+        let dir = tempfile::tempdir().unwrap();
+        crate::tests::write_test_bundle(
+            dir.path(),
+            "seed-amp.lv2",
+            "http://lv2plug.in/plugins/eg-amp",
+            r#"<http://lv2plug.in/plugins/eg-amp>
+    a lv2:Plugin ;
+    doap:name "Seed Amp" ;
+    lv2:port [
+        a lv2:InputPort, lv2:ControlPort ;
+        lv2:index 0 ;
+        lv2:symbol "seed" ;
+        lv2:name "Seed" ;
+        lv2:default 0.0 ;
+        lv2:minimum -3.0e38 ;
+        lv2:maximum 3.0e38
+    ] , [
+        a lv2:InputPort, lv2:AudioPort ;
+        lv2:index 1 ;
+        lv2:symbol "in" ;
+        lv2:name "In"
+    ] , [
+        a lv2:OutputPort, lv2:AudioPort ;
+        lv2:index 2 ;
+        lv2:symbol "out" ;
+        lv2:name "Out"
+    ] ."#,
+        );
+        let world = crate::World::with_search_path(&[dir.path()]);
+        let plugin = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-amp")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        assert_eq!(instance.set_random_seed(u64::MAX), Some(PortIndex(0)));
+        let value = instance.control_input(PortIndex(0)).unwrap();
+        assert!((-3.0e38..=3.0e38).contains(&value), "{}", value);
+    }
+
+    #[test]
+    fn test_set_random_seed_without_seed_control() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        assert_eq!(instance.set_random_seed(42), None);
+    }
+
+    #[test]
+    fn test_designated_port() {
+        let world = crate::World::new();
+        let sampler = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-sampler")
+            .expect("Plugin not found.");
+        assert_eq!(
+            sampler.designated_port("http://lv2plug.in/ns/lv2core#control"),
+            Some(PortIndex(0))
+        );
+        assert_eq!(
+            sampler.designated_port("http://lv2plug.in/ns/lv2core#enabled"),
+            None
+        );
+        let epiano = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        assert!(!epiano.has_latency_port());
+        assert_eq!(
+            epiano.designated_port("http://lv2plug.in/ns/lv2core#latency"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_set_enabled_without_enabled_port() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        let controls: Vec<_> = plugin
+            .ports_with_type(PortType::ControlInput)
+            .map(|p| instance.control_input(p.index))
+            .collect();
+        assert!(!instance.set_enabled(false));
+        let controls_after: Vec<_> = plugin
+            .ports_with_type(PortType::ControlInput)
+            .map(|p| instance.control_input(p.index))
+            .collect();
+        assert_eq!(controls, controls_after);
+    }
+
+    #[test]
+    fn test_set_enabled() {
+        let dir = tempfile::tempdir().unwrap();
+        crate::tests::write_test_bundle(
+            dir.path(),
+            "enabled-amp.lv2",
+            "http://lv2plug.in/plugins/eg-amp",
+            r#"<http://lv2plug.in/plugins/eg-amp>
+    a lv2:Plugin ;
+    doap:name "Enabled Amp" ;
+    lv2:port [
+        a lv2:InputPort, lv2:ControlPort ;
+        lv2:index 0 ;
+        lv2:symbol "gain" ;
+        lv2:name "Gain" ;
+        lv2:default 0.0 ;
+        lv2:minimum -90.0 ;
+        lv2:maximum 24.0
+    ] , [
+        a lv2:InputPort, lv2:AudioPort ;
+        lv2:index 1 ;
+        lv2:symbol "in" ;
+        lv2:name "In"
+    ] , [
+        a lv2:OutputPort, lv2:AudioPort ;
+        lv2:index 2 ;
+        lv2:symbol "out" ;
+        lv2:name "Out"
+    ] , [
+        a lv2:InputPort, lv2:ControlPort ;
+        lv2:index 3 ;
+        lv2:symbol "enabled" ;
+        lv2:name "Enabled" ;
+        lv2:designation lv2:enabled ;
+        lv2:portProperty lv2:toggled ;
+        lv2:default 1 ;
+        lv2:minimum 0 ;
+        lv2:maximum 1
+    ] ."#,
+        );
+        let world = crate::World::with_search_path(&[dir.path()]);
+        let plugin = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-amp")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        assert_eq!(instance.control_input(PortIndex(3)), Some(1.0));
+        assert!(instance.set_enabled(false));
+        assert_eq!(instance.control_input(PortIndex(3)), Some(0.0));
+        assert!(instance.set_enabled(true));
+        assert_eq!(instance.control_input(PortIndex(3)), Some(1.0));
+        assert_eq!(instance.control_input(PortIndex(0)), Some(0.0));
+    }
+
+    #[test]
+    fn test_control_outputs_are_updated_by_run() {
+        let world = crate::World::new();
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let block_size = 64;
+        let plugins = world.iter_plugins().filter(|p| {
+            p.port_counts().control_outputs > 0
+                && (p.uri().starts_with("http://lv2plug.in/plugins/")
+                    || p.uri().starts_with("http://drobilla.net/plugins/mda/"))
+        });
+        for plugin in plugins {
+            let mut instance = unsafe {
+                plugin
+                    .instantiate(features.clone(), 44100.0)
+                    .expect("Could not instantiate plugin.")
+            };
+            // Poison the control outputs so that the test can tell that the
+            // plugin wrote them.
+            let outputs: Vec<PortIndex> = plugin
+                .ports_with_type(PortType::ControlOutput)
+                .map(|p| p.index)
+                .collect();
+            for index in outputs.iter() {
+                unsafe { *instance.control_outputs.value_mut_ptr(*index).unwrap() = f32::NAN };
+            }
+            let port_counts = *plugin.port_counts();
+            let audio_in = vec![0.5; port_counts.audio_inputs * block_size];
+            let mut audio_out = vec![0.0; port_counts.audio_outputs * block_size];
+            let cv_in = vec![0.0; port_counts.cv_inputs * block_size];
+            let mut cv_out = vec![0.0; port_counts.cv_outputs * block_size];
+            let atom_in: Vec<_> = (0..port_counts.atom_sequence_inputs)
+                .map(|_| crate::event::LV2AtomSequence::new(&features, 1024))
+                .collect();
+            let ports = crate::EmptyPortConnections::new()
+                .with_audio_inputs(audio_in.chunks_exact(block_size))
+                .with_audio_outputs(audio_out.chunks_exact_mut(block_size))
+                .with_atom_sequence_inputs(atom_in.iter())
+                .with_cv_inputs(cv_in.chunks_exact(block_size))
+                .with_cv_outputs(cv_out.chunks_exact_mut(block_size));
+            unsafe { instance.run(block_size, ports).unwrap() };
+            for index in outputs {
+                let value = instance.control_output(index).unwrap();
+                assert!(!value.is_nan(), "{} {:?}", plugin.uri(), index);
+            }
+        }
+    }
+
+    #[test]
+    fn test_lv2_handle_and_extension_data() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-sampler")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        assert!(!unsafe { instance.lv2_handle() }.is_null());
+        assert!(
+            unsafe { instance.extension_data("http://lv2plug.in/ns/ext/worker#interface") }
+                .is_some()
+        );
+        assert!(
+            unsafe { instance.extension_data("http://example.com/not-an-extension") }.is_none()
+        );
+    }
+
+    #[test]
+    fn test_latency_samples_is_zero_without_latency_port() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features.clone(), 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        assert_eq!(instance.latency_samples(), 0);
+        let mut outputs = [vec![0.0; 64], vec![0.0; 64]];
+        let input = crate::event::LV2AtomSequence::new(&features, 1024);
+        let ports = crate::EmptyPortConnections::new()
+            .with_atom_sequence_inputs(std::iter::once(&input))
+            .with_audio_outputs(outputs.iter_mut().map(|o| o.as_mut_slice()));
+        unsafe { instance.run(64, ports).unwrap() };
+        assert_eq!(instance.latency_samples(), 0);
+    }
+
+    #[test]
+    fn test_reset_restores_default_controls() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        assert_eq!(instance.set_control_input(PortIndex(0), 0.1), Some(0.1));
+        unsafe { instance.reset() };
+        assert_eq!(instance.control_input(PortIndex(0)), Some(0.5));
+    }
+
+    #[test]
+    fn test_reset_releases_notes() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        let note_on = [(0, [0x90, 0x40, 0x7F])];
+        let outputs = unsafe { instance.render(&note_on, 256).unwrap() };
+        assert!(outputs.iter().flatten().any(|s| *s != 0.0));
+
+        unsafe { instance.reset() };
+        let no_events: [(u64, [u8; 3]); 0] = [];
+        let outputs = unsafe { instance.render(&no_events, 256).unwrap() };
+        assert!(outputs.iter().flatten().all(|s| *s == 0.0));
+    }
+
+    #[test]
+    fn test_reset_does_not_update_output_peaks() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        let note_on = [(0, [0x90, 0x40, 0x7F])];
+        unsafe { instance.render(&note_on, 256).unwrap() };
+        let peaks = instance.last_output_peaks().to_vec();
+        assert!(peaks.iter().any(|p| *p != 0.0));
+
+        // The block that releases the notes is not a run of the user.
+        unsafe { instance.reset() };
+        assert_eq!(instance.last_output_peaks(), peaks.as_slice());
+        assert!(!instance.state_changed_since_last_check());
+    }
+
+    #[test]
+    fn test_reset_sends_all_notes_off() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-fifths")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        unsafe { instance.reset() };
+        // eg-fifths forwards MIDI messages that are not notes to its output,
+        // which is not connected and goes to the sink of the instance.
+        let messages: Vec<Vec<u8>> = instance.atom_sequence_output_sinks[0]
+            .iter()
+            .map(|e| e.data.to_vec())
+            .collect();
+        let want: Vec<Vec<u8>> = (0..16u8)
+            .flat_map(|channel| [vec![0xB0 | channel, 0x78, 0], vec![0xB0 | channel, 0x7B, 0]])
+            .collect();
+        assert_eq!(messages, want);
+    }
+
+    #[test]
+    fn test_deactivate_and_activate() {
+        // eg-amp implements `deactivate`, so it can be deactivated.
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-amp")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        assert!(instance.is_active());
+        assert_eq!(instance.set_control_input(PortIndex(0), 0.1), Some(0.1));
+        unsafe { instance.deactivate() };
+        assert!(!instance.is_active());
+
+        let input = vec![0.0; 64];
+        let mut output = vec![0.0; 64];
+        let ports = crate::EmptyPortConnections::new()
+            .with_audio_inputs(std::iter::once(input.as_slice()))
+            .with_audio_outputs(std::iter::once(output.as_mut_slice()));
+        assert_eq!(
+            unsafe { instance.run(64, ports) },
+            Err(RunError::InstanceNotActive)
+        );
+
+        unsafe { instance.activate() };
+        assert!(instance.is_active());
+        assert_eq!(instance.control_input(PortIndex(0)), Some(0.1));
+        let ports = crate::EmptyPortConnections::new()
+            .with_audio_inputs(std::iter::once(input.as_slice()))
+            .with_audio_outputs(std::iter::once(output.as_mut_slice()));
+        unsafe { instance.run(64, ports).unwrap() };
+
+        // Dropping a deactivated instance frees it without deactivating it
+        // again.
+        unsafe { instance.deactivate() };
+        drop(instance);
+    }
+
+    #[test]
+    fn test_last_output_peaks() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-amp")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        assert_eq!(instance.last_output_peaks(), &[0.0]);
+
+        let mut input = vec![0.25; 64];
+        input[10] = -0.5;
+        let mut output = vec![0.0; 64];
+        let ports = crate::EmptyPortConnections::new()
+            .with_audio_inputs(std::iter::once(input.as_slice()))
+            .with_audio_outputs(std::iter::once(output.as_mut_slice()));
+        unsafe { instance.run(64, ports).unwrap() };
+        assert_eq!(instance.last_output_peaks(), &[0.5]);
+    }
+
+    #[test]
+    fn test_validate_ports() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let instance = unsafe {
+            plugin
+                .instantiate(features.clone(), 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        let mut outputs = [vec![0.0; 64], vec![0.0; 64]];
+        let input = crate::event::LV2AtomSequence::new(&features, 1024);
+
+        let ports = crate::EmptyPortConnections::new()
+            .with_atom_sequence_inputs(std::iter::once(&input))
+            .with_audio_outputs(outputs.iter_mut().map(|o| o.as_mut_slice()));
+        assert_eq!(instance.validate_ports(64, &ports), Ok(()));
+        assert_eq!(
+            instance.validate_ports(0, &ports),
+            Err(RunError::SampleCountTooSmall {
+                min_supported: 1,
+                actual: 0
+            })
+        );
+
+        let ports = crate::EmptyPortConnections::new()
+            .with_atom_sequence_inputs(std::iter::once(&input))
+            .with_audio_outputs(outputs[1..].iter_mut().map(|o| o.as_mut_slice()));
+        assert_eq!(
+            instance.validate_ports(64, &ports),
+            Err(RunError::AudioOutputsSizeMismatch {
+                expected: 2,
+                actual: 1
+            })
+        );
+
+        // The lengths of the buffers are only checked by `run`.
+        let mut short_outputs = [vec![0.0; 32], vec![0.0; 32]];
+        let ports = crate::EmptyPortConnections::new()
+            .with_atom_sequence_inputs(std::iter::once(&input))
+            .with_audio_outputs(short_outputs.iter_mut().map(|o| o.as_mut_slice()));
+        assert_eq!(instance.validate_ports(64, &ports), Ok(()));
+    }
+
+    #[test]
+    fn test_control_port_connections() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features.clone(), 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        let control_inputs = plugin.port_counts().control_inputs;
+        let mut values = vec![0.5; control_inputs];
+        values[0] = 0.1;
+        let mut outputs = [vec![0.0; 64], vec![0.0; 64]];
+        let input = crate::event::LV2AtomSequence::new(&features, 1024);
+
+        let ports = crate::EmptyPortConnections::new()
+            .with_atom_sequence_inputs(std::iter::once(&input))
+            .with_audio_outputs(outputs.iter_mut().map(|o| o.as_mut_slice()))
+            .with_control_inputs(&values);
+        unsafe { instance.run(64, ports).unwrap() };
+        assert_eq!(instance.control_input(PortIndex(0)), Some(0.1));
+
+        let ports = crate::EmptyPortConnections::new()
+            .with_atom_sequence_inputs(std::iter::once(&input))
+            .with_audio_outputs(outputs.iter_mut().map(|o| o.as_mut_slice()))
+            .with_control_inputs(&values[1..]);
+        assert_eq!(
+            unsafe { instance.run(64, ports) },
+            Err(RunError::ControlInputsSizeMismatch {
+                expected: control_inputs,
+                actual: control_inputs - 1
+            })
+        );
+
+        let mut control_output = [0.0];
+        let ports = crate::EmptyPortConnections::new()
+            .with_atom_sequence_inputs(std::iter::once(&input))
+            .with_audio_outputs(outputs.iter_mut().map(|o| o.as_mut_slice()))
+            .with_control_outputs(&mut control_output);
+        assert_eq!(
+            unsafe { instance.run(64, ports) },
+            Err(RunError::ControlOutputsSizeMismatch {
+                expected: 0,
+                actual: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_render_midi_events() {
+        let world = crate::World::new();
+        let features = world.build_features(crate::features::FeaturesBuilder {
+            min_block_length: 1,
+            max_block_length: 64,
+            ..crate::features::FeaturesBuilder::default()
+        });
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        let events = [(10, [0x90, 0x40, 0x7F]), (150, [0x80, 0x40, 0x00])];
+        let outputs = unsafe { instance.render(&events, 200).unwrap() };
+        assert_eq!(outputs.len(), 2);
+        for output in outputs.iter() {
+            assert_eq!(output.len(), 200);
+            assert!(output[..10].iter().all(|s| *s == 0.0));
+            assert!(output[10..].iter().any(|s| *s != 0.0));
+        }
+    }
+
+    #[test]
+    fn test_render_events_that_do_not_fit() {
+        let world = crate::World::new();
+        let features = world.build_features(crate::features::FeaturesBuilder {
+            min_block_length: 1,
+            max_block_length: 64,
+            ..crate::features::FeaturesBuilder::default()
+        });
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        let events = [(0, vec![0xF0; 1 << 17])];
+        assert!(matches!(
+            unsafe { instance.render(&events, 64) },
+            Err(RunError::MidiEventsFailed { .. })
+        ));
+    }
+
+    #[test]
+    fn test_run_with_transport() {
+        let world = crate::World::new();
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let plugin = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-metro")
+            .expect("Plugin not found.");
+        let render = |speed: f32| {
+            let mut instance = unsafe {
+                plugin
+                    .instantiate(features.clone(), 44100.0)
+                    .expect("Could not instantiate plugin.")
+            };
+            let transport = crate::TransportState {
+                speed,
+                ..crate::TransportState::default()
+            };
+            let input = crate::event::LV2AtomSequence::new(&features, 1024);
+            let mut output = vec![0.0; 256];
+            let ports = crate::EmptyPortConnections::new()
+                .with_atom_sequence_inputs(std::iter::once(&input))
+                .with_audio_outputs(std::iter::once(output.as_mut_slice()));
+            unsafe { instance.run_with_transport(256, ports, &transport) }.unwrap();
+            output
+        };
+        assert!(render(1.0).iter().any(|s| *s != 0.0));
+        assert!(render(0.0).iter().all(|s| *s == 0.0));
+    }
+
+    #[test]
+    fn test_run_without_atom_sequence_outputs() {
+        let world = crate::World::new();
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let plugin = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-sampler")
+            .expect("Plugin not found.");
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features.clone(), 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        let input = crate::event::LV2AtomSequence::new(&features, 1024);
+        let mut outputs = vec![vec![0.0; 64]; plugin.port_counts().audio_outputs];
+        let ports = crate::EmptyPortConnections::new()
+            .with_atom_sequence_inputs(std::iter::once(&input))
+            .with_audio_outputs(outputs.iter_mut().map(|o| o.as_mut_slice()));
+        unsafe { instance.run(64, ports).unwrap() };
+    }
+
+    #[test]
+    fn test_save_and_restore_state() {
+        let world = crate::World::new();
+        let features = world.build_features(crate::features::FeaturesBuilder {
+            map_path: Some(crate::PathMapping::with_directory("/")),
+            ..crate::features::FeaturesBuilder::default()
+        });
+        let plugin = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-sampler")
+            .expect("Plugin not found.");
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features.clone(), 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        let state = unsafe { instance.save_state().unwrap() };
+        let sample_key = features.urid(
+            std::ffi::CStr::from_bytes_with_nul(b"http://lv2plug.in/plugins/eg-sampler#sample\0")
+                .unwrap(),
+        );
+        assert!(state.get(sample_key).is_some());
+
+        let mut restored = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        unsafe { restored.restore_state(&state).unwrap() };
+        assert_eq!(unsafe { restored.save_state().unwrap() }, state);
+    }
+
+    /// Write a short sample that eg-sampler can load to `dir`.
+    fn write_test_sample(dir: &std::path::Path) -> std::path::PathBuf {
+        let path = dir.join("sample.wav");
+        let mut file = std::fs::File::create(&path).unwrap();
+        let header = wav::Header::new(wav::header::WAV_FORMAT_PCM, 1, 44100, 32);
+        let sample = wav::bit_depth::BitDepth::ThirtyTwoFloat(vec![1.0; 64]);
+        wav::write(header, &sample, &mut file).unwrap();
+        path
+    }
+
+    /// The events that make eg-sampler schedule work to load `sample`.
+    fn sampler_load_events(
+        features: &crate::Features,
+        sample: &std::path::Path,
+    ) -> crate::event::LV2AtomSequence {
+        let urids = features.common_urids();
+        let sample_urid = features.urid(
+            CStr::from_bytes_with_nul(b"http://lv2plug.in/plugins/eg-sampler#sample\0").unwrap(),
+        );
+        let mut message = crate::event::LV2AtomObjectBuilder::new(&urids, urids.patch_set);
+        message
+            .push_path(urids.patch_value, sample.to_str().unwrap())
+            .push_urid(urids.patch_property, sample_urid);
+        let mut events = crate::event::LV2AtomSequence::new(features, 1024);
+        events.push_object(0, &message).unwrap();
+        events
+    }
+
+    /// Run eg-sampler for a block with a note and return its output.
+    unsafe fn run_sampler_note(
+        instance: &mut super::Instance,
+        features: &crate::Features,
+    ) -> Vec<f32> {
+        let mut events = crate::event::LV2AtomSequence::new(features, 1024);
+        events
+            .push_midi_event::<3>(0, features.midi_urid(), &[0x90, 0x40, 0x7F])
+            .unwrap();
+        let mut output = vec![0.0; 64];
+        let ports = crate::EmptyPortConnections::new()
+            .with_atom_sequence_inputs(std::iter::once(&events))
+            .with_audio_outputs(std::iter::once(output.as_mut_slice()));
+        instance.run(64, ports).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_run_with_inline_worker() {
+        let world = crate::World::new();
+        let features = world.build_features(crate::features::FeaturesBuilder {
+            min_block_length: 1,
+            max_block_length: 64,
+            run_worker_thread: false,
+            ..crate::features::FeaturesBuilder::default()
+        });
+        let plugin = world
+            .plugin_by_uri("http://lv2plug.in/plugins/eg-sampler")
+            .expect("Plugin not found.");
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features.clone(), 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let load = sampler_load_events(&features, &write_test_sample(dir.path()));
+        let mut output = vec![0.0; 64];
+        let ports = crate::EmptyPortConnections::new()
+            .with_atom_sequence_inputs(std::iter::once(&load))
+            .with_audio_outputs(std::iter::once(output.as_mut_slice()));
+        unsafe {
+            instance
+                .run_with_inline_worker(64, ports, features.worker_manager())
+                .unwrap()
         };
-        let range = p.range();
-        Port {
-            port_type,
-            name: p
-                .name()
-                .expect("port has no name")
-                .as_str()
-                .unwrap_or("BAD_NAME")
-                .to_string(),
-            symbol: p
-                .symbol()
-                .expect("port has no symbol")
-                .as_str()
-                .unwrap_or("BAD_SYMBOL")
-                .to_string(),
-            default_value: node_to_value(&range.default),
-            min_value: range.minimum.map(|n| node_to_value(&Some(n))),
-            max_value: range.maximum.map(|n| node_to_value(&Some(n))),
-            index: PortIndex(p.index()),
-        }
-    })
-}
+        // The sample was loaded and the response was applied before returning,
+        // so the next block plays it.
+        let output = unsafe { run_sampler_note(&mut instance, &features) };
+        assert!(output.iter().any(|x| *x != 0.0));
+    }
 
-fn node_to_value(maybe_node: &Option<lilv::node::Node>) -> f32 {
-    let n = match maybe_node {
-        Some(n) => n,
-        None => return 0.0,
-    };
-    if n.is_float() {
-        n.as_float().unwrap_or(0.0)
-    } else if n.is_int() {
-        n.as_int().unwrap_or(0) as f32
-    } else if n.as_bool().unwrap_or(false) {
-        1f32
-    } else {
-        0f32
+    #[test]
+    fn test_instantiate_with_controls() {
+        let world = crate::World::new();
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let instance = unsafe {
+            plugin
+                .instantiate_with_controls(features, 44100.0, &[(PortIndex(0), 0.25)])
+                .expect("Could not instantiate plugin.")
+        };
+        assert_eq!(instance.control_input(PortIndex(0)), Some(0.25));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::{Port, PortCounts, PortIndex, PortType};
+    #[test]
+    fn test_apply_preset() {
+        let world = crate::World::new();
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let presets = plugin.presets();
+        assert!(!presets.is_empty());
+        assert!(presets.iter().all(|p| !p.name.is_empty()));
+
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        let preset = presets.last().unwrap();
+        assert!(!preset.port_values().is_empty());
+        unsafe { instance.apply_preset(preset) };
+        for (index, value) in preset.port_values() {
+            assert_eq!(instance.control_input(*index), Some(*value));
+        }
+    }
 
     #[test]
-    fn test_metadata() {
+    fn test_measure_impulse_response() {
         let world = crate::World::new();
+        let features = world.build_features(crate::features::FeaturesBuilder {
+            min_block_length: 1,
+            max_block_length: 64,
+            ..crate::features::FeaturesBuilder::default()
+        });
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/Delay")
+            .expect("Plugin not found.");
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features.clone(), 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        let response = unsafe { instance.measure_impulse_response(1000).unwrap() };
+        assert_eq!(response.len(), 1000);
+
         let plugin = world
             .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
-            .unwrap();
-        assert_eq!(plugin.uri(), "http://drobilla.net/plugins/mda/EPiano");
-        assert_eq!(plugin.name(), "MDA ePiano");
-        assert_eq!(
-            plugin.classes,
-            vec![
-                "Instrument Plugin".to_string(),
-                "Generator Plugin".to_string(),
-                "Plugin".to_string()
-            ]
-        );
-        assert!(plugin.is_instrument());
-        assert_eq!(
-            *plugin.port_counts(),
-            PortCounts {
-                control_inputs: 12,
-                control_outputs: 0,
-                audio_inputs: 0,
-                audio_outputs: 2,
-                atom_sequence_inputs: 1,
-                atom_sequence_outputs: 0,
-                cv_inputs: 0,
-                cv_outputs: 0
-            }
-        );
+            .expect("Plugin not found.");
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features, 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
         assert_eq!(
-            plugin.ports().collect::<Vec<_>>(),
-            vec![
-                Port {
-                    port_type: PortType::ControlInput,
-                    name: "Envelope Decay".to_string(),
-                    symbol: "env_decay".to_string(),
-                    default_value: 0.5,
-                    min_value: Some(0.0),
-                    max_value: Some(1.0),
-                    index: PortIndex(0)
-                },
-                Port {
-                    port_type: PortType::ControlInput,
-                    name: "Envelope Release".to_string(),
-                    symbol: "env_release".to_string(),
-                    default_value: 0.5,
-                    min_value: Some(0.0),
-                    max_value: Some(1.0),
-                    index: PortIndex(1)
-                },
-                Port {
-                    port_type: PortType::ControlInput,
-                    name: "Hardness".to_string(),
-                    symbol: "hardness".to_string(),
-                    default_value: 0.5,
-                    min_value: Some(0.0),
-                    max_value: Some(1.0),
-                    index: PortIndex(2)
-                },
-                Port {
-                    port_type: PortType::ControlInput,
-                    name: "Treble Boost".to_string(),
-                    symbol: "treble_boost".to_string(),
-                    default_value: 0.5,
-                    min_value: Some(0.0),
-                    max_value: Some(1.0),
-                    index: PortIndex(3)
-                },
-                Port {
-                    port_type: PortType::ControlInput,
-                    name: "Modulation".to_string(),
-                    symbol: "modulation".to_string(),
-                    default_value: 0.5,
-                    min_value: Some(0.0),
-                    max_value: Some(1.0),
-                    index: PortIndex(4)
-                },
-                Port {
-                    port_type: PortType::ControlInput,
-                    name: "LFO Rate".to_string(),
-                    symbol: "lfo_rate".to_string(),
-                    default_value: 0.65,
-                    min_value: Some(0.0),
-                    max_value: Some(1.0),
-                    index: PortIndex(5)
-                },
-                Port {
-                    port_type: PortType::ControlInput,
-                    name: "Velocity Sense".to_string(),
-                    symbol: "vel_sense".to_string(),
-                    default_value: 0.25,
-                    min_value: Some(0.0),
-                    max_value: Some(1.0),
-                    index: PortIndex(6)
-                },
-                Port {
-                    port_type: PortType::ControlInput,
-                    name: "Stereo Width".to_string(),
-                    symbol: "stereo_width".to_string(),
-                    default_value: 0.5,
-                    min_value: Some(0.0),
-                    max_value: Some(1.0),
-                    index: PortIndex(7)
-                },
-                Port {
-                    port_type: PortType::ControlInput,
-                    name: "Polyphonic".to_string(),
-                    symbol: "polyphonic".to_string(),
-                    default_value: 1.0,
-                    min_value: Some(0.0),
-                    max_value: Some(1.0),
-                    index: PortIndex(8)
-                },
-                Port {
-                    port_type: PortType::ControlInput,
-                    name: "Fine Tuning".to_string(),
-                    symbol: "fine_tuning".to_string(),
-                    default_value: 0.5,
-                    min_value: Some(0.0),
-                    max_value: Some(1.0),
-                    index: PortIndex(9)
-                },
-                Port {
-                    port_type: PortType::ControlInput,
-                    name: "Random Tuning".to_string(),
-                    symbol: "random_tuning".to_string(),
-                    default_value: 0.146,
-                    min_value: Some(0.0),
-                    max_value: Some(1.0),
-                    index: PortIndex(10)
-                },
-                Port {
-                    port_type: PortType::ControlInput,
-                    name: "Overdrive".to_string(),
-                    symbol: "overdrive".to_string(),
-                    default_value: 0.0,
-                    min_value: Some(0.0),
-                    max_value: Some(1.0),
-                    index: PortIndex(11)
-                },
-                Port {
-                    port_type: PortType::AudioOutput,
-                    name: "Left Out".to_string(),
-                    symbol: "left_out".to_string(),
-                    default_value: 0.0,
-                    min_value: None,
-                    max_value: None,
-                    index: PortIndex(12)
-                },
-                Port {
-                    port_type: PortType::AudioOutput,
-                    name: "Right Out".to_string(),
-                    symbol: "right_out".to_string(),
-                    default_value: 0.0,
-                    min_value: None,
-                    max_value: None,
-                    index: PortIndex(13)
-                },
-                Port {
-                    port_type: PortType::AtomSequenceInput,
-                    name: "Event In".to_string(),
-                    symbol: "event_in".to_string(),
-                    default_value: 0.0,
-                    min_value: None,
-                    max_value: None,
-                    index: PortIndex(14)
-                },
-            ]
+            unsafe { instance.measure_impulse_response(1000) }.err(),
+            Some(crate::error::RunError::AudioInputsSizeMismatch {
+                expected: 1,
+                actual: 0
+            })
         );
     }
 
@@ -765,6 +4903,7 @@ mod tests {
         let features = world.build_features(crate::features::FeaturesBuilder {
             min_block_length: block_size,
             max_block_length: block_size,
+            ..crate::features::FeaturesBuilder::default()
         });
         let mut instance = unsafe {
             plugin
@@ -800,6 +4939,7 @@ mod tests {
         let features = world.build_features(crate::features::FeaturesBuilder {
             min_block_length: supported_block_size.0,
             max_block_length: supported_block_size.1,
+            ..crate::features::FeaturesBuilder::default()
         });
         let mut instance = unsafe {
             plugin
@@ -816,6 +4956,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn run_padded_accepts_sample_count_smaller_than_supported_block_size() {
+        let world = crate::World::new();
+        let plugin = world
+            .plugin_by_uri("http://drobilla.net/plugins/mda/EPiano")
+            .expect("Plugin not found.");
+        let features = world.build_features(crate::features::FeaturesBuilder {
+            min_block_length: 512,
+            max_block_length: 1024,
+            ..crate::features::FeaturesBuilder::default()
+        });
+        let mut instance = unsafe {
+            plugin
+                .instantiate(features.clone(), 44100.0)
+                .expect("Could not instantiate plugin.")
+        };
+        let input = crate::event::LV2AtomSequence::new(&features, 1024);
+        let mut outputs = [vec![0.0; 256], vec![0.0; 256]];
+        let ports = crate::EmptyPortConnections::new()
+            .with_atom_sequence_inputs(std::iter::once(&input))
+            .with_audio_outputs(outputs.iter_mut().map(|o| o.as_mut_slice()));
+        assert_eq!(unsafe { instance.run_padded(256, ports) }, Ok(256));
+    }
+
     #[test]
     fn sample_count_larger_than_supported_block_size_produces_error() {
         let world = crate::World::new();
@@ -827,6 +4991,7 @@ mod tests {
         let features = world.build_features(crate::features::FeaturesBuilder {
             min_block_length: supported_block_size.0,
             max_block_length: supported_block_size.1,
+            ..crate::features::FeaturesBuilder::default()
         });
         let mut instance = unsafe {
             plugin
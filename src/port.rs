@@ -1,4 +1,5 @@
 use crate::event::LV2AtomSequence;
+use crate::PortUnit;
 
 /// The type of IO for the port. Either input or output.
 #[derive(Copy, Clone, Debug)]
@@ -66,26 +67,221 @@ pub struct PortIndex(pub usize);
 /// A port represents a connection (either input or output) to a plugin.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Port {
-    /// The type of port.
+    /// The type of port. Ports of the morph extension have the single data
+    /// type that they declare. This is their default type since
+    /// `morph:interface` is not provided to plugins.
     pub port_type: PortType,
 
     /// The name of the port.
     pub name: String,
 
-    /// The symbol of the port.
+    /// The symbol of the port, as declared by `lv2:symbol`. Unlike `name`, the
+    /// symbol is unique within the plugin and stable, so it should be used as
+    /// the key when saving control values.
     pub symbol: String,
 
     /// The default value for the port if it is a `ControlInputs`.
+    ///
+    /// This is the value as written in the plugin's data. If the port has the
+    /// `lv2:sampleRate` property, it is a multiple of the sample rate. Use
+    /// `default_value_at` to get the actual default.
     pub default_value: f32,
 
-    /// The minimum value allowed for the port.
+    /// The minimum value allowed for the port. Like `default_value`, this is a
+    /// multiple of the sample rate if the port has the `lv2:sampleRate`
+    /// property.
     pub min_value: Option<f32>,
 
-    /// The maximum value allowed for the port.
+    /// The maximum value allowed for the port. Like `default_value`, this is a
+    /// multiple of the sample rate if the port has the `lv2:sampleRate`
+    /// property.
     pub max_value: Option<f32>,
 
     /// The index of this port within the plugin.
     pub index: PortIndex,
+
+    /// The LV2 port properties of the port.
+    pub properties: PortProperties,
+
+    /// `true` if the port is a control port that is also a `lv2:CVPort` or a
+    /// `mod:CVPort`. Such ports are connected as control ports, but the plugin
+    /// also accepts a CV signal for them.
+    pub cv_capable: bool,
+
+    /// The URIs of the atom types that an atom port supports, as declared by
+    /// `atom:supports`. See `supported_atom_types`.
+    pub(crate) atom_types: Vec<String>,
+
+    /// The unit of the port's value, as declared by `units:unit`.
+    pub unit: Option<PortUnit>,
+
+    /// The named values of the port, as declared by `lv2:scalePoint`. Hosts
+    /// may show these as a list of choices instead of a slider.
+    pub scale_points: Vec<ScalePoint>,
+
+    /// The importance of the port, as declared by `pprops:displayPriority`.
+    /// See `display_priority`.
+    pub(crate) display_priority: Option<i32>,
+
+    /// The description of the port, as declared by `rdfs:comment`. Hosts may
+    /// show this as help text, for example in a tooltip.
+    pub comment: Option<String>,
+}
+
+impl Port {
+    /// Returns `true` if the port has the `pprops:trigger` property. The value
+    /// of a trigger port is only set for a single block, like a momentary
+    /// event, and then returns to its default value. Hosts should not hold the
+    /// value of a trigger output, for example in a meter.
+    pub fn is_trigger(&self) -> bool {
+        self.properties.trigger
+    }
+
+    /// Returns `true` if the port is a control input that is suitable for
+    /// realtime automation. Ports with the `pprops:expensive` or
+    /// `pprops:notOnGUI` property are better set once, for example as part of
+    /// the plugin's configuration.
+    pub fn is_automatable(&self) -> bool {
+        matches!(self.port_type, PortType::ControlInput)
+            && !self.properties.expensive
+            && !self.properties.not_on_gui
+    }
+
+    /// Returns `true` if the port can be driven by a CV signal. This is `true`
+    /// for CV ports and for control ports that are also declared as CV ports.
+    /// Hosts may drive the latter per sample while a modulator is connected
+    /// and per block otherwise.
+    pub fn supports_cv(&self) -> bool {
+        self.cv_capable || matches!(self.port_type, PortType::CVInput | PortType::CVOutput)
+    }
+
+    /// The URIs of the atom types that the port supports, as declared by
+    /// `atom:supports`. Hosts should only send events of these types to the
+    /// port, for example MIDI events to a port that supports
+    /// `http://lv2plug.in/ns/ext/midi#MidiEvent`.
+    pub fn supported_atom_types(&self) -> Vec<String> {
+        self.atom_types.clone()
+    }
+
+    /// The display priority of the port as declared by
+    /// `pprops:displayPriority`. Ports with a higher priority are more
+    /// important and should be shown more prominently. Returns `None` if the
+    /// port does not declare a priority.
+    pub fn display_priority(&self) -> Option<i32> {
+        self.display_priority
+    }
+
+    /// Format `value` for display. The `render` string or the symbol of the
+    /// port's unit is used if it is available, for example `-6.0 dB` for
+    /// `%.1f dB` or `440 Hz` for `Hz`. Otherwise, the value is formatted as a
+    /// plain number.
+    pub fn format_value(&self, value: f32) -> String {
+        self.unit
+            .as_ref()
+            .and_then(|unit| unit.format_value(value))
+            .unwrap_or_else(|| value.to_string())
+    }
+
+    /// Returns `true` if the port has the `lv2:sampleRate` property. The
+    /// default, minimum, and maximum values of such ports are multiples of the
+    /// sample rate.
+    pub fn is_sample_rate_relative(&self) -> bool {
+        self.properties.sample_rate
+    }
+
+    /// The default value of the port when running at `sample_rate`. This is
+    /// the same as `default_value` unless the port has the `lv2:sampleRate`
+    /// property.
+    pub fn default_value_at(&self, sample_rate: f64) -> f32 {
+        self.default_value * self.value_scale(sample_rate)
+    }
+
+    /// Returns `true` if the port looks like it sets the seed of a random
+    /// number generator.
+    pub(crate) fn is_random_seed(&self) -> bool {
+        self.symbol.to_lowercase().contains("seed") || self.name.to_lowercase().contains("seed")
+    }
+
+    /// The factor to apply to the values in the plugin's data to get the
+    /// actual values when running at `sample_rate`.
+    fn value_scale(&self, sample_rate: f64) -> f32 {
+        if self.properties.sample_rate {
+            sample_rate as f32
+        } else {
+            1.0
+        }
+    }
+}
+
+/// The LV2 port properties of a port.
+/// See http://lv2plug.in/ns/ext/port-props.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct PortProperties {
+    /// The port has the `pprops:trigger` property.
+    pub trigger: bool,
+
+    /// The port has the `lv2:sampleRate` property.
+    pub sample_rate: bool,
+
+    /// The port has the `lv2:toggled` property. The value is either off,
+    /// `0.0`, or on, `1.0`.
+    pub toggled: bool,
+
+    /// The port has the `lv2:integer` property. The value is always rounded to
+    /// an integer.
+    pub integer: bool,
+
+    /// The port has the `pprops:logarithmic` property. Hosts should use a
+    /// logarithmic scale when displaying the value.
+    pub logarithmic: bool,
+
+    /// The port has the `lv2:enumeration` property. The value must be one of
+    /// the port's scale points.
+    pub enumeration: bool,
+
+    /// The port has the `pprops:expensive` property. Changing the value causes
+    /// the plugin to do a lot of work, so it should not be automated.
+    pub expensive: bool,
+
+    /// The port has the `pprops:notOnGUI` property. The port should not be
+    /// shown to the user, although it may still be set by the host.
+    pub not_on_gui: bool,
+}
+
+/// A named value of a port as declared by `lv2:scalePoint`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScalePoint {
+    /// The label of the value.
+    pub label: String,
+
+    /// The value.
+    pub value: f32,
+}
+
+/// A group of ports that belong together as declared by `pg:group`, for
+/// example the left and right channels of a stereo output. See
+/// http://lv2plug.in/ns/ext/port-groups.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PortGroup {
+    /// The URI of the group.
+    pub uri: String,
+
+    /// The symbol of the group, if the plugin declares one.
+    pub symbol: Option<String>,
+
+    /// The ports in the group, sorted by index.
+    pub members: Vec<PortGroupMember>,
+}
+
+/// A port that belongs to a `PortGroup`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PortGroupMember {
+    /// The index of the port.
+    pub index: PortIndex,
+
+    /// The role of the port in the group as declared by `lv2:designation`, for
+    /// example `http://lv2plug.in/ns/ext/port-groups#left`.
+    pub designation: Option<String>,
 }
 
 /// A `PortConnections` object with no connections.
@@ -109,6 +305,9 @@ impl EmptyPortConnections {
             atom_sequence_outputs: std::iter::empty(),
             cv_inputs: std::iter::empty(),
             cv_outputs: std::iter::empty(),
+            control_inputs: &[],
+            control_outputs: &mut [],
+            cv_clamping: false,
         }
     }
 }
@@ -119,7 +318,10 @@ impl Default for EmptyPortConnections {
     }
 }
 
-/// All the inputs and outputs for an instance.
+/// All the inputs and outputs for an instance. The control values and CV
+/// clamping can only be set with `with_control_inputs`,
+/// `with_control_outputs` and `with_cv_clamping`, so an instance with those
+/// set must be built from `EmptyPortConnections::new`.
 pub struct PortConnections<
     'a,
     AudioInputs,
@@ -136,23 +338,45 @@ pub struct PortConnections<
     CVInputs: ExactSizeIterator + Iterator<Item = &'a [f32]>,
     CVOutputs: ExactSizeIterator + Iterator<Item = &'a mut [f32]>,
 {
-    /// The audio inputs.
+    /// The audio inputs. These are connected to the plugin's audio input
+    /// ports in port index order.
     pub audio_inputs: AudioInputs,
 
-    /// The audio outputs.
+    /// The audio outputs. These are connected to the plugin's audio output
+    /// ports in port index order.
     pub audio_outputs: AudioOutputs,
 
-    /// The events input.
+    /// The events input. These are connected to the plugin's atom sequence
+    /// input ports in port index order.
     pub atom_sequence_inputs: AtomSequenceInputs,
 
-    /// The events output.
+    /// The events output. These are connected to the plugin's atom sequence
+    /// output ports in port index order.
     pub atom_sequence_outputs: AtomSequenceOutputs,
 
-    /// The CV inputs.
+    /// The CV inputs. These are connected to the plugin's CV input ports in
+    /// port index order.
     pub cv_inputs: CVInputs,
 
-    /// The CV outputs.
+    /// The CV outputs. These are connected to the plugin's CV output ports in
+    /// port index order.
     pub cv_outputs: CVOutputs,
+
+    /// The values of the control inputs in port index order. If empty, the
+    /// values set with `Instance::set_control_input` are used. Otherwise, the
+    /// values are set on the instance like `Instance::set_control_input`
+    /// before running.
+    pub(crate) control_inputs: &'a [f32],
+
+    /// Receives the values of the control outputs in port index order after
+    /// the plugin is run. If empty, the values can still be read with
+    /// `Instance::control_output`.
+    pub(crate) control_outputs: &'a mut [f32],
+
+    /// If `true`, the CV inputs are clamped to the minimum and maximum values
+    /// of their ports before they are passed to the plugin. The inputs
+    /// themselves are not modified.
+    pub(crate) cv_clamping: bool,
 }
 
 impl<
@@ -184,8 +408,8 @@ where
     /// Returns the number of ports supported by type.
     pub fn port_counts(&self) -> PortCounts {
         PortCounts {
-            control_inputs: 0,
-            control_outputs: 0,
+            control_inputs: self.control_inputs.len(),
+            control_outputs: self.control_outputs.len(),
             audio_inputs: self.audio_inputs.len(),
             audio_outputs: self.audio_outputs.len(),
             atom_sequence_inputs: self.atom_sequence_inputs.len(),
@@ -195,6 +419,17 @@ where
         }
     }
 
+    /// Create an instance of `PortConnections` that clamps the CV inputs to the
+    /// ranges of their ports if `cv_clamping` is `true`. This is useful for
+    /// catching modulation sources that overshoot the range that the plugin
+    /// expects.
+    pub fn with_cv_clamping(self, cv_clamping: bool) -> Self {
+        PortConnections {
+            cv_clamping,
+            ..self
+        }
+    }
+
     /// Create an instance of `PortConnections` with the given audio inputs.
     pub fn with_audio_inputs<I>(
         self,
@@ -218,6 +453,9 @@ where
             atom_sequence_outputs: self.atom_sequence_outputs,
             cv_inputs: self.cv_inputs,
             cv_outputs: self.cv_outputs,
+            control_inputs: self.control_inputs,
+            control_outputs: self.control_outputs,
+            cv_clamping: self.cv_clamping,
         }
     }
 
@@ -244,6 +482,9 @@ where
             atom_sequence_outputs: self.atom_sequence_outputs,
             cv_inputs: self.cv_inputs,
             cv_outputs: self.cv_outputs,
+            control_inputs: self.control_inputs,
+            control_outputs: self.control_outputs,
+            cv_clamping: self.cv_clamping,
         }
     }
 
@@ -262,6 +503,9 @@ where
             atom_sequence_outputs: self.atom_sequence_outputs,
             cv_inputs: self.cv_inputs,
             cv_outputs: self.cv_outputs,
+            control_inputs: self.control_inputs,
+            control_outputs: self.control_outputs,
+            cv_clamping: self.cv_clamping,
         }
     }
 
@@ -280,6 +524,9 @@ where
             atom_sequence_outputs,
             cv_inputs: self.cv_inputs,
             cv_outputs: self.cv_outputs,
+            control_inputs: self.control_inputs,
+            control_outputs: self.control_outputs,
+            cv_clamping: self.cv_clamping,
         }
     }
 
@@ -306,6 +553,9 @@ where
             atom_sequence_outputs: self.atom_sequence_outputs,
             cv_inputs,
             cv_outputs: self.cv_outputs,
+            control_inputs: self.control_inputs,
+            control_outputs: self.control_outputs,
+            cv_clamping: self.cv_clamping,
         }
     }
 
@@ -332,6 +582,27 @@ where
             atom_sequence_outputs: self.atom_sequence_outputs,
             cv_inputs: self.cv_inputs,
             cv_outputs,
+            control_inputs: self.control_inputs,
+            control_outputs: self.control_outputs,
+            cv_clamping: self.cv_clamping,
+        }
+    }
+
+    /// Create an instance of `PortConnections` with the given control input
+    /// values, one for each control input in port index order.
+    pub fn with_control_inputs(self, control_inputs: &'a [f32]) -> Self {
+        PortConnections {
+            control_inputs,
+            ..self
+        }
+    }
+
+    /// Create an instance of `PortConnections` that receives the values of
+    /// the control outputs, one for each control output in port index order.
+    pub fn with_control_outputs(self, control_outputs: &'a mut [f32]) -> Self {
+        PortConnections {
+            control_outputs,
+            ..self
         }
     }
 }
@@ -349,12 +620,72 @@ pub struct PortCounts {
     pub cv_outputs: usize,
 }
 
+/// The channel layout of a set of audio ports.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ChannelLayout {
+    /// A single channel.
+    Mono,
+
+    /// Two channels, left and right.
+    Stereo,
+
+    /// Any other number of channels. This includes `0` for plugins that do not
+    /// have any audio ports, like MIDI effects.
+    Multi(usize),
+}
+
+impl ChannelLayout {
+    /// Get the layout for the given number of channels.
+    pub fn from_channel_count(channels: usize) -> ChannelLayout {
+        match channels {
+            1 => ChannelLayout::Mono,
+            2 => ChannelLayout::Stereo,
+            n => ChannelLayout::Multi(n),
+        }
+    }
+
+    /// The number of channels in the layout.
+    pub fn channel_count(&self) -> usize {
+        match self {
+            ChannelLayout::Mono => 1,
+            ChannelLayout::Stereo => 2,
+            ChannelLayout::Multi(n) => *n,
+        }
+    }
+}
+
+/// The channel layouts of the audio inputs and outputs of a plugin. For
+/// example, a reverb may be mono to stereo and an instrument has no audio
+/// inputs.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct IoLayout {
+    /// The layout of the audio inputs.
+    pub inputs: ChannelLayout,
+
+    /// The layout of the audio outputs.
+    pub outputs: ChannelLayout,
+}
+
+impl IoLayout {
+    /// Get the layout of the audio ports in `counts`.
+    pub fn from_port_counts(counts: &PortCounts) -> IoLayout {
+        IoLayout {
+            inputs: ChannelLayout::from_channel_count(counts.audio_inputs),
+            outputs: ChannelLayout::from_channel_count(counts.audio_outputs),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ControlPort {
     pub port_index: PortIndex,
     pub value: f32,
+    pub default_value: f32,
     pub minimum: f32,
     pub maximum: f32,
+    // Only used to normalize values in `Controls::set`. The properties are
+    // public through `Port::properties`.
+    pub(crate) properties: PortProperties,
 }
 
 /// Controls holds the values of control ports. These are also known as
@@ -365,17 +696,23 @@ pub(crate) struct Controls {
 }
 
 impl Controls {
-    /// Construct a new `Controls` instance from the given ports.
-    pub(crate) fn new<I>(ports: I) -> Controls
+    /// Construct a new `Controls` instance from the given ports. Values that
+    /// are relative to the sample rate are scaled by `sample_rate`.
+    pub(crate) fn new<I>(ports: I, sample_rate: f64) -> Controls
     where
         I: Iterator<Item = Port>,
     {
         let mut controls: Vec<ControlPort> = ports
-            .map(|p| ControlPort {
-                port_index: p.index,
-                value: p.default_value,
-                minimum: p.min_value.unwrap_or(f32::NEG_INFINITY),
-                maximum: p.max_value.unwrap_or(f32::INFINITY),
+            .map(|p| {
+                let scale = p.value_scale(sample_rate);
+                ControlPort {
+                    port_index: p.index,
+                    value: p.default_value_at(sample_rate),
+                    default_value: p.default_value_at(sample_rate),
+                    minimum: p.min_value.map_or(f32::NEG_INFINITY, |v| v * scale),
+                    maximum: p.max_value.map_or(f32::INFINITY, |v| v * scale),
+                    properties: p.properties,
+                }
             })
             .collect();
         controls.sort_by(|a, b| a.port_index.cmp(&b.port_index));
@@ -400,16 +737,78 @@ impl Controls {
         self.controls.len()
     }
 
+    /// Get up to `count` controls starting at position `start`. Positions
+    /// past the end are ignored.
+    pub fn range(&self, start: usize, count: usize) -> &[ControlPort] {
+        let start = start.min(self.controls.len());
+        let end = start.saturating_add(count).min(self.controls.len());
+        &self.controls[start..end]
+    }
+
     /// Set the value of the control at the given index. The value will be
-    /// clamped to the minimum and maximum bounds and returned.
+    /// clamped to the minimum and maximum bounds and returned. Values of
+    /// toggled ports are set to `0.0` or `1.0` and values of integer ports are
+    /// rounded to an integer within the bounds.
     pub fn set(&mut self, port: PortIndex, value: f32) -> Option<f32> {
         let idx = self.port_index_to_index_in_controls(port)?;
+        self.set_at(idx, value)
+    }
+
+    /// Set the value of the control at position `idx`, in port index order,
+    /// like `set`.
+    pub fn set_at(&mut self, idx: usize, value: f32) -> Option<f32> {
         let p = self.controls.get_mut(idx)?;
-        let normalized_value = value.clamp(p.minimum, p.maximum);
+        let normalized_value = if p.properties.toggled {
+            let value: f32 = if value > 0.0 { 1.0 } else { 0.0 };
+            value.clamp(p.minimum, p.maximum)
+        } else if p.properties.integer {
+            // Rounding after clamping keeps the value an integer when a bound
+            // is not. The nearest integer may be just outside of the bounds.
+            let value = value.clamp(p.minimum, p.maximum).round();
+            if value > p.maximum {
+                value - 1.0
+            } else if value < p.minimum {
+                value + 1.0
+            } else {
+                value
+            }
+        } else {
+            value.clamp(p.minimum, p.maximum)
+        };
         p.value = normalized_value;
         Some(normalized_value)
     }
 
+    /// Set the values of several controls like `set`. Returns the number of
+    /// controls that were set. Each search starts after the previously set
+    /// control so sorted `values` are applied in a single pass over the
+    /// controls.
+    pub fn set_sorted(&mut self, values: &[(PortIndex, f32)]) -> usize {
+        let mut set_count = 0;
+        let mut start = 0;
+        for &(port, value) in values {
+            let idx = match self.controls[start..].binary_search_by_key(&port, |p| p.port_index) {
+                Ok(offset) => start + offset,
+                // `values` is not sorted so fall back to searching all controls.
+                Err(_) => match self.port_index_to_index_in_controls(port) {
+                    Some(idx) => idx,
+                    None => continue,
+                },
+            };
+            self.set_at(idx, value);
+            set_count += 1;
+            start = idx;
+        }
+        set_count
+    }
+
+    /// Set all controls to their default values.
+    pub fn reset(&mut self) {
+        for p in self.controls.iter_mut() {
+            p.value = p.default_value;
+        }
+    }
+
     /// Get a pointer to the value of the control at the given index.
     pub fn value_ptr(&self, port: PortIndex) -> Option<*const f32> {
         let idx = self.port_index_to_index_in_controls(port)?;
@@ -417,6 +816,15 @@ impl Controls {
         Some(&p.value)
     }
 
+    /// Get a pointer to the value of the given port that the plugin may write
+    /// to. This is used to connect control outputs. The pointer stays valid as
+    /// long as `self` is alive since the controls are never reallocated.
+    pub fn value_mut_ptr(&mut self, port: PortIndex) -> Option<*mut f32> {
+        let idx = self.port_index_to_index_in_controls(port)?;
+        let p = self.controls.get_mut(idx)?;
+        Some(&mut p.value)
+    }
+
     /// Get the index within the controls vector of the given port index.
     fn port_index_to_index_in_controls(&self, port: PortIndex) -> Option<usize> {
         self.controls
@@ -424,3 +832,201 @@ impl Controls {
             .ok()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_value_at_scales_sample_rate_relative_ports() {
+        let mut port = Port {
+            port_type: PortType::ControlInput,
+            name: "Cutoff".to_string(),
+            symbol: "cutoff".to_string(),
+            default_value: 0.25,
+            min_value: Some(0.0),
+            max_value: Some(0.5),
+            index: PortIndex(0),
+            properties: PortProperties::default(),
+            cv_capable: false,
+            atom_types: Vec::new(),
+            unit: None,
+            scale_points: Vec::new(),
+            display_priority: None,
+            comment: None,
+        };
+        assert_eq!(port.default_value_at(48000.0), 0.25);
+
+        port.properties.sample_rate = true;
+        assert_eq!(port.default_value_at(48000.0), 12000.0);
+        let controls = Controls::new(std::iter::once(port), 48000.0);
+        assert_eq!(controls.get(PortIndex(0)), Some(12000.0));
+        let max = controls.iter_ports().next().unwrap().maximum;
+        assert_eq!(max, 24000.0);
+    }
+
+    #[test]
+    fn test_is_automatable() {
+        let mut port = Port {
+            port_type: PortType::ControlInput,
+            name: "Table Size".to_string(),
+            symbol: "table_size".to_string(),
+            default_value: 1024.0,
+            min_value: None,
+            max_value: None,
+            index: PortIndex(0),
+            properties: PortProperties::default(),
+            cv_capable: false,
+            atom_types: Vec::new(),
+            unit: None,
+            scale_points: Vec::new(),
+            display_priority: None,
+            comment: None,
+        };
+        assert!(port.is_automatable());
+
+        port.properties.expensive = true;
+        assert!(!port.is_automatable());
+
+        port.properties = PortProperties {
+            not_on_gui: true,
+            ..PortProperties::default()
+        };
+        assert!(!port.is_automatable());
+
+        port.properties = PortProperties::default();
+        port.port_type = PortType::ControlOutput;
+        assert!(!port.is_automatable());
+    }
+
+    #[test]
+    fn test_set_toggled_and_integer_controls() {
+        let port = |index: usize, properties: PortProperties| Port {
+            port_type: PortType::ControlInput,
+            name: "Control".to_string(),
+            symbol: format!("control_{}", index),
+            default_value: 0.0,
+            min_value: Some(0.0),
+            max_value: Some(8.0),
+            index: PortIndex(index),
+            properties,
+            cv_capable: false,
+            atom_types: Vec::new(),
+            unit: None,
+            scale_points: Vec::new(),
+            display_priority: None,
+            comment: None,
+        };
+        let toggled = PortProperties {
+            toggled: true,
+            ..PortProperties::default()
+        };
+        let integer = PortProperties {
+            integer: true,
+            ..PortProperties::default()
+        };
+        let mut controls = Controls::new(
+            vec![
+                port(0, toggled),
+                port(1, integer),
+                port(2, PortProperties::default()),
+            ]
+            .into_iter(),
+            44100.0,
+        );
+        assert_eq!(controls.set(PortIndex(0), 0.3), Some(1.0));
+        assert_eq!(controls.set(PortIndex(0), 0.0), Some(0.0));
+        assert_eq!(controls.set(PortIndex(1), 2.6), Some(3.0));
+        assert_eq!(controls.set(PortIndex(1), 9.4), Some(8.0));
+        assert_eq!(controls.set(PortIndex(2), 2.6), Some(2.6));
+
+        let mut bounded = port(0, integer);
+        bounded.min_value = Some(0.5);
+        bounded.max_value = Some(7.5);
+        let mut controls = Controls::new(std::iter::once(bounded), 44100.0);
+        assert_eq!(controls.set(PortIndex(0), 9.0), Some(7.0));
+        assert_eq!(controls.set(PortIndex(0), -3.0), Some(1.0));
+        assert_eq!(controls.set(PortIndex(0), 3.4), Some(3.0));
+
+        let indices =
+            |ports: &[ControlPort]| ports.iter().map(|p| p.port_index).collect::<Vec<_>>();
+        assert_eq!(indices(controls.range(1, 5)), [PortIndex(1), PortIndex(2)]);
+        assert_eq!(indices(controls.range(0, 1)), [PortIndex(0)]);
+        assert!(controls.range(7, usize::MAX).is_empty());
+    }
+
+    #[test]
+    fn test_set_sorted() {
+        let port = |index: usize| Port {
+            port_type: PortType::ControlInput,
+            name: "Control".to_string(),
+            symbol: format!("control_{}", index),
+            default_value: 0.0,
+            min_value: Some(0.0),
+            max_value: Some(8.0),
+            index: PortIndex(index),
+            properties: PortProperties::default(),
+            cv_capable: false,
+            atom_types: Vec::new(),
+            unit: None,
+            scale_points: Vec::new(),
+            display_priority: None,
+            comment: None,
+        };
+        let mut controls = Controls::new(vec![port(1), port(3), port(5)].into_iter(), 44100.0);
+        assert_eq!(
+            controls.set_sorted(&[
+                (PortIndex(1), 1.0),
+                (PortIndex(2), 2.0),
+                (PortIndex(5), 9.0)
+            ]),
+            2
+        );
+        // Unsorted values are also applied.
+        assert_eq!(
+            controls.set_sorted(&[(PortIndex(3), 3.0), (PortIndex(1), 4.0)]),
+            2
+        );
+        assert_eq!(controls.get(PortIndex(1)), Some(4.0));
+        assert_eq!(controls.get(PortIndex(3)), Some(3.0));
+        assert_eq!(controls.get(PortIndex(5)), Some(8.0));
+    }
+
+    #[test]
+    fn test_value_mut_ptr() {
+        let port = Port {
+            port_type: PortType::ControlOutput,
+            name: "Level".to_string(),
+            symbol: "level".to_string(),
+            default_value: 0.0,
+            min_value: Some(0.0),
+            max_value: Some(1.0),
+            index: PortIndex(2),
+            properties: PortProperties::default(),
+            cv_capable: false,
+            atom_types: Vec::new(),
+            unit: None,
+            scale_points: Vec::new(),
+            display_priority: None,
+            comment: None,
+        };
+        let mut controls = Controls::new(std::iter::once(port), 44100.0);
+        assert!(controls.value_mut_ptr(PortIndex(0)).is_none());
+        let ptr = controls.value_mut_ptr(PortIndex(2)).unwrap();
+        // Plugins write control outputs through the connected pointer.
+        unsafe { *ptr = 0.75 };
+        assert_eq!(controls.get(PortIndex(2)), Some(0.75));
+    }
+
+    #[test]
+    fn test_with_cv_clamping() {
+        let cv = [0.0f32; 4];
+        let ports = EmptyPortConnections::new();
+        assert!(!ports.cv_clamping);
+        let ports = ports
+            .with_cv_clamping(true)
+            .with_cv_inputs(std::iter::once(&cv[..]));
+        assert!(ports.cv_clamping);
+        assert_eq!(ports.port_counts().cv_inputs, 1);
+    }
+}
@@ -0,0 +1,141 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::{CommonUris, Features, Port, PortIndex, PortType};
+
+/// The file URIs of the directories that user presets were loaded from.
+pub(crate) type UserPresetDirs = Arc<Mutex<Vec<String>>>;
+
+/// The file URI of the directory at `path`, with a trailing slash.
+pub(crate) fn dir_uri(world: &lilv::World, path: &Path) -> String {
+    let node = world.new_file_uri(None, &path.to_string_lossy());
+    let uri = node.as_uri().unwrap_or("");
+    if uri.ends_with('/') {
+        uri.to_string()
+    } else {
+        format!("{}/", uri)
+    }
+}
+
+/// A preset of a plugin as declared with `pset:Preset`.
+/// See http://lv2plug.in/ns/ext/presets.
+///
+/// Presets are obtained with `Plugin::presets` and applied with
+/// `Instance::apply_preset`.
+#[derive(Clone)]
+pub struct Preset {
+    /// The URI of the preset.
+    pub uri: String,
+
+    /// The name of the preset.
+    pub name: String,
+
+    port_values: Vec<(PortIndex, f32)>,
+    file_uri: Option<String>,
+    world: Arc<lilv::World>,
+}
+
+impl Preset {
+    /// Read the preset from the `preset` node. The resource of the preset must
+    /// already be loaded.
+    pub(crate) fn from_node(
+        world: &Arc<lilv::World>,
+        common_uris: &CommonUris,
+        preset: &lilv::node::Node,
+        ports: &[Port],
+    ) -> Preset {
+        let control_inputs: Vec<&Port> = ports
+            .iter()
+            .filter(|p| p.port_type == PortType::ControlInput)
+            .collect();
+        let port_values = world
+            .find_nodes(Some(preset), &common_uris.lv2_port_uri, None)
+            .iter()
+            .filter_map(|port| {
+                let symbol = world.get(Some(&port), Some(&common_uris.lv2_symbol_uri), None)?;
+                let value = world.get(Some(&port), Some(&common_uris.pset_value_uri), None)?;
+                let value = value
+                    .as_float()
+                    .or_else(|| value.as_int().map(|v| v as f32))?;
+                let index = control_inputs
+                    .iter()
+                    .find(|p| Some(p.symbol.as_str()) == symbol.as_str())?
+                    .index;
+                Some((index, value))
+            })
+            .collect();
+        Preset {
+            uri: preset.as_uri().unwrap_or("BAD_URI").to_string(),
+            name: world
+                .get(Some(preset), Some(&common_uris.rdfs_label_uri), None)
+                .and_then(|n| n.as_str().map(str::to_string))
+                .unwrap_or_default(),
+            port_values,
+            file_uri: world
+                .get(Some(preset), Some(&common_uris.rdfs_see_also_uri), None)
+                .and_then(|n| n.as_uri().map(str::to_string)),
+            world: world.clone(),
+        }
+    }
+
+    /// The values of the control input ports that the preset sets. Ports that
+    /// are not included are left unchanged when the preset is applied.
+    pub fn port_values(&self) -> &[(PortIndex, f32)] {
+        &self.port_values
+    }
+
+    /// Returns `true` if the preset was loaded from one of `dirs`.
+    pub(crate) fn is_in_dirs(&self, dirs: &[String]) -> bool {
+        match self.file_uri.as_ref() {
+            Some(uri) => dirs.iter().any(|dir| uri.starts_with(dir)),
+            None => false,
+        }
+    }
+
+    /// Restore the state that is stored in the preset, if any, on the plugin
+    /// behind `descriptor` and `handle`. Port values are not restored.
+    pub(crate) unsafe fn restore_state(
+        &self,
+        features: &Features,
+        descriptor: &lv2_raw::LV2Descriptor,
+        handle: lv2_raw::LV2Handle,
+        state_features: &[*const lv2_raw::LV2Feature],
+    ) {
+        let preset = self.world.new_uri(&self.uri);
+        let state = lilv_sys::lilv_state_new_from_world(
+            self.world.as_ptr(),
+            features.urid_map_ptr().cast(),
+            preset.as_ptr(),
+        );
+        if state.is_null() {
+            return;
+        }
+        if lilv_sys::lilv_state_get_num_properties(state) > 0 {
+            // lilv only needs the descriptor and handle of the instance.
+            let mut instance = lilv_sys::LilvInstanceImpl {
+                lv2_descriptor: (descriptor as *const lv2_raw::LV2Descriptor).cast(),
+                lv2_handle: handle,
+                pimpl: std::ptr::null_mut(),
+            };
+            lilv_sys::lilv_state_restore(
+                state,
+                &mut instance,
+                None,
+                std::ptr::null_mut(),
+                0,
+                state_features.as_ptr().cast(),
+            );
+        }
+        lilv_sys::lilv_state_free(state);
+    }
+}
+
+impl std::fmt::Debug for Preset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Preset")
+            .field("uri", &self.uri)
+            .field("name", &self.name)
+            .field("port_values", &self.port_values)
+            .finish()
+    }
+}
@@ -0,0 +1,267 @@
+use core::ffi::c_void;
+use lv2_sys::LV2_State_Flags;
+use std::convert::TryInto;
+
+use crate::error::StateError;
+use crate::event::LV2AtomEventWithData;
+use crate::Features;
+
+/// A single property of a plugin's state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateProperty {
+    /// The URID of the key that the property is stored under.
+    pub key: lv2_raw::LV2Urid,
+
+    /// The URID of the type of `value`.
+    pub value_type: lv2_raw::LV2Urid,
+
+    /// The `LV2_State_Flags` of the property.
+    pub flags: u32,
+
+    /// The raw bytes of the value.
+    pub value: Vec<u8>,
+}
+
+/// The state of a plugin instance as saved through `state:interface`. See
+/// http://lv2plug.in/ns/ext/state.
+///
+/// The keys and types are URIDs of the `Features` that the instance was created
+/// with, so the state can only be restored on instances that use the same
+/// `Features`. Use `Features::uri` to get the URIs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PluginState {
+    /// The properties in the order that the plugin stored them.
+    pub properties: Vec<StateProperty>,
+}
+
+impl PluginState {
+    /// Get the property that is stored under `key`.
+    pub fn get(&self, key: lv2_raw::LV2Urid) -> Option<&StateProperty> {
+        self.properties.iter().find(|p| p.key == key)
+    }
+
+    /// Save the state of the instance behind `handle`.
+    pub(crate) unsafe fn save(
+        interface: &lv2_sys::LV2_State_Interface,
+        handle: lv2_sys::LV2_Handle,
+        features: &[*const lv2_raw::LV2Feature],
+    ) -> Result<PluginState, StateError> {
+        let save = interface.save.ok_or(StateError::NotSupported)?;
+        let mut state = PluginState::default();
+        let status = save(
+            handle,
+            Some(store),
+            (&mut state as *mut PluginState).cast(),
+            flags(),
+            features.as_ptr().cast(),
+        );
+        match status {
+            lv2_sys::LV2_State_Status_LV2_STATE_SUCCESS => Ok(state),
+            status => Err(StateError::PluginError { status }),
+        }
+    }
+
+    /// Restore this state on the instance behind `handle`.
+    pub(crate) unsafe fn restore(
+        &self,
+        interface: &lv2_sys::LV2_State_Interface,
+        handle: lv2_sys::LV2_Handle,
+        features: &[*const lv2_raw::LV2Feature],
+    ) -> Result<(), StateError> {
+        let restore = interface.restore.ok_or(StateError::NotSupported)?;
+        let status = restore(
+            handle,
+            Some(retrieve),
+            (self as *const PluginState as *mut PluginState).cast(),
+            flags(),
+            features.as_ptr().cast(),
+        );
+        match status {
+            lv2_sys::LV2_State_Status_LV2_STATE_SUCCESS => Ok(()),
+            status => Err(StateError::PluginError { status }),
+        }
+    }
+}
+
+/// The flags that are passed to `save` and `restore`. The state is kept in
+/// memory as plain bytes, so only portable plain old data is accepted.
+fn flags() -> u32 {
+    (LV2_State_Flags::LV2_STATE_IS_POD | LV2_State_Flags::LV2_STATE_IS_PORTABLE).0
+}
+
+unsafe extern "C" fn store(
+    handle: lv2_sys::LV2_State_Handle,
+    key: u32,
+    value: *const c_void,
+    size: usize,
+    value_type: u32,
+    flags: u32,
+) -> lv2_sys::LV2_State_Status {
+    let state = match (handle as *mut PluginState).as_mut() {
+        Some(s) => s,
+        None => return lv2_sys::LV2_State_Status_LV2_STATE_ERR_UNKNOWN,
+    };
+    if value.is_null() && size > 0 {
+        return lv2_sys::LV2_State_Status_LV2_STATE_ERR_UNKNOWN;
+    }
+    let value = if size == 0 {
+        Vec::new()
+    } else {
+        std::slice::from_raw_parts(value.cast::<u8>(), size).to_vec()
+    };
+    let property = StateProperty {
+        key,
+        value_type,
+        flags,
+        value,
+    };
+    // Storing a key again replaces the old value.
+    match state.properties.iter_mut().find(|p| p.key == key) {
+        Some(p) => *p = property,
+        None => state.properties.push(property),
+    }
+    lv2_sys::LV2_State_Status_LV2_STATE_SUCCESS
+}
+
+unsafe extern "C" fn retrieve(
+    handle: lv2_sys::LV2_State_Handle,
+    key: u32,
+    size: *mut usize,
+    value_type: *mut u32,
+    flags: *mut u32,
+) -> *const c_void {
+    let property = match (handle as *const PluginState)
+        .as_ref()
+        .and_then(|s| s.get(key))
+    {
+        Some(p) => p,
+        None => return std::ptr::null(),
+    };
+    if let Some(size) = size.as_mut() {
+        *size = property.value.len();
+    }
+    if let Some(value_type) = value_type.as_mut() {
+        *value_type = property.value_type;
+    }
+    if let Some(flags) = flags.as_mut() {
+        *flags = property.flags;
+    }
+    property.value.as_ptr().cast()
+}
+
+/// The URIDs that are needed to detect `state:StateChanged` notifications.
+/// They are mapped ahead of time so that notifications can be detected in
+/// `run`.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct StateChangedUrids {
+    object: lv2_raw::LV2Urid,
+    blank: lv2_raw::LV2Urid,
+    state_changed: lv2_raw::LV2Urid,
+}
+
+impl StateChangedUrids {
+    pub(crate) fn new(features: &Features) -> StateChangedUrids {
+        let urids = features.common_urids();
+        StateChangedUrids {
+            object: urids.atom_object,
+            blank: urids.atom_blank,
+            state_changed: urids.state_changed,
+        }
+    }
+
+    /// Returns `true` if `event` is an object with the type
+    /// `state:StateChanged`.
+    pub(crate) fn is_state_changed(&self, event: &LV2AtomEventWithData) -> bool {
+        let mytype = event.event.body.mytype;
+        if mytype != self.object && mytype != self.blank {
+            return false;
+        }
+        // The object body starts with the object id followed by its type.
+        match event.data.get(4..8) {
+            Some(otype) => u32::from_ne_bytes(otype.try_into().unwrap()) == self.state_changed,
+            None => false,
+        }
+    }
+}
+
+// Not real-time safe.
+pub(crate) unsafe fn maybe_get_state_interface(
+    instance: &lilv::instance::ActiveInstance,
+) -> Option<lv2_sys::LV2_State_Interface> {
+    if !crate::plugin::has_extension_data_fn(instance.instance()) {
+        return None;
+    }
+    Some(
+        *instance
+            .instance()
+            .extension_data::<lv2_sys::LV2_State_Interface>(
+                "http://lv2plug.in/ns/ext/state#interface",
+            )?
+            .as_ref(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_and_retrieve() {
+        let mut state = PluginState::default();
+        let handle: lv2_sys::LV2_State_Handle = (&mut state as *mut PluginState).cast();
+        let first = [1u8, 2, 3];
+        let second = [4u8, 5];
+        unsafe {
+            assert_eq!(store(handle, 7, first.as_ptr().cast(), 3, 9, 1), 0);
+            assert_eq!(store(handle, 8, first.as_ptr().cast(), 3, 9, 1), 0);
+            assert_eq!(store(handle, 7, second.as_ptr().cast(), 2, 10, 3), 0);
+        }
+        assert_eq!(state.properties.len(), 2);
+
+        let handle: lv2_sys::LV2_State_Handle = (&mut state as *mut PluginState).cast();
+        let (mut size, mut value_type, mut flags) = (0, 0, 0);
+        let value = unsafe { retrieve(handle, 7, &mut size, &mut value_type, &mut flags) };
+        assert_eq!((size, value_type, flags), (2, 10, 3));
+        assert_eq!(
+            unsafe { std::slice::from_raw_parts(value.cast::<u8>(), size) },
+            &second
+        );
+        let missing = unsafe {
+            retrieve(
+                handle,
+                11,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+        assert!(missing.is_null());
+    }
+
+    #[test]
+    fn test_is_state_changed() {
+        let world = crate::World::new();
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let urids = StateChangedUrids::new(&features);
+        let common_urids = features.common_urids();
+        let object = common_urids.atom_object;
+        let state_changed = common_urids.state_changed;
+        let other = common_urids.patch_set;
+
+        let mut sequence = crate::event::LV2AtomSequence::new(&features, 1024);
+        for (mytype, otype) in [
+            (object, other),
+            (other, state_changed),
+            (object, state_changed),
+        ] {
+            let mut body = [0u8; 8];
+            body[4..].copy_from_slice(&otype.to_ne_bytes());
+            sequence.push_raw_atom(0, mytype, &body).unwrap();
+        }
+        let detected: Vec<bool> = sequence
+            .iter()
+            .map(|e| urids.is_state_changed(&e))
+            .collect();
+        assert_eq!(detected, [false, false, true]);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::error::EventError;
+use crate::event::LV2AtomSequence;
+use crate::Features;
+
+/// The size of the body of a `time:Position` object. The object body header is
+/// 8 bytes and each of the 9 properties takes 16 bytes for its header and 8
+/// bytes for its padded value.
+const POSITION_BODY_SIZE: usize = 8 + 9 * 24;
+
+/// The state of the host transport. This is sent to plugins as a `time:Position`
+/// object. See http://lv2plug.in/ns/ext/time.
+///
+/// The URIDs of the `time:Position` object and its properties are available
+/// from `Features::common_urids` for hosts that parse positions sent by
+/// plugins.
+///
+/// #### Example usage:
+/// ```
+/// # let world = livi::World::new();
+/// # let features = world.build_features(livi::FeaturesBuilder::default());
+/// let transport = livi::TransportState {
+///     speed: 1.0,
+///     beats_per_minute: 140.0,
+///     ..livi::TransportState::default()
+/// };
+/// let mut sequence = livi::event::LV2AtomSequence::new(&features, 1024);
+/// transport.push_position(&features, &mut sequence, 0).unwrap();
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TransportState {
+    /// The position in frames since the start of the timeline. This is
+    /// `time:frame`.
+    pub frame: i64,
+
+    /// The sample rate. This is `time:framesPerSecond`.
+    pub frames_per_second: f32,
+
+    /// The playback speed. `0.0` is stopped and `1.0` is playing at normal
+    /// speed. This is `time:speed`.
+    pub speed: f32,
+
+    /// The beat within the current bar, starting at `0.0`. This is
+    /// `time:barBeat`.
+    pub bar_beat: f32,
+
+    /// The current bar, starting at `0`. This is `time:bar`.
+    pub bar: i64,
+
+    /// The position in beats since the start of the timeline. This is
+    /// `time:beat`.
+    pub beat: f64,
+
+    /// The note value that counts as one beat, for example `4` for a quarter
+    /// note. This is `time:beatUnit`.
+    pub beat_unit: i32,
+
+    /// The number of beats in a bar. This is `time:beatsPerBar`.
+    pub beats_per_bar: f32,
+
+    /// The tempo. This is `time:beatsPerMinute`.
+    pub beats_per_minute: f32,
+}
+
+impl Default for TransportState {
+    /// A stopped transport at the start of the timeline in 4/4 at 120 beats per
+    /// minute and 44100 frames per second.
+    fn default() -> TransportState {
+        TransportState {
+            frame: 0,
+            frames_per_second: 44100.0,
+            speed: 0.0,
+            bar_beat: 0.0,
+            bar: 0,
+            beat: 0.0,
+            beat_unit: 4,
+            beats_per_bar: 4.0,
+            beats_per_minute: 120.0,
+        }
+    }
+}
+
+impl TransportState {
+    /// Append the transport state to `sequence` as a `time:Position` object
+    /// with all of its fields set. This does not allocate or map URIs so it may
+    /// be called from the realtime thread.
+    ///
+    /// # Errors
+    /// Returns an error if the sequence does not have the capacity for the
+    /// event.
+    pub fn push_position(
+        &self,
+        features: &Features,
+        sequence: &mut LV2AtomSequence,
+        time_in_frames: i64,
+    ) -> Result<(), EventError> {
+        let urids = features.common_urids();
+        let long = urids.atom_long;
+        let int = urids.atom_int;
+        let float = urids.atom_float;
+        let double = urids.atom_double;
+
+        let mut body = [0u8; POSITION_BODY_SIZE];
+        let mut writer = Writer {
+            body: &mut body,
+            offset: 0,
+        };
+        // The object id is 0 for a blank object.
+        writer.write(&0u32.to_ne_bytes());
+        writer.write(&urids.time_position.to_ne_bytes());
+        let properties: [(lv2_raw::LV2Urid, u32, [u8; 8], usize); 9] = [
+            (urids.time_frame, long, self.frame.to_ne_bytes(), 8),
+            (
+                urids.time_frames_per_second,
+                float,
+                pad(self.frames_per_second.to_ne_bytes()),
+                4,
+            ),
+            (urids.time_speed, float, pad(self.speed.to_ne_bytes()), 4),
+            (
+                urids.time_bar_beat,
+                float,
+                pad(self.bar_beat.to_ne_bytes()),
+                4,
+            ),
+            (urids.time_bar, long, self.bar.to_ne_bytes(), 8),
+            (urids.time_beat, double, self.beat.to_ne_bytes(), 8),
+            (
+                urids.time_beat_unit,
+                int,
+                pad(self.beat_unit.to_ne_bytes()),
+                4,
+            ),
+            (
+                urids.time_beats_per_bar,
+                float,
+                pad(self.beats_per_bar.to_ne_bytes()),
+                4,
+            ),
+            (
+                urids.time_beats_per_minute,
+                float,
+                pad(self.beats_per_minute.to_ne_bytes()),
+                4,
+            ),
+        ];
+        for (key, value_type, value, value_size) in properties.iter() {
+            writer.write(&key.to_ne_bytes());
+            // The context is unused and must be 0.
+            writer.write(&0u32.to_ne_bytes());
+            writer.write(&(*value_size as u32).to_ne_bytes());
+            writer.write(&value_type.to_ne_bytes());
+            writer.write(value);
+        }
+        debug_assert_eq!(writer.offset, POSITION_BODY_SIZE);
+        sequence.push_raw_atom(time_in_frames, urids.atom_object, &body)
+    }
+}
+
+/// Pad a 4 byte value to the 8 byte alignment of atoms.
+fn pad(value: [u8; 4]) -> [u8; 8] {
+    [value[0], value[1], value[2], value[3], 0, 0, 0, 0]
+}
+
+struct Writer<'a> {
+    body: &'a mut [u8],
+    offset: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn write(&mut self, data: &[u8]) {
+        self.body[self.offset..self.offset + data.len()].copy_from_slice(data);
+        self.offset += data.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    #[test]
+    fn test_push_position_writes_all_fields() {
+        let world = crate::World::new();
+        let features = world.build_features(crate::features::FeaturesBuilder::default());
+        let mut sequence = LV2AtomSequence::new(&features, 1024);
+        let transport = TransportState {
+            frame: 48000,
+            speed: 1.0,
+            beats_per_minute: 90.0,
+            ..TransportState::default()
+        };
+        transport
+            .push_position(&features, &mut sequence, 3)
+            .unwrap();
+
+        let event = sequence.iter().next().unwrap();
+        assert_eq!(event.event.time_in_frames, 3);
+        assert_eq!(
+            features.uri(event.event.body.mytype),
+            Some("http://lv2plug.in/ns/ext/atom#Object")
+        );
+        assert_eq!(event.data.len(), POSITION_BODY_SIZE);
+        let u32_at = |offset: usize| {
+            u32::from_ne_bytes([
+                event.data[offset],
+                event.data[offset + 1],
+                event.data[offset + 2],
+                event.data[offset + 3],
+            ])
+        };
+        assert_eq!(
+            features.uri(u32_at(4)),
+            Some("http://lv2plug.in/ns/ext/time#Position")
+        );
+        let keys: Vec<&str> = (0..9)
+            .map(|i| features.uri(u32_at(8 + i * 24)).unwrap())
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                "http://lv2plug.in/ns/ext/time#frame",
+                "http://lv2plug.in/ns/ext/time#framesPerSecond",
+                "http://lv2plug.in/ns/ext/time#speed",
+                "http://lv2plug.in/ns/ext/time#barBeat",
+                "http://lv2plug.in/ns/ext/time#bar",
+                "http://lv2plug.in/ns/ext/time#beat",
+                "http://lv2plug.in/ns/ext/time#beatUnit",
+                "http://lv2plug.in/ns/ext/time#beatsPerBar",
+                "http://lv2plug.in/ns/ext/time#beatsPerMinute",
+            ]
+        );
+        let frame = &event.data[8 + 16..8 + 24];
+        assert_eq!(i64::from_ne_bytes(frame.try_into().unwrap()), 48000);
+        let bpm = &event.data[8 + 8 * 24 + 16..8 + 8 * 24 + 20];
+        assert_eq!(f32::from_ne_bytes(bpm.try_into().unwrap()), 90.0);
+    }
+}
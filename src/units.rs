@@ -0,0 +1,291 @@
+/// The unit of a port as declared by `units:unit`.
+/// See http://lv2plug.in/ns/extensions/units.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PortUnit {
+    /// The URI of the unit, for example `http://lv2plug.in/ns/extensions/units#db`.
+    /// This is `None` for custom units that are declared inline by the plugin.
+    pub uri: Option<String>,
+
+    /// A printf format string for displaying a value in the unit, as declared
+    /// by `units:render`. For example, `%.1f dB`.
+    pub render: Option<String>,
+
+    /// The abbreviated symbol of the unit, as declared by `units:symbol`. For
+    /// example, `dB` or `Hz`.
+    pub symbol: Option<String>,
+
+    /// The name of the unit, as declared by `rdfs:label`. For example,
+    /// `decibels` or `hertz`.
+    pub label: Option<String>,
+}
+
+impl PortUnit {
+    /// Read the unit from the `unit` node of a port. The symbol and label of
+    /// the units in the LV2 units extension are known even if its data is not
+    /// installed.
+    pub(crate) fn from_node(
+        world: &lilv::World,
+        common_uris: &crate::CommonUris,
+        unit: &lilv::node::Node,
+    ) -> PortUnit {
+        let get = |predicate: &lilv::node::Node| {
+            world
+                .get(Some(unit), Some(predicate), None)
+                .and_then(|n| n.as_str().map(str::to_string))
+        };
+        let uri = unit.as_uri().map(str::to_string);
+        let builtin = uri.as_deref().and_then(builtin_unit);
+        PortUnit {
+            render: get(&common_uris.units_render_uri),
+            symbol: get(&common_uris.units_symbol_uri)
+                .or_else(|| builtin.map(|(_, symbol)| symbol.to_string())),
+            label: get(&common_uris.rdfs_label_uri)
+                .or_else(|| builtin.map(|(label, _)| label.to_string())),
+            uri,
+        }
+    }
+
+    /// Format `value` with the `render` string of the unit. If there is no
+    /// `render` string, the value is followed by the unit's symbol, for example
+    /// `440 Hz`. Returns `None` if there is neither or if the `render` string
+    /// is not supported.
+    pub fn format_value(&self, value: f32) -> Option<String> {
+        match (&self.render, &self.symbol) {
+            (Some(render), _) => format_printf(render, value),
+            (None, Some(symbol)) if !symbol.is_empty() => Some(format!("{} {}", value, symbol)),
+            _ => None,
+        }
+    }
+}
+
+/// The label and symbol of a unit of the LV2 units extension.
+fn builtin_unit(uri: &str) -> Option<(&'static str, &'static str)> {
+    let unit = match uri.strip_prefix("http://lv2plug.in/ns/extensions/units#")? {
+        "bar" => ("bars", "bars"),
+        "beat" => ("beats", "beats"),
+        "bpm" => ("beats per minute", "BPM"),
+        "cent" => ("cents", "ct"),
+        "cm" => ("centimetres", "cm"),
+        "coef" => ("coefficient", ""),
+        "db" => ("decibels", "dB"),
+        "degree" => ("degrees", "deg"),
+        "frame" => ("audio frames", "frames"),
+        "hz" => ("hertz", "Hz"),
+        "inch" => ("inches", "in"),
+        "khz" => ("kilohertz", "kHz"),
+        "km" => ("kilometres", "km"),
+        "m" => ("metres", "m"),
+        "mhz" => ("megahertz", "MHz"),
+        "midiNote" => ("MIDI note", "note"),
+        "mile" => ("miles", "mi"),
+        "min" => ("minutes", "min"),
+        "mm" => ("millimetres", "mm"),
+        "ms" => ("milliseconds", "ms"),
+        "oct" => ("octaves", "oct"),
+        "pc" => ("percent", "%"),
+        "s" => ("seconds", "s"),
+        "semitone12TET" => ("semitones", "semi"),
+        _ => return None,
+    };
+    Some(unit)
+}
+
+/// Format `value` with a printf format string. Only a safe subset of printf is
+/// supported: a single `%d`, `%i`, `%f`, `%e`, or `%g` conversion with
+/// optional flags, width, and precision, and `%%`. Returns `None` if `format`
+/// contains anything else.
+pub(crate) fn format_printf(format: &str, value: f32) -> Option<String> {
+    let value = f64::from(value);
+    let mut out = String::with_capacity(format.len() + 8);
+    let mut chars = format.chars().peekable();
+    let mut converted = false;
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        if chars.peek() == Some(&'%') {
+            chars.next();
+            out.push('%');
+            continue;
+        }
+        if converted {
+            return None;
+        }
+        converted = true;
+
+        let mut spec = Spec::default();
+        while let Some(&flag) = chars.peek() {
+            match flag {
+                '-' => spec.left_align = true,
+                '+' => spec.plus_sign = true,
+                ' ' => spec.space_sign = true,
+                '0' => spec.zero_pad = true,
+                '#' => spec.alternate = true,
+                _ => break,
+            }
+            chars.next();
+        }
+        spec.width = read_number(&mut chars).unwrap_or(0);
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            spec.precision = Some(read_number(&mut chars).unwrap_or(0));
+        }
+        // Length modifiers do not matter since the value is always converted.
+        while let Some('l' | 'h' | 'L') = chars.peek() {
+            chars.next();
+        }
+        let body = match chars.next()? {
+            'd' | 'i' | 'u' => format!("{}", value.round().abs() as i64),
+            'f' | 'F' => format!("{:.*}", spec.precision.unwrap_or(6), value.abs()),
+            'e' => format_exponent(value.abs(), spec.precision.unwrap_or(6)),
+            'g' => format_general(value.abs(), spec.precision.unwrap_or(6), spec.alternate),
+            _ => return None,
+        };
+        out.push_str(&spec.pad(value.is_sign_negative() && value != 0.0, body));
+    }
+    Some(out)
+}
+
+#[derive(Default)]
+struct Spec {
+    left_align: bool,
+    plus_sign: bool,
+    space_sign: bool,
+    zero_pad: bool,
+    alternate: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl Spec {
+    /// Add the sign and padding to the formatted absolute value.
+    fn pad(&self, negative: bool, body: String) -> String {
+        let sign = if negative {
+            "-"
+        } else if self.plus_sign {
+            "+"
+        } else if self.space_sign {
+            " "
+        } else {
+            ""
+        };
+        let len = sign.len() + body.len();
+        let padding = self.width.saturating_sub(len);
+        if self.left_align {
+            format!("{}{}{}", sign, body, " ".repeat(padding))
+        } else if self.zero_pad {
+            format!("{}{}{}", sign, "0".repeat(padding), body)
+        } else {
+            format!("{}{}{}", " ".repeat(padding), sign, body)
+        }
+    }
+}
+
+fn read_number(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<usize> {
+    let mut number = None;
+    while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+        chars.next();
+        number = Some(number.unwrap_or(0) * 10 + d as usize);
+    }
+    number
+}
+
+/// Format like printf's `%e`, for example `1.500000e+03`.
+fn format_exponent(value: f64, precision: usize) -> String {
+    let formatted = format!("{:.*e}", precision, value);
+    let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let sign = if exponent < 0 { '-' } else { '+' };
+    format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+}
+
+/// Format like printf's `%g`. This uses `%e` for very small and large values
+/// and `%f` otherwise. Trailing zeros are removed unless `alternate` is set.
+fn format_general(value: f64, precision: usize, alternate: bool) -> String {
+    let precision = precision.max(1);
+    let exponent = if value == 0.0 {
+        0
+    } else {
+        // Round to the precision first since it may change the exponent.
+        let rounded = format!("{:.*e}", precision - 1, value);
+        rounded
+            .split_once('e')
+            .and_then(|(_, e)| e.parse::<i32>().ok())
+            .unwrap_or(0)
+    };
+    let formatted = if exponent < -4 || exponent >= precision as i32 {
+        format_exponent(value, precision - 1)
+    } else {
+        format!("{:.*}", (precision as i32 - 1 - exponent) as usize, value)
+    };
+    if alternate {
+        return formatted;
+    }
+    let (number, exponent) = match formatted.find('e') {
+        Some(idx) => formatted.split_at(idx),
+        None => (formatted.as_str(), ""),
+    };
+    let number = if number.contains('.') {
+        number.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        number
+    };
+    format!("{}{}", number, exponent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_printf() {
+        assert_eq!(format_printf("%.1f dB", -6.0).unwrap(), "-6.0 dB");
+        assert_eq!(format_printf("%f", 0.5).unwrap(), "0.500000");
+        assert_eq!(format_printf("%d Hz", 440.4).unwrap(), "440 Hz");
+        assert_eq!(format_printf("%+d", 3.0).unwrap(), "+3");
+        assert_eq!(format_printf("%5.1f%%", 12.34).unwrap(), " 12.3%");
+        assert_eq!(format_printf("%-4d|", 7.0).unwrap(), "7   |");
+        assert_eq!(format_printf("%03d", -7.0).unwrap(), "-07");
+        assert_eq!(format_printf("%g s", 0.25).unwrap(), "0.25 s");
+        assert_eq!(format_printf("%g", 1_000_000.0).unwrap(), "1e+06");
+        assert_eq!(format_printf("%g", 0.00001).unwrap(), "1e-05");
+        assert_eq!(format_printf("%e", 1500.0).unwrap(), "1.500000e+03");
+    }
+
+    #[test]
+    fn test_builtin_units() {
+        assert_eq!(
+            builtin_unit("http://lv2plug.in/ns/extensions/units#hz"),
+            Some(("hertz", "Hz"))
+        );
+        assert_eq!(
+            builtin_unit("http://lv2plug.in/ns/extensions/units#db"),
+            Some(("decibels", "dB"))
+        );
+        assert_eq!(builtin_unit("http://example.com/units#hz"), None);
+    }
+
+    #[test]
+    fn test_format_value_falls_back_to_symbol() {
+        let mut unit = PortUnit {
+            uri: Some("http://lv2plug.in/ns/extensions/units#hz".to_string()),
+            render: None,
+            symbol: Some("Hz".to_string()),
+            label: Some("hertz".to_string()),
+        };
+        assert_eq!(unit.format_value(440.0).unwrap(), "440 Hz");
+        unit.render = Some("%.1f Hz".to_string());
+        assert_eq!(unit.format_value(440.0).unwrap(), "440.0 Hz");
+        unit.render = None;
+        unit.symbol = None;
+        assert_eq!(unit.format_value(440.0), None);
+    }
+
+    #[test]
+    fn test_format_printf_rejects_unsupported_formats() {
+        assert_eq!(format_printf("%s", 1.0), None);
+        assert_eq!(format_printf("%f %f", 1.0), None);
+        assert_eq!(format_printf("%", 1.0), None);
+    }
+}
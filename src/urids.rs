@@ -0,0 +1,140 @@
+use std::ffi::CStr;
+
+use crate::features::urid_map::UridMap;
+
+/// The URIDs of URIs that hosts commonly need. They are mapped once when the
+/// `Features` are built and are obtained with `Features::common_urids`.
+///
+/// #### Example usage:
+/// ```
+/// # let world = livi::World::new();
+/// # let features = world.build_features(livi::FeaturesBuilder::default());
+/// let urids = features.common_urids();
+/// assert_eq!(
+///     features.uri(urids.midi_event),
+///     Some("http://lv2plug.in/ns/ext/midi#MidiEvent")
+/// );
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CommonUrids {
+    /// `midi:MidiEvent`
+    pub midi_event: lv2_raw::LV2Urid,
+    /// `atom:Blank`
+    pub atom_blank: lv2_raw::LV2Urid,
+    /// `atom:Bool`
+    pub atom_bool: lv2_raw::LV2Urid,
+    /// `atom:Chunk`
+    pub atom_chunk: lv2_raw::LV2Urid,
+    /// `atom:Double`
+    pub atom_double: lv2_raw::LV2Urid,
+    /// `atom:Float`
+    pub atom_float: lv2_raw::LV2Urid,
+    /// `atom:Int`
+    pub atom_int: lv2_raw::LV2Urid,
+    /// `atom:Long`
+    pub atom_long: lv2_raw::LV2Urid,
+    /// `atom:Object`
+    pub atom_object: lv2_raw::LV2Urid,
+    /// `atom:Path`
+    pub atom_path: lv2_raw::LV2Urid,
+    /// `atom:Sequence`
+    pub atom_sequence: lv2_raw::LV2Urid,
+    /// `atom:String`
+    pub atom_string: lv2_raw::LV2Urid,
+    /// `atom:URID`
+    pub atom_urid: lv2_raw::LV2Urid,
+    /// `atom:Vector`
+    pub atom_vector: lv2_raw::LV2Urid,
+    /// `patch:Get`
+    pub patch_get: lv2_raw::LV2Urid,
+    /// `patch:Set`
+    pub patch_set: lv2_raw::LV2Urid,
+    /// `patch:property`
+    pub patch_property: lv2_raw::LV2Urid,
+    /// `patch:value`
+    pub patch_value: lv2_raw::LV2Urid,
+    /// `state:StateChanged`
+    pub state_changed: lv2_raw::LV2Urid,
+    /// `time:Position`
+    pub time_position: lv2_raw::LV2Urid,
+    /// `time:bar`
+    pub time_bar: lv2_raw::LV2Urid,
+    /// `time:barBeat`
+    pub time_bar_beat: lv2_raw::LV2Urid,
+    /// `time:beat`
+    pub time_beat: lv2_raw::LV2Urid,
+    /// `time:beatUnit`
+    pub time_beat_unit: lv2_raw::LV2Urid,
+    /// `time:beatsPerBar`
+    pub time_beats_per_bar: lv2_raw::LV2Urid,
+    /// `time:beatsPerMinute`
+    pub time_beats_per_minute: lv2_raw::LV2Urid,
+    /// `time:frame`
+    pub time_frame: lv2_raw::LV2Urid,
+    /// `time:framesPerSecond`
+    pub time_frames_per_second: lv2_raw::LV2Urid,
+    /// `time:speed`
+    pub time_speed: lv2_raw::LV2Urid,
+}
+
+impl CommonUrids {
+    pub(crate) fn new(urid_map: &UridMap) -> CommonUrids {
+        let urid = |uri: &[u8]| urid_map.map(CStr::from_bytes_with_nul(uri).unwrap());
+        CommonUrids {
+            midi_event: urid(b"http://lv2plug.in/ns/ext/midi#MidiEvent\0"),
+            atom_blank: urid(b"http://lv2plug.in/ns/ext/atom#Blank\0"),
+            atom_bool: urid(b"http://lv2plug.in/ns/ext/atom#Bool\0"),
+            atom_chunk: urid(b"http://lv2plug.in/ns/ext/atom#Chunk\0"),
+            atom_double: urid(b"http://lv2plug.in/ns/ext/atom#Double\0"),
+            atom_float: urid(b"http://lv2plug.in/ns/ext/atom#Float\0"),
+            atom_int: urid(b"http://lv2plug.in/ns/ext/atom#Int\0"),
+            atom_long: urid(b"http://lv2plug.in/ns/ext/atom#Long\0"),
+            atom_object: urid(b"http://lv2plug.in/ns/ext/atom#Object\0"),
+            atom_path: urid(b"http://lv2plug.in/ns/ext/atom#Path\0"),
+            atom_sequence: urid(b"http://lv2plug.in/ns/ext/atom#Sequence\0"),
+            atom_string: urid(b"http://lv2plug.in/ns/ext/atom#String\0"),
+            atom_urid: urid(b"http://lv2plug.in/ns/ext/atom#URID\0"),
+            atom_vector: urid(b"http://lv2plug.in/ns/ext/atom#Vector\0"),
+            patch_get: urid(b"http://lv2plug.in/ns/ext/patch#Get\0"),
+            patch_set: urid(b"http://lv2plug.in/ns/ext/patch#Set\0"),
+            patch_property: urid(b"http://lv2plug.in/ns/ext/patch#property\0"),
+            patch_value: urid(b"http://lv2plug.in/ns/ext/patch#value\0"),
+            state_changed: urid(b"http://lv2plug.in/ns/ext/state#StateChanged\0"),
+            time_position: urid(b"http://lv2plug.in/ns/ext/time#Position\0"),
+            time_bar: urid(b"http://lv2plug.in/ns/ext/time#bar\0"),
+            time_bar_beat: urid(b"http://lv2plug.in/ns/ext/time#barBeat\0"),
+            time_beat: urid(b"http://lv2plug.in/ns/ext/time#beat\0"),
+            time_beat_unit: urid(b"http://lv2plug.in/ns/ext/time#beatUnit\0"),
+            time_beats_per_bar: urid(b"http://lv2plug.in/ns/ext/time#beatsPerBar\0"),
+            time_beats_per_minute: urid(b"http://lv2plug.in/ns/ext/time#beatsPerMinute\0"),
+            time_frame: urid(b"http://lv2plug.in/ns/ext/time#frame\0"),
+            time_frames_per_second: urid(b"http://lv2plug.in/ns/ext/time#framesPerSecond\0"),
+            time_speed: urid(b"http://lv2plug.in/ns/ext/time#speed\0"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_common_urids_match_urid_map() {
+        let urid_map = UridMap::new();
+        let urids = CommonUrids::new(&urid_map);
+        let urid = |uri: &[u8]| urid_map.map(CStr::from_bytes_with_nul(uri).unwrap());
+        assert_eq!(
+            urids.atom_float,
+            urid(b"http://lv2plug.in/ns/ext/atom#Float\0")
+        );
+        assert_eq!(
+            urids.patch_set,
+            urid(b"http://lv2plug.in/ns/ext/patch#Set\0")
+        );
+        assert_eq!(
+            urid_map.unmap(urids.time_position),
+            Some("http://lv2plug.in/ns/ext/time#Position")
+        );
+        assert_ne!(urids.atom_object, urids.atom_blank);
+    }
+}
@@ -34,10 +34,11 @@
 // the instance.run method will usually be called in the realtime
 // thread while the worker will be run in a non-realtime thread.
 
-use livi::event::{LV2AtomEventBuilder, LV2AtomSequence};
+use livi::event::{LV2AtomEventBuilder, LV2AtomObjectBuilder, LV2AtomSequence};
 use livi::{EmptyPortConnections, Features, Instance, World};
 use std::ffi::CStr;
 use std::mem::size_of;
+use std::sync::Arc;
 use tempfile::NamedTempFile;
 
 const MIN_BLOCK_SIZE: usize = 1;
@@ -154,6 +155,49 @@ fn build_sampler_message(features: &Features, sample_filepath: &str) -> SetSampl
     )
 }
 
+// Build the same message as `build_sampler_message` with
+// `LV2AtomObjectBuilder` and push it into a sequence.
+fn build_sampler_object_message(features: &Features, sample_filepath: &str) -> LV2AtomSequence {
+    let urids = features.common_urids();
+    let eg_sample_urid = features
+        .urid(CStr::from_bytes_with_nul(b"http://lv2plug.in/plugins/eg-sampler#sample\0").unwrap());
+    let mut message = LV2AtomObjectBuilder::new(&urids, urids.patch_set);
+    message
+        .push_path(urids.patch_value, sample_filepath)
+        .push_urid(urids.patch_property, eg_sample_urid);
+
+    let mut sequence = LV2AtomSequence::new(features, 1024);
+    sequence.push_object(0, &message).unwrap();
+    sequence
+}
+
+// Write a sample to disk and instantiate eg-sampler. The worker thread is not
+// run, so work is only done when the test calls `run_workers`.
+fn instantiate_sampler_without_worker_thread() -> (NamedTempFile, Arc<Features>, Instance) {
+    let cwd = std::env::current_dir().unwrap();
+    let mut out_file = NamedTempFile::new_in(cwd).unwrap();
+    let sample = wav::bit_depth::BitDepth::ThirtyTwoFloat(vec![1.0; MAX_BLOCK_SIZE]);
+    let header = wav::Header::new(wav::header::WAV_FORMAT_PCM, 1, SAMPLE_RATE as u32, 32);
+    wav::write(header, &sample, &mut out_file).unwrap();
+
+    let world = World::with_load_bundle("file:///usr/lib/lv2/eg-sampler.lv2/");
+    let plugin = world
+        .plugin_by_uri("http://lv2plug.in/plugins/eg-sampler")
+        .expect("Plugin not found.");
+    let features = world.build_features(livi::FeaturesBuilder {
+        min_block_length: MIN_BLOCK_SIZE,
+        max_block_length: MAX_BLOCK_SIZE,
+        run_worker_thread: false,
+        ..livi::FeaturesBuilder::default()
+    });
+    let instance = unsafe {
+        plugin
+            .instantiate(features.clone(), SAMPLE_RATE)
+            .expect("Could not instantiate plugin.")
+    };
+    (out_file, features, instance)
+}
+
 fn assert_silence(buffers: [Vec<f32>; 1]) {
     for buffer in buffers {
         for sample in buffer {
@@ -185,6 +229,7 @@ fn test_sampler() {
     let features = world.build_features(livi::FeaturesBuilder {
         min_block_length: MIN_BLOCK_SIZE,
         max_block_length: MAX_BLOCK_SIZE,
+        ..livi::FeaturesBuilder::default()
     });
     let mut instance = unsafe {
         plugin
@@ -223,3 +268,57 @@ fn test_sampler() {
     // correctly by the worker.
     assert_not_silence(outputs);
 }
+
+#[test]
+fn test_sampler_with_object_builder() {
+    let (out_file, features, mut instance) = instantiate_sampler_without_worker_thread();
+
+    let input = build_sampler_object_message(&features, out_file.path().to_str().unwrap());
+    let outputs = run_instance_with_input_sequence(&mut instance, &features, input);
+    assert_silence(outputs);
+
+    features.worker_manager().run_workers();
+    let outputs = run_instance_with_single_midi_note_input(&mut instance, &features);
+    assert_silence(outputs);
+
+    let outputs = run_instance_with_single_midi_note_input(&mut instance, &features);
+    assert_not_silence(outputs);
+}
+
+#[test]
+fn test_drop_instance_with_pending_work() {
+    let (out_file, features, mut instance) = instantiate_sampler_without_worker_thread();
+
+    // Schedule work to load the sample, but drop the instance before the
+    // worker has a chance to handle it.
+    let input = build_sampler_object_message(&features, out_file.path().to_str().unwrap());
+    run_instance_with_input_sequence(&mut instance, &features, input);
+    assert!(features.worker_manager().has_pending_work());
+    drop(instance);
+
+    // The pending message must be discarded without being handled by the
+    // dropped instance.
+    features.worker_manager().run_workers();
+    assert!(!features.worker_manager().has_pending_work());
+    assert_eq!(features.worker_manager().workers_count(), 0);
+}
+
+#[test]
+fn test_handle_worker_responses_manually() {
+    let (out_file, features, mut instance) = instantiate_sampler_without_worker_thread();
+    instance.set_auto_handle_worker_responses(false);
+
+    let input = build_sampler_object_message(&features, out_file.path().to_str().unwrap());
+    run_instance_with_input_sequence(&mut instance, &features, input);
+    features.worker_manager().run_workers();
+
+    // The loaded sample is not swapped in until the responses are handled.
+    for _ in 0..2 {
+        let outputs = run_instance_with_single_midi_note_input(&mut instance, &features);
+        assert_silence(outputs);
+    }
+
+    unsafe { instance.handle_worker_responses() };
+    let outputs = run_instance_with_single_midi_note_input(&mut instance, &features);
+    assert_not_silence(outputs);
+}